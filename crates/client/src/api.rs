@@ -0,0 +1,257 @@
+//! Functions to send HTTP request to the API endpoints supported by the v1 API.
+//! Each call builds a one-off [ShcsClient], keep a client around instead to
+//! reuse its connections.
+use crate::ShcsClient;
+
+#[derive(Debug)]
+pub enum Error {
+  Serde(serde_json::Error),
+  Reqwest(reqwest::Error),
+
+  /// The item or the bucket doesn't exist, the server answered 404
+  NotFound(shcs_types::Problem),
+
+  /// The credentials are missing or were refused, the server answered 401
+  Unauthorized(shcs_types::Problem),
+
+  /// The request conflicts with the current state of the storage: the bucket
+  /// already exists or isn't empty, or the metadata changed since the given
+  /// revision. The server answered 409 or 412.
+  Conflict(shcs_types::Problem),
+
+  /// The server answered with another unexpected status
+  UnhandledStatus(shcs_types::Problem),
+  InvalidUrl,
+
+  /// The credentials provider couldn't give an `Authorization` header
+  Credentials(String),
+
+  /// The server answered with a storage path that couldn't be parsed
+  InvalidStoragePath(shcs_types::ParseError),
+
+  /// The file or the writer of a transfer failed
+  Io(std::io::Error),
+
+  /// The item was replaced while its interrupted download was resumed
+  ContentChanged,
+}
+
+impl Error {
+  /// The error of a response with an unexpected `status`, its `body` is the
+  /// problem the server answered or the text of a proxy in between
+  pub(crate) fn from_response(status: reqwest::StatusCode, body: &str) -> Self {
+    use reqwest::StatusCode;
+
+    let problem = serde_json::from_str(body).unwrap_or_else(|_| shcs_types::Problem {
+      status: status.as_u16(),
+      title: status.canonical_reason().unwrap_or_default().to_owned(),
+      detail: Some(body.to_owned()).filter(|body| !body.is_empty()),
+    });
+
+    match status {
+      StatusCode::NOT_FOUND => Self::NotFound(problem),
+      StatusCode::UNAUTHORIZED => Self::Unauthorized(problem),
+      StatusCode::CONFLICT | StatusCode::PRECONDITION_FAILED => Self::Conflict(problem),
+      _ => Self::UnhandledStatus(problem),
+    }
+  }
+
+  /// The problem the server answered with, for the errors of a response
+  pub fn problem(&self) -> Option<&shcs_types::Problem> {
+    match self {
+      Self::NotFound(problem)
+      | Self::Unauthorized(problem)
+      | Self::Conflict(problem)
+      | Self::UnhandledStatus(problem) => Some(problem),
+      _ => None,
+    }
+  }
+}
+
+impl std::fmt::Display for Error {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Self::Serde(e) => write!(f, "invalid JSON: {e}"),
+      Self::Reqwest(e) => write!(f, "request failed: {e}"),
+      Self::NotFound(problem)
+      | Self::Unauthorized(problem)
+      | Self::Conflict(problem)
+      | Self::UnhandledStatus(problem) => write!(f, "{problem}"),
+      Self::InvalidUrl => write!(f, "invalid url"),
+      Self::Credentials(reason) => write!(f, "no credentials: {reason}"),
+      Self::InvalidStoragePath(e) => write!(f, "the server answered an {e}"),
+      Self::Io(e) => write!(f, "io error: {e}"),
+      Self::ContentChanged => write!(f, "the item changed while its download was resumed"),
+    }
+  }
+}
+
+impl std::error::Error for Error {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      Self::Serde(e) => Some(e),
+      Self::Reqwest(e) => Some(e),
+      Self::InvalidStoragePath(e) => Some(e),
+      Self::Io(e) => Some(e),
+      _ => None,
+    }
+  }
+}
+
+impl From<std::io::Error> for Error {
+  fn from(value: std::io::Error) -> Self {
+    Self::Io(value)
+  }
+}
+
+impl From<serde_json::Error> for Error {
+  fn from(value: serde_json::Error) -> Self {
+    Self::Serde(value)
+  }
+}
+
+impl From<reqwest::Error> for Error {
+  fn from(value: reqwest::Error) -> Self {
+    Self::Reqwest(value)
+  }
+}
+
+fn client(domain: &str, authorization: String) -> Result<ShcsClient, Error> {
+  ShcsClient::builder(domain)
+    .credentials(authorization)
+    .build()
+}
+
+/// Upload the file and get the storage path in return
+pub async fn upload_file(
+  domain: &str, authorization: String, file: Vec<u8>, filename: Option<String>,
+  metadata: Option<impl serde::Serialize>,
+) -> Result<shcs_types::StoragePath, Error> {
+  client(domain, authorization)?
+    .upload_file(file, filename, metadata)
+    .await
+}
+
+/// Upload a ZIP or tar archive that the server expands into one file per entry,
+/// and get the storage path of every entry in return
+pub async fn upload_archive(
+  domain: &str, authorization: String, archive: Vec<u8>, metadata: Option<impl serde::Serialize>,
+) -> Result<Vec<shcs_types::ExtractedItem>, Error> {
+  client(domain, authorization)?
+    .upload_archive(archive, metadata)
+    .await
+}
+
+/// Replace the file at the provided storage path
+pub async fn replace_file(
+  domain: &str, authorization: String, file: Vec<u8>, filename: Option<String>,
+  metadata: Option<impl serde::Serialize>, storage_path: &shcs_types::StoragePath,
+) -> Result<shcs_types::StoragePath, Error> {
+  client(domain, authorization)?
+    .replace_file(file, filename, metadata, storage_path)
+    .await
+}
+
+/// This function is lower level than the other **C**R**UD** functions as it
+/// returns a raw `Response` object directly. This allows the response to be used
+/// in proxy functions
+pub async fn get_file(
+  client: &reqwest::Client, domain: &str, storage_path: &shcs_types::StoragePath,
+) -> Result<reqwest::Response, Error> {
+  ShcsClient::builder(domain)
+    .http_client(client.clone())
+    .build()?
+    .get_file(storage_path)
+    .await
+}
+
+pub async fn get_metadata<Out>(
+  domain: &str, authorization: String, storage_path: &shcs_types::StoragePath,
+) -> Result<Out, Error>
+where
+  Out: serde::de::DeserializeOwned,
+{
+  client(domain, authorization)?
+    .get_metadata(storage_path)
+    .await
+}
+
+/// Apply the JSON merge `patch` to the alias and custom metadata of the file,
+/// and get the patched metadata with its new revision in return. When a
+/// `revision` is given the patch is refused if the metadata changed since.
+pub async fn patch_metadata<Out>(
+  domain: &str, authorization: String, storage_path: &shcs_types::StoragePath,
+  patch: &impl serde::Serialize, revision: Option<u64>,
+) -> Result<(Out, u64), Error>
+where
+  Out: serde::de::DeserializeOwned,
+{
+  client(domain, authorization)?
+    .patch_metadata(storage_path, patch, revision)
+    .await
+}
+
+/// Get the alias, system and custom metadata of the file in one request
+pub async fn stat(
+  domain: &str, authorization: String, storage_path: &shcs_types::StoragePath,
+) -> Result<shcs_types::Stat, Error> {
+  client(domain, authorization)?.stat(storage_path).await
+}
+
+pub async fn get_alias(
+  domain: &str, authorization: String, storage_path: &shcs_types::StoragePath,
+) -> Result<Option<String>, Error> {
+  client(domain, authorization)?.get_alias(storage_path).await
+}
+
+/// Find the items whose metadata match the `query`, one page at a time
+pub async fn query(
+  domain: &str, authorization: String, query: &shcs_types::Query,
+) -> Result<shcs_types::QueryPage, Error> {
+  client(domain, authorization)?.query(query).await
+}
+
+/// Run the `operations` with a single request, the results are in the same
+/// order as the operations
+pub async fn batch(
+  domain: &str, authorization: String, operations: &[shcs_types::BatchOperation],
+) -> Result<Vec<shcs_types::BatchResult>, Error> {
+  client(domain, authorization)?.batch(operations).await
+}
+
+/// Download the items the `request` selects as a single archive, the response
+/// body is streamed as the server writes it
+pub async fn archive(
+  domain: &str, authorization: String, request: &shcs_types::ArchiveRequest,
+) -> Result<reqwest::Response, Error> {
+  client(domain, authorization)?.archive(request).await
+}
+
+pub async fn delete_file(
+  domain: &str, authorization: String, storage_path: &shcs_types::StoragePath,
+) -> Result<(), Error> {
+  client(domain, authorization)?
+    .delete_file(storage_path)
+    .await
+}
+
+/// Copy the item at `storage_path` and its metadata to `destination`, without
+/// downloading it
+pub async fn copy_file(
+  domain: &str, authorization: String, storage_path: &shcs_types::StoragePath,
+  destination: &shcs_types::StoragePath,
+) -> Result<(), Error> {
+  client(domain, authorization)?
+    .copy_file(storage_path, destination)
+    .await
+}
+
+/// Move the item at `storage_path` and its metadata to `destination`
+pub async fn move_file(
+  domain: &str, authorization: String, storage_path: &shcs_types::StoragePath,
+  destination: &shcs_types::StoragePath,
+) -> Result<(), Error> {
+  client(domain, authorization)?
+    .move_file(storage_path, destination)
+    .await
+}
@@ -0,0 +1,127 @@
+//! The blocking counterparts of the functions of [crate::api], with the same
+//! arguments and errors
+use super::ShcsClient;
+use crate::api::Error;
+
+fn client(domain: &str, authorization: String) -> Result<ShcsClient, Error> {
+  ShcsClient::builder(domain)
+    .credentials(authorization)
+    .build()
+}
+
+/// Upload the file and get the storage path in return
+pub fn upload_file(
+  domain: &str, authorization: String, file: Vec<u8>, filename: Option<String>,
+  metadata: Option<impl serde::Serialize>,
+) -> Result<shcs_types::StoragePath, Error> {
+  client(domain, authorization)?.upload_file(file, filename, metadata)
+}
+
+/// Upload a ZIP or tar archive that the server expands into one file per entry,
+/// and get the storage path of every entry in return
+pub fn upload_archive(
+  domain: &str, authorization: String, archive: Vec<u8>, metadata: Option<impl serde::Serialize>,
+) -> Result<Vec<shcs_types::ExtractedItem>, Error> {
+  client(domain, authorization)?.upload_archive(archive, metadata)
+}
+
+/// Replace the file at the provided storage path
+pub fn replace_file(
+  domain: &str, authorization: String, file: Vec<u8>, filename: Option<String>,
+  metadata: Option<impl serde::Serialize>, storage_path: &shcs_types::StoragePath,
+) -> Result<shcs_types::StoragePath, Error> {
+  client(domain, authorization)?.replace_file(file, filename, metadata, storage_path)
+}
+
+/// This function is lower level than the other **C**R**UD** functions as it
+/// returns a raw `Response` object directly. This allows the response to be used
+/// in proxy functions
+pub fn get_file(
+  client: &reqwest::blocking::Client, domain: &str, storage_path: &shcs_types::StoragePath,
+) -> Result<reqwest::blocking::Response, Error> {
+  ShcsClient::builder(domain)
+    .http_client(client.clone())
+    .build()?
+    .get_file(storage_path)
+}
+
+pub fn get_metadata<Out>(
+  domain: &str, authorization: String, storage_path: &shcs_types::StoragePath,
+) -> Result<Out, Error>
+where
+  Out: serde::de::DeserializeOwned,
+{
+  client(domain, authorization)?.get_metadata(storage_path)
+}
+
+/// Apply the JSON merge `patch` to the alias and custom metadata of the file,
+/// and get the patched metadata with its new revision in return. When a
+/// `revision` is given the patch is refused if the metadata changed since.
+pub fn patch_metadata<Out>(
+  domain: &str, authorization: String, storage_path: &shcs_types::StoragePath,
+  patch: &impl serde::Serialize, revision: Option<u64>,
+) -> Result<(Out, u64), Error>
+where
+  Out: serde::de::DeserializeOwned,
+{
+  client(domain, authorization)?.patch_metadata(storage_path, patch, revision)
+}
+
+/// Get the alias, system and custom metadata of the file in one request
+pub fn stat(
+  domain: &str, authorization: String, storage_path: &shcs_types::StoragePath,
+) -> Result<shcs_types::Stat, Error> {
+  client(domain, authorization)?.stat(storage_path)
+}
+
+pub fn get_alias(
+  domain: &str, authorization: String, storage_path: &shcs_types::StoragePath,
+) -> Result<Option<String>, Error> {
+  client(domain, authorization)?.get_alias(storage_path)
+}
+
+/// Find the items whose metadata match the `query`, one page at a time
+pub fn query(
+  domain: &str, authorization: String, query: &shcs_types::Query,
+) -> Result<shcs_types::QueryPage, Error> {
+  client(domain, authorization)?.query(query)
+}
+
+/// Run the `operations` with a single request, the results are in the same
+/// order as the operations
+pub fn batch(
+  domain: &str, authorization: String, operations: &[shcs_types::BatchOperation],
+) -> Result<Vec<shcs_types::BatchResult>, Error> {
+  client(domain, authorization)?.batch(operations)
+}
+
+/// Download the items the `request` selects as a single archive, the response
+/// body is streamed as the server writes it
+pub fn archive(
+  domain: &str, authorization: String, request: &shcs_types::ArchiveRequest,
+) -> Result<reqwest::blocking::Response, Error> {
+  client(domain, authorization)?.archive(request)
+}
+
+pub fn delete_file(
+  domain: &str, authorization: String, storage_path: &shcs_types::StoragePath,
+) -> Result<(), Error> {
+  client(domain, authorization)?.delete_file(storage_path)
+}
+
+/// Copy the item at `storage_path` and its metadata to `destination`, without
+/// downloading it
+pub fn copy_file(
+  domain: &str, authorization: String, storage_path: &shcs_types::StoragePath,
+  destination: &shcs_types::StoragePath,
+) -> Result<(), Error> {
+  client(domain, authorization)?.copy_file(storage_path, destination)
+}
+
+/// Move the item at `storage_path` and its metadata to `destination`
+pub fn move_file(
+  domain: &str, authorization: String, storage_path: &shcs_types::StoragePath,
+  destination: &shcs_types::StoragePath,
+) -> Result<(), Error> {
+  client(domain, authorization)?.move_file(storage_path, destination)
+}
@@ -0,0 +1,12 @@
+//! A blocking client for the v1 API, for the synchronous programs that don't
+//! run an async runtime. It mirrors [crate::ShcsClient] and [crate::api] with
+//! the same types and [crate::api::Error].
+pub mod api;
+
+mod client;
+pub use client::Credentials;
+pub use client::ShcsClient;
+pub use client::ShcsClientBuilder;
+
+mod transfer;
+pub use transfer::UploadBody;
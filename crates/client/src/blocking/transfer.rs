@@ -0,0 +1,218 @@
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+
+use reqwest::header;
+use reqwest::StatusCode;
+
+use super::client::expect;
+use super::client::parse_storage_path;
+use super::client::upload_form;
+use super::ShcsClient;
+use crate::api::Error;
+use crate::transfer::range_validator;
+
+type Progress = Box<dyn FnMut(u64, Option<u64>) + Send>;
+
+/// The content of an upload, read while it is sent rather than loaded in
+/// memory first
+pub struct UploadBody {
+  reader: Box<dyn Read + Send>,
+  length: Option<u64>,
+  progress: Option<Progress>,
+}
+
+impl UploadBody {
+  /// `length` is the size of the content when it is known up front
+  pub fn from_reader(reader: impl Read + Send + 'static, length: Option<u64>) -> Self {
+    Self {
+      reader: Box::new(reader),
+      length,
+      progress: None,
+    }
+  }
+
+  pub fn from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+    let file = std::fs::File::open(path)?;
+    let length = file.metadata()?.len();
+
+    Ok(Self::from_reader(file, Some(length)))
+  }
+
+  /// Call `progress` with the number of bytes sent so far and the length of
+  /// the content, after every chunk
+  pub fn on_progress(mut self, progress: impl FnMut(u64, Option<u64>) + Send + 'static) -> Self {
+    self.progress = Some(Box::new(progress));
+    self
+  }
+
+  fn into_part(self) -> reqwest::blocking::multipart::Part {
+    let length = self.length;
+    let reader = ProgressReader {
+      body: self,
+      sent: 0,
+    };
+
+    match length {
+      Some(length) => reqwest::blocking::multipart::Part::reader_with_length(reader, length),
+      None => reqwest::blocking::multipart::Part::reader(reader),
+    }
+  }
+}
+
+impl From<Vec<u8>> for UploadBody {
+  fn from(value: Vec<u8>) -> Self {
+    let length = value.len() as u64;
+
+    Self::from_reader(std::io::Cursor::new(value), Some(length))
+  }
+}
+
+struct ProgressReader {
+  body: UploadBody,
+  sent: u64,
+}
+
+impl Read for ProgressReader {
+  fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+    let read = self.body.reader.read(buf)?;
+    self.sent += read as u64;
+
+    if let Some(progress) = self.body.progress.as_mut() {
+      progress(self.sent, self.body.length);
+    }
+
+    Ok(read)
+  }
+}
+
+impl ShcsClient {
+  /// Upload the `body` and get the storage path in return
+  pub fn upload(
+    &self, body: UploadBody, filename: Option<String>, metadata: Option<impl serde::Serialize>,
+  ) -> Result<shcs_types::StoragePath, Error> {
+    let url = self.url().ok()?;
+    let form = upload_form(body.into_part(), filename, metadata)?;
+
+    let response = self.send(self.http_client().put(url).multipart(form))?;
+
+    parse_storage_path(expect(response, StatusCode::CREATED)?)
+  }
+
+  /// Replace the file at the provided storage path with the `body`
+  pub fn replace(
+    &self, body: UploadBody, filename: Option<String>, metadata: Option<impl serde::Serialize>,
+    storage_path: &shcs_types::StoragePath,
+  ) -> Result<shcs_types::StoragePath, Error> {
+    let url = self.item_url(storage_path).ok()?;
+    let form = upload_form(body.into_part(), filename, metadata)?;
+
+    let response = self.send(self.http_client().post(url).multipart(form))?;
+
+    parse_storage_path(expect(response, StatusCode::CREATED)?)
+  }
+
+  /// Write the content of the item into `writer` as it is received, and get
+  /// its size in return. An interrupted download is resumed the same way as
+  /// with [crate::ShcsClient::download].
+  pub fn download(
+    &self, storage_path: &shcs_types::StoragePath, writer: &mut impl Write,
+    mut on_progress: impl FnMut(u64, Option<u64>),
+  ) -> Result<u64, Error> {
+    let url = self.item_url(storage_path).ok()?;
+    let mut written = 0;
+    let mut total = None;
+    let mut validator: Option<header::HeaderValue> = None;
+    let mut attempt = 0;
+    let mut buffer = vec![0; 64 * 1024];
+
+    loop {
+      let response = self.send_idempotent(|| {
+        let request = self.http_client().get(url.clone());
+
+        match (written, &validator) {
+          (0, _) => request,
+          (_, Some(validator)) => request
+            .header(header::RANGE, format!("bytes={written}-"))
+            .header(header::IF_RANGE, validator.clone()),
+          (_, None) => request.header(header::RANGE, format!("bytes={written}-")),
+        }
+      })?;
+
+      let status = response.status();
+      let mut response = match status {
+        StatusCode::PARTIAL_CONTENT if written > 0 => response,
+        _ => expect(response, StatusCode::OK)?,
+      };
+
+      if status == StatusCode::OK && written > 0 && validator.is_some() {
+        return Err(Error::ContentChanged);
+      }
+
+      if written == 0 {
+        validator = range_validator(response.headers());
+      }
+
+      total = total.or_else(|| {
+        let length = response.content_length()?;
+
+        match status {
+          StatusCode::PARTIAL_CONTENT => Some(written + length),
+          _ => Some(length),
+        }
+      });
+
+      // the bytes already written that the response sends again, when the
+      // server answered the range with the whole content
+      let mut skip = match status {
+        StatusCode::PARTIAL_CONTENT => 0,
+        _ => written,
+      };
+
+      let interrupted = loop {
+        let read = match response.read(&mut buffer) {
+          Ok(0) => break None,
+          Ok(read) => read,
+          Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+          Err(err) => break Some(err),
+        };
+
+        let skipped = skip.min(read as u64);
+        skip -= skipped;
+
+        let chunk = &buffer[skipped as usize..read];
+
+        if !chunk.is_empty() {
+          writer.write_all(chunk)?;
+          written += chunk.len() as u64;
+          on_progress(written, total);
+        }
+      };
+
+      match interrupted {
+        None => {
+          writer.flush()?;
+
+          return Ok(written);
+        }
+        Some(err) => match self.backoff(attempt) {
+          Some(delay) => std::thread::sleep(delay),
+          None => return Err(err.into()),
+        },
+      }
+
+      attempt += 1;
+    }
+  }
+
+  /// Download the item into the file at `path`, that is created or truncated
+  /// first
+  pub fn download_to_path(
+    &self, storage_path: &shcs_types::StoragePath, path: impl AsRef<Path>,
+    on_progress: impl FnMut(u64, Option<u64>),
+  ) -> Result<u64, Error> {
+    let mut file = std::fs::File::create(path)?;
+
+    self.download(storage_path, &mut file, on_progress)
+  }
+}
@@ -0,0 +1,479 @@
+//! A reusable client for the v1 API, it keeps one connection pool and the
+//! credentials for every request it sends
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use reqwest::header::AUTHORIZATION;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::RequestBuilder;
+use reqwest::Response;
+use reqwest::StatusCode;
+
+use crate::api::Error;
+use crate::UrlBuilder;
+
+pub(crate) const DEFAULT_USER_AGENT: &str = concat!("shcs-sdk/", env!("CARGO_PKG_VERSION"));
+pub(crate) const DEFAULT_RETRIES: u32 = 2;
+pub(crate) const DEFAULT_BACKOFF: Duration = Duration::from_millis(200);
+
+/// The statuses of a response that a later attempt may not get
+pub(crate) const RETRY_STATUSES: [StatusCode; 4] = [
+  StatusCode::TOO_MANY_REQUESTS,
+  StatusCode::BAD_GATEWAY,
+  StatusCode::SERVICE_UNAVAILABLE,
+  StatusCode::GATEWAY_TIMEOUT,
+];
+
+/// Provides the value of the `Authorization` header, it is asked before every
+/// request so that short lived tokens can be refreshed
+pub trait Credentials: Send + Sync {
+  fn authorization(&self) -> BoxFuture<'_, Result<String, Error>>;
+}
+
+/// A fixed value, such as `"Bearer <token>"`
+impl Credentials for String {
+  fn authorization(&self) -> BoxFuture<'_, Result<String, Error>> {
+    Box::pin(async move { Ok(self.clone()) })
+  }
+}
+
+pub struct ShcsClientBuilder {
+  base_url: String,
+  credentials: Option<Arc<dyn Credentials>>,
+  http: Option<reqwest::Client>,
+  timeout: Option<Duration>,
+  connect_timeout: Option<Duration>,
+  user_agent: String,
+  retries: u32,
+  backoff: Duration,
+}
+
+impl ShcsClientBuilder {
+  /// Send the `Authorization` header the `credentials` provide with every request
+  pub fn credentials(mut self, credentials: impl Credentials + 'static) -> Self {
+    self.credentials = Some(Arc::new(credentials));
+    self
+  }
+
+  /// Use an existing client and its connection pool, the timeouts and the
+  /// user-agent of this builder are then ignored
+  pub fn http_client(mut self, client: reqwest::Client) -> Self {
+    self.http = Some(client);
+    self
+  }
+
+  /// The timeout of a whole request, from connecting until the end of the
+  /// body. Ignored in WASM, like the connect timeout.
+  pub fn timeout(mut self, timeout: Duration) -> Self {
+    self.timeout = Some(timeout);
+    self
+  }
+
+  pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+    self.connect_timeout = Some(timeout);
+    self
+  }
+
+  pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+    self.user_agent = user_agent.into();
+    self
+  }
+
+  /// How many times an idempotent request is sent again when it failed to
+  /// connect, timed out or got a 429, 502, 503 or 504. The delay starts at
+  /// `backoff` and doubles after every attempt.
+  pub fn retries(mut self, retries: u32, backoff: Duration) -> Self {
+    self.retries = retries;
+    self.backoff = backoff;
+    self
+  }
+
+  pub fn build(self) -> Result<ShcsClient, Error> {
+    UrlBuilder::new(&self.base_url).ok()?;
+
+    let http = match self.http {
+      Some(http) => http,
+      None => {
+        #[allow(unused_mut)]
+        let mut builder = reqwest::Client::builder().user_agent(self.user_agent);
+
+        // the browser handles the timeouts of the requests in WASM
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+          if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+          }
+
+          if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+          }
+        }
+
+        builder.build()?
+      }
+    };
+
+    Ok(ShcsClient {
+      base_url: self.base_url,
+      credentials: self.credentials,
+      http,
+      retries: self.retries,
+      backoff: self.backoff,
+    })
+  }
+}
+
+/// Sends the requests to the v1 API of the server at `base_url`. Cloning the
+/// client is cheap and the clones share the same connection pool.
+///
+/// ```rs
+/// let client = ShcsClient::builder("https://files.example.com")
+///   .credentials(String::from("Bearer <token>"))
+///   .timeout(Duration::from_secs(30))
+///   .build()?;
+///
+/// let storage_path = client.upload_file(bytes, None, None::<()>).await?;
+/// ```
+#[derive(Clone)]
+pub struct ShcsClient {
+  base_url: String,
+  credentials: Option<Arc<dyn Credentials>>,
+  http: reqwest::Client,
+  retries: u32,
+  backoff: Duration,
+}
+
+impl ShcsClient {
+  pub fn builder(base_url: impl Into<String>) -> ShcsClientBuilder {
+    ShcsClientBuilder {
+      base_url: base_url.into(),
+      credentials: None,
+      http: None,
+      timeout: None,
+      connect_timeout: None,
+      user_agent: DEFAULT_USER_AGENT.to_string(),
+      retries: DEFAULT_RETRIES,
+      backoff: DEFAULT_BACKOFF,
+    }
+  }
+
+  /// The underlying client, to send requests the SDK doesn't cover
+  pub fn http_client(&self) -> &reqwest::Client {
+    &self.http
+  }
+
+  pub(crate) fn url(&self) -> UrlBuilder {
+    UrlBuilder::new(&self.base_url)
+  }
+
+  pub(crate) fn item_url(&self, storage_path: &shcs_types::StoragePath) -> UrlBuilder {
+    self.url().join(&storage_path.to_string())
+  }
+
+  async fn authorize(&self, request: RequestBuilder) -> Result<RequestBuilder, Error> {
+    match &self.credentials {
+      Some(credentials) => Ok(request.header(AUTHORIZATION, credentials.authorization().await?)),
+      None => Ok(request),
+    }
+  }
+
+  /// The delay before the retry that follows the failed `attempt`, none when
+  /// the client gave up retrying
+  pub(crate) fn backoff(&self, attempt: u32) -> Option<Duration> {
+    match attempt < self.retries {
+      true => Some(self.backoff * 2u32.saturating_pow(attempt)),
+      false => None,
+    }
+  }
+
+  /// Send a request once, for the ones that must not be applied twice
+  pub(crate) async fn send(&self, request: RequestBuilder) -> Result<Response, Error> {
+    Ok(self.authorize(request).await?.send().await?)
+  }
+
+  /// Send the request `build` makes, and again after a backoff as long as it
+  /// fails in a way that a retry could fix
+  pub(crate) async fn send_idempotent<F>(&self, build: F) -> Result<Response, Error>
+  where
+    F: Fn() -> RequestBuilder,
+  {
+    let mut attempt = 0;
+
+    loop {
+      let result = self.authorize(build()).await?.send().await;
+      let retry = match &result {
+        Ok(response) => RETRY_STATUSES.contains(&response.status()),
+        Err(err) => err.is_connect() || err.is_timeout(),
+      };
+
+      match self.backoff(attempt) {
+        Some(delay) if retry => sleep(delay).await,
+        _ => return Ok(result?),
+      }
+
+      attempt += 1;
+    }
+  }
+
+  /// Upload the file and get the storage path in return
+  pub async fn upload_file(
+    &self, file: Vec<u8>, filename: Option<String>, metadata: Option<impl serde::Serialize>,
+  ) -> Result<shcs_types::StoragePath, Error> {
+    let url = self.url().ok()?;
+    let form = upload_form(reqwest::multipart::Part::stream(file), filename, metadata)?;
+
+    let response = self.send(self.http.put(url).multipart(form)).await?;
+
+    parse_storage_path(expect(response, StatusCode::CREATED).await?).await
+  }
+
+  /// Upload a ZIP or tar archive that the server expands into one file per
+  /// entry, and get the storage path of every entry in return
+  pub async fn upload_archive(
+    &self, archive: Vec<u8>, metadata: Option<impl serde::Serialize>,
+  ) -> Result<Vec<shcs_types::ExtractedItem>, Error> {
+    let mut url = self.url().ok()?;
+    url.set_query(Some("extract=true"));
+    let form = upload_form(reqwest::multipart::Part::stream(archive), None, metadata)?;
+
+    let response = self.send(self.http.put(url).multipart(form)).await?;
+
+    json(expect(response, StatusCode::CREATED).await?).await
+  }
+
+  /// Replace the file at the provided storage path
+  pub async fn replace_file(
+    &self, file: Vec<u8>, filename: Option<String>, metadata: Option<impl serde::Serialize>,
+    storage_path: &shcs_types::StoragePath,
+  ) -> Result<shcs_types::StoragePath, Error> {
+    let url = self.item_url(storage_path).ok()?;
+    let form = upload_form(reqwest::multipart::Part::stream(file), filename, metadata)?;
+
+    let response = self.send(self.http.post(url).multipart(form)).await?;
+
+    parse_storage_path(expect(response, StatusCode::CREATED).await?).await
+  }
+
+  /// Returns the raw `Response` so that its body can be streamed, by a proxy
+  /// for example
+  pub async fn get_file(&self, storage_path: &shcs_types::StoragePath) -> Result<Response, Error> {
+    let url = self.item_url(storage_path).ok()?;
+
+    let response = self.send_idempotent(|| self.http.get(url.clone())).await?;
+
+    expect(response, StatusCode::OK).await
+  }
+
+  pub async fn get_metadata<Out>(
+    &self, storage_path: &shcs_types::StoragePath,
+  ) -> Result<Out, Error>
+  where
+    Out: serde::de::DeserializeOwned,
+  {
+    let url = self.item_url(storage_path).join("metadata").ok()?;
+
+    let response = self.send_idempotent(|| self.http.get(url.clone())).await?;
+
+    json(expect(response, StatusCode::OK).await?).await
+  }
+
+  /// Apply the JSON merge `patch` to the alias and custom metadata of the file,
+  /// and get the patched metadata with its new revision in return. When a
+  /// `revision` is given the patch is refused if the metadata changed since.
+  pub async fn patch_metadata<Out>(
+    &self, storage_path: &shcs_types::StoragePath, patch: &impl serde::Serialize,
+    revision: Option<u64>,
+  ) -> Result<(Out, u64), Error>
+  where
+    Out: serde::de::DeserializeOwned,
+  {
+    let url = self.item_url(storage_path).join("metadata").ok()?;
+    let body = serde_json::to_string(patch)?;
+
+    let mut request = self
+      .http
+      .patch(url)
+      .body(body)
+      .header(CONTENT_TYPE, "application/merge-patch+json");
+
+    if let Some(revision) = revision {
+      request = request.header("If-Match", format!("\"{revision}\""));
+    }
+
+    let response = expect(self.send(request).await?, StatusCode::OK).await?;
+    let revision = response
+      .headers()
+      .get("ETag")
+      .and_then(|etag| etag.to_str().ok())
+      .and_then(|etag| etag.trim_matches('"').parse().ok())
+      .unwrap_or_default();
+
+    Ok((json(response).await?, revision))
+  }
+
+  /// Get the alias, system and custom metadata of the file in one request
+  pub async fn stat(
+    &self, storage_path: &shcs_types::StoragePath,
+  ) -> Result<shcs_types::Stat, Error> {
+    let url = self.item_url(storage_path).join("stat").ok()?;
+
+    let response = self.send_idempotent(|| self.http.get(url.clone())).await?;
+
+    json(expect(response, StatusCode::OK).await?).await
+  }
+
+  /// Get the alias of the file, that is None when the file has no metadata
+  pub async fn get_alias(
+    &self, storage_path: &shcs_types::StoragePath,
+  ) -> Result<Option<String>, Error> {
+    let url = self.item_url(storage_path).join("alias").ok()?;
+
+    let response = self.send_idempotent(|| self.http.get(url.clone())).await?;
+
+    json(expect(response, StatusCode::OK).await?).await
+  }
+
+  /// Find the items whose metadata match the `query`, one page at a time
+  pub async fn query(&self, query: &shcs_types::Query) -> Result<shcs_types::QueryPage, Error> {
+    let url = self.url().join("query").ok()?;
+    let body = serde_json::to_string(query)?;
+
+    let response = self
+      .send_idempotent(|| json_request(self.http.post(url.clone()), &body))
+      .await?;
+
+    json(expect(response, StatusCode::OK).await?).await
+  }
+
+  /// Run the `operations` with a single request, the results are in the same
+  /// order as the operations
+  pub async fn batch(
+    &self, operations: &[shcs_types::BatchOperation],
+  ) -> Result<Vec<shcs_types::BatchResult>, Error> {
+    let url = self.url().join("batch").ok()?;
+    let body = serde_json::to_string(operations)?;
+
+    let response = self.send(json_request(self.http.post(url), &body)).await?;
+
+    json(expect(response, StatusCode::OK).await?).await
+  }
+
+  /// Download the items the `request` selects as a single archive, the
+  /// response body is streamed as the server writes it
+  pub async fn archive(&self, request: &shcs_types::ArchiveRequest) -> Result<Response, Error> {
+    let url = self.url().join("archive").ok()?;
+    let body = serde_json::to_string(request)?;
+
+    let response = self
+      .send_idempotent(|| json_request(self.http.post(url.clone()), &body))
+      .await?;
+
+    expect(response, StatusCode::OK).await
+  }
+
+  pub async fn delete_file(&self, storage_path: &shcs_types::StoragePath) -> Result<(), Error> {
+    let url = self.item_url(storage_path).ok()?;
+
+    let response = self
+      .send_idempotent(|| self.http.delete(url.clone()))
+      .await?;
+
+    expect(response, StatusCode::OK).await.map(drop)
+  }
+
+  /// Copy the item at `storage_path` and its metadata to `destination`,
+  /// without downloading it
+  pub async fn copy_file(
+    &self, storage_path: &shcs_types::StoragePath, destination: &shcs_types::StoragePath,
+  ) -> Result<(), Error> {
+    self.relocate_file(storage_path, destination, "copy").await
+  }
+
+  /// Move the item at `storage_path` and its metadata to `destination`
+  pub async fn move_file(
+    &self, storage_path: &shcs_types::StoragePath, destination: &shcs_types::StoragePath,
+  ) -> Result<(), Error> {
+    self.relocate_file(storage_path, destination, "move").await
+  }
+
+  async fn relocate_file(
+    &self, storage_path: &shcs_types::StoragePath, destination: &shcs_types::StoragePath,
+    action: &str,
+  ) -> Result<(), Error> {
+    let url = self.item_url(storage_path).join(action).ok()?;
+    let body = serde_json::json!({ "destination": destination }).to_string();
+
+    let response = self.send(json_request(self.http.post(url), &body)).await?;
+
+    expect(response, StatusCode::OK).await.map(drop)
+  }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn sleep(duration: Duration) {
+  tokio::time::sleep(duration).await
+}
+
+/// There is no timer without a runtime in WASM, the retries are sent right away
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn sleep(_: Duration) {}
+
+pub(crate) fn upload_form(
+  mut filepart: reqwest::multipart::Part, filename: Option<String>,
+  metadata: Option<impl serde::Serialize>,
+) -> Result<reqwest::multipart::Form, Error> {
+  if let Some(filename) = filename {
+    filepart = filepart.file_name(filename);
+  }
+
+  let mut form = reqwest::multipart::Form::new().part("file", filepart);
+
+  if let Some(metadata) = metadata {
+    let json = serde_json::to_string(&metadata)?;
+    let metadatapart = reqwest::multipart::Part::text(json).mime_str("application/json")?;
+
+    form = form.part("metadata", metadatapart);
+  }
+
+  Ok(form)
+}
+
+fn json_request(request: RequestBuilder, body: &str) -> RequestBuilder {
+  request
+    .body(body.to_string())
+    .header(CONTENT_TYPE, "application/json")
+}
+
+/// Pass the response through if it has the `expected` status, otherwise turn
+/// it into the error of its status
+pub(crate) async fn expect(response: Response, expected: StatusCode) -> Result<Response, Error> {
+  let status = response.status();
+
+  if status == expected {
+    return Ok(response);
+  }
+
+  let body = response.text().await.unwrap_or_default();
+
+  Err(Error::from_response(status, &body))
+}
+
+async fn json<Out>(response: Response) -> Result<Out, Error>
+where
+  Out: serde::de::DeserializeOwned,
+{
+  let text = response.text().await?;
+
+  Ok(serde_json::from_str(&text)?)
+}
+
+pub(crate) async fn parse_storage_path(
+  response: Response,
+) -> Result<shcs_types::StoragePath, Error> {
+  response
+    .text()
+    .await?
+    .parse()
+    .map_err(Error::InvalidStoragePath)
+}
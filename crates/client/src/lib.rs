@@ -0,0 +1,24 @@
+//! A client for the v1 API of the storage server, that only depends on
+//! reqwest and serde
+pub use shcs_types::*;
+
+pub mod api;
+
+mod client;
+pub use client::Credentials;
+pub use client::ShcsClient;
+pub use client::ShcsClientBuilder;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod transfer;
+#[cfg(not(target_arch = "wasm32"))]
+pub use transfer::UploadBody;
+
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub mod blocking;
+
+mod params;
+pub(crate) use params::UrlBuilder;
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests;
@@ -1,4 +1,4 @@
-use super::api::Error;
+use crate::api::Error;
 use reqwest::Url;
 
 pub struct UrlBuilder(String);
@@ -8,10 +8,8 @@ impl UrlBuilder {
   }
 
   pub(crate) fn join(mut self, segment: &str) -> Self {
-    if !self.0.is_empty() {
-      if !self.0.ends_with("/") && !segment.starts_with("/") {
-        self.0.push('/');
-      }
+    if !self.0.is_empty() && !self.0.ends_with("/") && !segment.starts_with("/") {
+      self.0.push('/');
     }
 
     self.0.push_str(segment);
@@ -0,0 +1,427 @@
+use std::io::BufRead;
+use std::io::Read;
+use std::io::Write;
+use std::time::Duration;
+
+use crate::api::Error;
+use crate::ShcsClient;
+use crate::StoragePath;
+
+/// A server that answers the scripted responses in order, one per connection,
+/// and records the head of every request it receives
+struct MockServer {
+  url: String,
+  requests: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+}
+
+impl MockServer {
+  fn start(responses: Vec<Vec<u8>>) -> Self {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("the listener is bound");
+    let url = format!(
+      "http://{}",
+      listener.local_addr().expect("it has an address")
+    );
+    let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorded = requests.clone();
+
+    std::thread::spawn(move || {
+      for response in responses {
+        let Ok((stream, _)) = listener.accept() else {
+          return;
+        };
+
+        let mut reader = std::io::BufReader::new(stream);
+        let mut head = String::new();
+        let mut length = 0;
+
+        loop {
+          let mut line = String::new();
+          if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+            break;
+          }
+
+          if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+            length = value.trim().parse().unwrap_or(0);
+          }
+
+          head.push_str(&line.to_lowercase());
+        }
+
+        let mut body = vec![0; length];
+        let _ = reader.read_exact(&mut body);
+        recorded.lock().unwrap().push(head);
+
+        let mut stream = reader.into_inner();
+        let _ = stream.write_all(&response);
+      }
+    });
+
+    Self { url, requests }
+  }
+
+  /// The heads of the requests received so far, lowercased
+  fn requests(&self) -> Vec<String> {
+    self.requests.lock().unwrap().clone()
+  }
+
+  fn client(&self) -> ShcsClient {
+    ShcsClient::builder(&self.url)
+      .retries(2, Duration::from_millis(1))
+      .build()
+      .expect("the client is built")
+  }
+}
+
+/// A response with the `status` line, extra `headers` and `body`, that closes
+/// its connection
+fn response(status: &str, headers: &[&str], body: &str) -> Vec<u8> {
+  truncated(status, headers, body, body.len())
+}
+
+/// A response that announces `length` bytes of body but sends only `body`
+/// before closing its connection
+fn truncated(status: &str, headers: &[&str], body: &str, length: usize) -> Vec<u8> {
+  let mut response =
+    format!("HTTP/1.1 {status}\r\nconnection: close\r\ncontent-length: {length}\r\n");
+
+  for header in headers {
+    response.push_str(header);
+    response.push_str("\r\n");
+  }
+
+  response.push_str("\r\n");
+  response.push_str(body);
+  response.into_bytes()
+}
+
+fn storage_path() -> StoragePath {
+  "bucket/item.md".parse().expect("the storage path is valid")
+}
+
+fn status(result: Result<impl std::fmt::Debug, Error>) -> Option<u16> {
+  result.err()?.problem().map(|problem| problem.status)
+}
+
+#[tokio::test]
+async fn test_idempotent_retries() {
+  // the statuses a later attempt may not get are retried
+  let server = MockServer::start(vec![
+    response("503 Service Unavailable", &[], ""),
+    response("429 Too Many Requests", &[], ""),
+    response(
+      "200 OK",
+      &["content-type: application/json"],
+      "\"report.pdf\"",
+    ),
+  ]);
+  let alias = server.client().get_alias(&storage_path()).await;
+  assert_eq!(alias.ok().flatten().as_deref(), Some("report.pdf"));
+  assert_eq!(server.requests().len(), 3);
+
+  // until the client gives up
+  let server = MockServer::start(vec![response("502 Bad Gateway", &[], ""); 4]);
+  assert_eq!(
+    status(server.client().get_alias(&storage_path()).await),
+    Some(502)
+  );
+  assert_eq!(server.requests().len(), 3);
+
+  // the other statuses are answered right away
+  let server = MockServer::start(vec![
+    response("500 Internal Server Error", &[], ""),
+    response("200 OK", &[], "null"),
+  ]);
+  assert_eq!(
+    status(server.client().get_alias(&storage_path()).await),
+    Some(500)
+  );
+  assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn test_non_idempotent_requests_are_sent_once() {
+  let server = MockServer::start(vec![
+    response("503 Service Unavailable", &[], ""),
+    response("200 OK", &[], ""),
+  ]);
+  let destination = "bucket/copy.md".parse().expect("the storage path is valid");
+
+  assert_eq!(
+    status(
+      server
+        .client()
+        .copy_file(&storage_path(), &destination)
+        .await
+    ),
+    Some(503)
+  );
+  assert_eq!(server.requests().len(), 1);
+  assert!(server.requests()[0].starts_with("post /v1/bucket/item.md/copy "));
+
+  // nor are the requests of a client that doesn't retry
+  let server = MockServer::start(vec![response("503 Service Unavailable", &[], ""); 2]);
+  let client = ShcsClient::builder(&server.url)
+    .retries(0, Duration::from_millis(1))
+    .build()
+    .expect("the client is built");
+  assert_eq!(status(client.get_alias(&storage_path()).await), Some(503));
+  assert_eq!(server.requests().len(), 1);
+}
+
+#[test]
+fn test_error_from_response() {
+  use reqwest::StatusCode;
+
+  let problem = r#"{ "status": 404, "title": "Not Found", "detail": "item not found" }"#;
+  let error = Error::from_response(StatusCode::NOT_FOUND, problem);
+  assert!(matches!(error, Error::NotFound(_)));
+  assert_eq!(error.to_string(), "404 Not Found: item not found");
+
+  let error = Error::from_response(StatusCode::UNAUTHORIZED, "");
+  assert!(matches!(error, Error::Unauthorized(_)));
+
+  for status in [StatusCode::CONFLICT, StatusCode::PRECONDITION_FAILED] {
+    let error = Error::from_response(status, "");
+    assert!(matches!(error, Error::Conflict(_)));
+    assert_eq!(
+      error.problem().map(|problem| problem.status),
+      Some(status.as_u16())
+    );
+  }
+
+  // the proxies in front of the server answer bodies that aren't problems
+  let error = Error::from_response(StatusCode::BAD_GATEWAY, "upstream unreachable");
+  assert!(matches!(error, Error::UnhandledStatus(_)));
+  assert_eq!(
+    error.problem(),
+    Some(&shcs_types::Problem {
+      status: 502,
+      title: "Bad Gateway".to_owned(),
+      detail: Some("upstream unreachable".to_owned()),
+    })
+  );
+
+  let error = Error::from_response(StatusCode::INTERNAL_SERVER_ERROR, "");
+  assert_eq!(error.to_string(), "500 Internal Server Error");
+}
+
+#[test]
+fn test_range_validator() {
+  use reqwest::header;
+
+  use crate::transfer::range_validator;
+
+  let headers = |values: &[(header::HeaderName, &'static str)]| {
+    let mut headers = header::HeaderMap::new();
+    for (name, value) in values {
+      headers.insert(name, header::HeaderValue::from_static(value));
+    }
+    headers
+  };
+  let last_modified = "Wed, 21 Oct 2015 07:28:00 GMT";
+
+  // a strong ETag is preferred, a weak one cannot be used with If-Range
+  assert_eq!(
+    range_validator(&headers(&[
+      (header::ETAG, "\"v1\""),
+      (header::LAST_MODIFIED, last_modified)
+    ])),
+    Some(header::HeaderValue::from_static("\"v1\""))
+  );
+  assert_eq!(
+    range_validator(&headers(&[
+      (header::ETAG, "W/\"v1\""),
+      (header::LAST_MODIFIED, last_modified)
+    ])),
+    Some(header::HeaderValue::from_static(last_modified))
+  );
+  assert_eq!(
+    range_validator(&headers(&[(header::ETAG, "W/\"v1\"")])),
+    None
+  );
+  assert_eq!(range_validator(&header::HeaderMap::new()), None);
+}
+
+#[tokio::test]
+async fn test_download_resumes() {
+  // the rest of the content is requested from where it stopped
+  let server = MockServer::start(vec![
+    truncated("200 OK", &["etag: \"v1\""], "01234", 10),
+    response("206 Partial Content", &["etag: \"v1\""], "56789"),
+  ]);
+  let mut content = Vec::new();
+  let mut progress = Vec::new();
+  let size = server
+    .client()
+    .download(&storage_path(), &mut content, |written, total| {
+      progress.push((written, total))
+    })
+    .await;
+
+  assert_eq!(size.ok(), Some(10));
+  assert_eq!(content, b"0123456789");
+  assert_eq!(progress.last(), Some(&(10, Some(10))));
+
+  let requests = server.requests();
+  assert!(!requests[0].contains("range:"));
+  assert!(requests[1].contains("range: bytes=5-\r\n"));
+  assert!(requests[1].contains("if-range: \"v1\"\r\n"));
+}
+
+#[tokio::test]
+async fn test_download_skips_the_content_sent_again() {
+  // without a validator, a server that ignores the range sends the whole
+  // content again and the bytes already written are skipped
+  let server = MockServer::start(vec![
+    truncated("200 OK", &[], "01234", 10),
+    response("200 OK", &[], "0123456789"),
+  ]);
+  let mut content = Vec::new();
+  let size = server
+    .client()
+    .download(&storage_path(), &mut content, |_, _| {})
+    .await;
+
+  assert_eq!(size.ok(), Some(10));
+  assert_eq!(content, b"0123456789");
+  assert!(server.requests()[1].contains("range: bytes=5-\r\n"));
+  assert!(!server.requests()[1].contains("if-range:"));
+}
+
+#[tokio::test]
+async fn test_download_detects_changed_content() {
+  // with a validator, the whole content is only sent again when the item
+  // changed, and the bytes already written don't belong to it
+  let server = MockServer::start(vec![
+    truncated("200 OK", &["etag: \"v1\""], "01234", 10),
+    response("200 OK", &["etag: \"v2\""], "abcdefghij"),
+  ]);
+  let mut content = Vec::new();
+  let result = server
+    .client()
+    .download(&storage_path(), &mut content, |_, _| {})
+    .await;
+
+  assert!(matches!(result, Err(Error::ContentChanged)));
+
+  // an interrupted download fails once the client gave up retrying
+  let server = MockServer::start(vec![truncated("200 OK", &[], "01234", 10); 3]);
+  let result = server
+    .client()
+    .download(&storage_path(), &mut Vec::new(), |_, _| {})
+    .await;
+
+  assert!(matches!(result, Err(Error::Reqwest(_))));
+  assert_eq!(server.requests().len(), 3);
+}
+
+/// The responses of the calls of [test_blocking_parity], in order
+fn parity_script() -> Vec<Vec<u8>> {
+  let problem = |status: &str, code: u16, detail: &str| {
+    let title = status
+      .split_once(' ')
+      .map(|(_, title)| title)
+      .unwrap_or_default();
+    let body = format!(r#"{{"status":{code},"title":"{title}","detail":"{detail}"}}"#);
+    response(status, &["content-type: application/problem+json"], &body)
+  };
+
+  vec![
+    response("200 OK", &["content-type: application/json"], "\"a.md\""),
+    problem("404 Not Found", 404, "item not found"),
+    response("401 Unauthorized", &[], "denied"),
+    response("503 Service Unavailable", &[], ""),
+    response("200 OK", &[], ""),
+    problem("412 Precondition Failed", 412, "the metadata changed"),
+    truncated("200 OK", &["etag: \"v1\""], "01234", 10),
+    response("206 Partial Content", &["etag: \"v1\""], "56789"),
+  ]
+}
+
+/// The outcome of a call, comparable between the clients
+fn outcome<T: std::fmt::Debug>(result: Result<T, Error>) -> String {
+  match result {
+    Ok(value) => format!("ok {value:?}"),
+    Err(e) => format!("err {e}"),
+  }
+}
+
+/// The heads of the requests with their headers sorted, as the clients send
+/// them in different orders, and without the `host` header that holds the port
+/// of the server
+fn comparable(requests: Vec<String>) -> Vec<Vec<String>> {
+  requests
+    .into_iter()
+    .map(|head| {
+      let mut lines: Vec<String> = head
+        .lines()
+        .filter(|line| !line.starts_with("host:"))
+        .map(str::to_owned)
+        .collect();
+      lines[1..].sort();
+      lines
+    })
+    .collect()
+}
+
+#[test]
+fn test_blocking_parity() {
+  let destination: StoragePath = "bucket/copy.md".parse().expect("the storage path is valid");
+
+  let server = MockServer::start(parity_script());
+  let runtime = tokio::runtime::Builder::new_current_thread()
+    .enable_all()
+    .build()
+    .expect("the runtime is built");
+  let client = server.client();
+  let mut content = Vec::new();
+  let outcomes = runtime.block_on(async {
+    vec![
+      outcome(client.get_alias(&storage_path()).await),
+      outcome(client.get_alias(&storage_path()).await),
+      outcome(
+        client
+          .get_metadata::<serde_json::Value>(&storage_path())
+          .await,
+      ),
+      outcome(client.delete_file(&storage_path()).await),
+      outcome(client.copy_file(&storage_path(), &destination).await),
+      outcome(
+        client
+          .download(&storage_path(), &mut content, |_, _| {})
+          .await,
+      ),
+    ]
+  });
+  let requests = comparable(server.requests());
+
+  let server = MockServer::start(parity_script());
+  let client = crate::blocking::ShcsClient::builder(&server.url)
+    .retries(2, Duration::from_millis(1))
+    .build()
+    .expect("the client is built");
+  let mut blocking_content = Vec::new();
+  let blocking_outcomes = vec![
+    outcome(client.get_alias(&storage_path())),
+    outcome(client.get_alias(&storage_path())),
+    outcome(client.get_metadata::<serde_json::Value>(&storage_path())),
+    outcome(client.delete_file(&storage_path())),
+    outcome(client.copy_file(&storage_path(), &destination)),
+    outcome(client.download(&storage_path(), &mut blocking_content, |_, _| {})),
+  ];
+
+  assert_eq!(
+    outcomes,
+    [
+      "ok Some(\"a.md\")",
+      "err 404 Not Found: item not found",
+      "err 401 Unauthorized: denied",
+      "ok ()",
+      "err 412 Precondition Failed: the metadata changed",
+      "ok 10",
+    ]
+  );
+  assert_eq!(blocking_outcomes, outcomes);
+  assert_eq!(blocking_content, content);
+  assert_eq!(comparable(server.requests()), requests);
+}
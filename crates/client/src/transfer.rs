@@ -0,0 +1,258 @@
+//! Uploads read from files, readers or streams as they are sent, and downloads
+//! written as they are received
+use std::path::Path;
+
+use bytes::Bytes;
+use futures_util::stream::BoxStream;
+use futures_util::Stream;
+use futures_util::StreamExt;
+use futures_util::TryStreamExt;
+use reqwest::header;
+use reqwest::StatusCode;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+
+use crate::api::Error;
+use crate::client::expect;
+use crate::client::parse_storage_path;
+use crate::client::sleep;
+use crate::client::upload_form;
+use crate::ShcsClient;
+
+type Progress = Box<dyn FnMut(u64, Option<u64>) + Send>;
+
+/// The content of an upload, read while it is sent rather than loaded in
+/// memory first
+///
+/// ```rs
+/// let body = UploadBody::from_path("report.pdf")
+///   .await?
+///   .on_progress(|sent, total| println!("{sent}/{total:?}"));
+///
+/// let storage_path = client.upload(body, Some("report.pdf".into()), None::<()>).await?;
+/// ```
+pub struct UploadBody {
+  stream: BoxStream<'static, std::io::Result<Bytes>>,
+  length: Option<u64>,
+  progress: Option<Progress>,
+}
+
+impl UploadBody {
+  /// `length` is the size of the content when it is known up front
+  pub fn from_stream<S, E>(stream: S, length: Option<u64>) -> Self
+  where
+    S: Stream<Item = Result<Bytes, E>> + Send + 'static,
+    E: Into<Box<dyn std::error::Error + Send + Sync>> + 'static,
+  {
+    Self {
+      stream: stream.map_err(std::io::Error::other).boxed(),
+      length,
+      progress: None,
+    }
+  }
+
+  pub fn from_reader(reader: impl AsyncRead + Send + 'static, length: Option<u64>) -> Self {
+    Self::from_stream(tokio_util::io::ReaderStream::new(reader), length)
+  }
+
+  pub async fn from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+    let file = tokio::fs::File::open(path).await?;
+    let length = file.metadata().await?.len();
+
+    Ok(Self::from_reader(file, Some(length)))
+  }
+
+  /// Call `progress` with the number of bytes sent so far and the length of
+  /// the content, after every chunk
+  pub fn on_progress(mut self, progress: impl FnMut(u64, Option<u64>) + Send + 'static) -> Self {
+    self.progress = Some(Box::new(progress));
+    self
+  }
+
+  fn into_part(self) -> reqwest::multipart::Part {
+    let Self {
+      stream,
+      length,
+      mut progress,
+    } = self;
+    let mut sent = 0;
+
+    let stream = stream.inspect_ok(move |chunk| {
+      sent += chunk.len() as u64;
+
+      if let Some(progress) = progress.as_mut() {
+        progress(sent, length);
+      }
+    });
+    let body = reqwest::Body::wrap_stream(stream);
+
+    match length {
+      Some(length) => reqwest::multipart::Part::stream_with_length(body, length),
+      None => reqwest::multipart::Part::stream(body),
+    }
+  }
+}
+
+impl From<Vec<u8>> for UploadBody {
+  fn from(value: Vec<u8>) -> Self {
+    let length = value.len() as u64;
+    let chunk = futures_util::stream::once(async { Ok::<_, std::io::Error>(Bytes::from(value)) });
+
+    Self::from_stream(chunk, Some(length))
+  }
+}
+
+impl ShcsClient {
+  /// Upload the `body` and get the storage path in return
+  pub async fn upload(
+    &self, body: UploadBody, filename: Option<String>, metadata: Option<impl serde::Serialize>,
+  ) -> Result<shcs_types::StoragePath, Error> {
+    let url = self.url().ok()?;
+    let form = upload_form(body.into_part(), filename, metadata)?;
+
+    let response = self
+      .send(self.http_client().put(url).multipart(form))
+      .await?;
+
+    parse_storage_path(expect(response, StatusCode::CREATED).await?).await
+  }
+
+  /// Replace the file at the provided storage path with the `body`
+  pub async fn replace(
+    &self, body: UploadBody, filename: Option<String>, metadata: Option<impl serde::Serialize>,
+    storage_path: &shcs_types::StoragePath,
+  ) -> Result<shcs_types::StoragePath, Error> {
+    let url = self.item_url(storage_path).ok()?;
+    let form = upload_form(body.into_part(), filename, metadata)?;
+
+    let response = self
+      .send(self.http_client().post(url).multipart(form))
+      .await?;
+
+    parse_storage_path(expect(response, StatusCode::CREATED).await?).await
+  }
+
+  /// Write the content of the item into `writer` as it is received, and get
+  /// its size in return. `on_progress` is called with the number of bytes
+  /// written so far and the size of the item when the server sent it.
+  ///
+  /// An interrupted download is resumed where it stopped with a `Range`
+  /// request, as many times as the client retries. When the server ignores the
+  /// range the bytes already written are skipped, and the download fails with
+  /// [Error::ContentChanged] if the item was replaced in the meantime.
+  pub async fn download<W>(
+    &self, storage_path: &shcs_types::StoragePath, writer: &mut W,
+    mut on_progress: impl FnMut(u64, Option<u64>),
+  ) -> Result<u64, Error>
+  where
+    W: AsyncWrite + Unpin,
+  {
+    let url = self.item_url(storage_path).ok()?;
+    let mut written = 0;
+    let mut total = None;
+    let mut validator: Option<header::HeaderValue> = None;
+    let mut attempt = 0;
+
+    loop {
+      let response = self
+        .send_idempotent(|| {
+          let request = self.http_client().get(url.clone());
+
+          match (written, &validator) {
+            (0, _) => request,
+            (_, Some(validator)) => request
+              .header(header::RANGE, format!("bytes={written}-"))
+              .header(header::IF_RANGE, validator.clone()),
+            (_, None) => request.header(header::RANGE, format!("bytes={written}-")),
+          }
+        })
+        .await?;
+
+      let status = response.status();
+      let response = match status {
+        StatusCode::PARTIAL_CONTENT if written > 0 => response,
+        _ => expect(response, StatusCode::OK).await?,
+      };
+
+      if status == StatusCode::OK && written > 0 && validator.is_some() {
+        return Err(Error::ContentChanged);
+      }
+
+      if written == 0 {
+        validator = range_validator(response.headers());
+      }
+
+      total = total.or_else(|| {
+        let length = response.content_length()?;
+
+        match status {
+          StatusCode::PARTIAL_CONTENT => Some(written + length),
+          _ => Some(length),
+        }
+      });
+
+      // the bytes already written that the response sends again, when the
+      // server answered the range with the whole content
+      let mut skip = match status {
+        StatusCode::PARTIAL_CONTENT => 0,
+        _ => written,
+      };
+
+      let mut chunks = response.bytes_stream();
+      let interrupted = loop {
+        let chunk = match chunks.next().await {
+          Some(Ok(chunk)) => chunk,
+          Some(Err(err)) => break Some(err),
+          None => break None,
+        };
+
+        let skipped = skip.min(chunk.len() as u64);
+        skip -= skipped;
+
+        let chunk = &chunk[skipped as usize..];
+
+        if !chunk.is_empty() {
+          writer.write_all(chunk).await?;
+          written += chunk.len() as u64;
+          on_progress(written, total);
+        }
+      };
+
+      match interrupted {
+        None => {
+          writer.flush().await?;
+
+          return Ok(written);
+        }
+        Some(err) => match self.backoff(attempt) {
+          Some(delay) => sleep(delay).await,
+          None => return Err(err.into()),
+        },
+      }
+
+      attempt += 1;
+    }
+  }
+
+  /// Download the item into the file at `path`, that is created or truncated
+  /// first
+  pub async fn download_to_path(
+    &self, storage_path: &shcs_types::StoragePath, path: impl AsRef<Path>,
+    on_progress: impl FnMut(u64, Option<u64>),
+  ) -> Result<u64, Error> {
+    let mut file = tokio::fs::File::create(path).await?;
+
+    self.download(storage_path, &mut file, on_progress).await
+  }
+}
+
+/// The value of the `If-Range` header that resumes a download only if the item
+/// is still the one of the `headers`, `If-Range` only accepts a strong ETag
+pub(crate) fn range_validator(headers: &header::HeaderMap) -> Option<header::HeaderValue> {
+  headers
+    .get(header::ETAG)
+    .filter(|etag| !etag.as_bytes().starts_with(b"W/"))
+    .or_else(|| headers.get(header::LAST_MODIFIED))
+    .cloned()
+}
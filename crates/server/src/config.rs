@@ -0,0 +1,479 @@
+use std::path::Path;
+use std::path::PathBuf;
+
+use storage::BucketNaming;
+
+use crate::tls::TlsConfig;
+use crate::v1;
+
+/// The configuration of a whole server instance, usually loaded from a
+/// `shcs.toml` file with [ServerConfig::load].
+///
+/// Every top-level value can be overridden by an environment variable of the
+/// same name in uppercase and prefixed by `SHCS_`, for example `SHCS_ROOT`.
+/// Values of an API version section are prefixed by the version too:
+/// `SHCS_V1_AUTHENTICATION_ENDPOINT`.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+  /// The `host:port` addresses the server listens on for plain HTTP
+  /// connections
+  pub listen: Vec<String>,
+
+  /// The directory where the buckets are stored
+  pub root: PathBuf,
+
+  /// The maximum number of items a single bucket can hold before a new one is
+  /// created, see [storage::initialize]. It is a shorthand for an `item_count`
+  /// `bucket_policy` and cannot be used alongside it.
+  pub bucket_size: Option<usize>,
+
+  /// Decides when new buckets are created and how they are named
+  pub bucket_policy: Option<BucketPolicyConfig>,
+
+  /// The format the metadata files are written in, the existing files are
+  /// read whatever their format is
+  pub metadata_format: storage::MetadataFormat,
+
+  /// The directory where the multipart uploads are buffered, in a
+  /// `.shcs-uploads` subdirectory, before being moved into their bucket.
+  /// Defaults to the `root` so that the final move never crosses a filesystem
+  /// boundary.
+  pub tempdir: Option<PathBuf>,
+
+  /// The number of HTTP workers, defaults to the number of physical CPUs
+  pub workers: Option<usize>,
+
+  /// The number of seconds the server waits, once it received a SIGTERM, for
+  /// the in-flight requests and then for the pending completion callbacks
+  pub shutdown_timeout: u64,
+
+  /// Enables HTTPS on the addresses listed in the section
+  pub tls: Option<TlsConfig>,
+
+  /// Encrypts the items written from then on, and their metadata
+  pub encryption: Option<EncryptionConfig>,
+
+  pub v1: v1::Config,
+}
+
+impl Default for ServerConfig {
+  fn default() -> Self {
+    Self {
+      listen: vec!["127.0.0.1:8080".to_owned()],
+      root: PathBuf::from("buckets"),
+      bucket_size: None,
+      bucket_policy: None,
+      metadata_format: storage::MetadataFormat::default(),
+      tempdir: None,
+      workers: None,
+      shutdown_timeout: 30,
+      tls: None,
+      encryption: None,
+      v1: v1::Config::default(),
+    }
+  }
+}
+
+impl ServerConfig {
+  /// Read the configuration file at `path`, apply the environment overrides
+  /// and validate the result.
+  pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+    let mut config = Self::from_path(path)?;
+
+    config.apply_env()?;
+    config.validate()?;
+
+    Ok(config)
+  }
+
+  /// Read the configuration file at `path` as is, without applying the
+  /// environment overrides nor validating it.
+  pub fn from_path(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+    let path = path.as_ref();
+    let content = std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.into(), e))?;
+
+    Ok(toml::from_str(&content)?)
+  }
+
+  /// Override the values of the configuration with the `SHCS_*` environment
+  /// variables that are set.
+  pub fn apply_env(&mut self) -> Result<(), ConfigError> {
+    if let Some(listen) = env("SHCS_LISTEN") {
+      self.listen = listen.split(',').map(|s| s.trim().to_owned()).collect();
+    }
+
+    if let Some(root) = env("SHCS_ROOT") {
+      self.root = root.into();
+    }
+
+    if let Some(size) = env_parse("SHCS_BUCKET_SIZE")? {
+      self.bucket_size = Some(size);
+    }
+
+    if let Some(format) = env_parse("SHCS_METADATA_FORMAT")? {
+      self.metadata_format = format;
+    }
+
+    if let Some(tempdir) = env("SHCS_TEMPDIR") {
+      self.tempdir = Some(tempdir.into());
+    }
+
+    if let Some(workers) = env_parse("SHCS_WORKERS")? {
+      self.workers = Some(workers);
+    }
+
+    if let Some(timeout) = env_parse("SHCS_SHUTDOWN_TIMEOUT")? {
+      self.shutdown_timeout = timeout;
+    }
+
+    if let Some(tls) = &mut self.tls {
+      tls.apply_env();
+    }
+
+    if let Some(encryption) = &mut self.encryption {
+      encryption.apply_env();
+    }
+
+    self.v1.apply_env()
+  }
+
+  /// Ensure the configuration holds values the server can start with
+  pub fn validate(&self) -> Result<(), ConfigError> {
+    let tls_listen = self.tls.as_ref().map(|tls| tls.listen.as_slice());
+
+    if self.listen.is_empty() && tls_listen.unwrap_or_default().is_empty() {
+      return Err(ConfigError::invalid(
+        "listen",
+        "at least one address is required",
+      ));
+    }
+
+    validate_addresses("listen", &self.listen)?;
+
+    if let Some(tls) = &self.tls {
+      validate_addresses("tls.listen", &tls.listen)?;
+      tls.validate()?;
+    }
+
+    if self.root.as_os_str().is_empty() {
+      return Err(ConfigError::invalid("root", "cannot be empty"));
+    }
+
+    if self.bucket_size == Some(0) {
+      return Err(ConfigError::invalid(
+        "bucket_size",
+        "must be greater than 0",
+      ));
+    }
+
+    if self.bucket_size.is_some() && self.bucket_policy.is_some() {
+      return Err(ConfigError::invalid(
+        "bucket_size",
+        "cannot be used alongside a bucket_policy",
+      ));
+    }
+
+    if let Some(policy) = &self.bucket_policy {
+      policy.validate()?;
+    }
+
+    if self.workers == Some(0) {
+      return Err(ConfigError::invalid("workers", "must be greater than 0"));
+    }
+
+    if let Some(encryption) = &self.encryption {
+      encryption.keyring()?;
+    }
+
+    self.v1.validate()
+  }
+
+  /// The directory where the multipart uploads are buffered
+  pub fn tempdir(&self) -> &Path {
+    self.tempdir.as_deref().unwrap_or(&self.root)
+  }
+
+  /// The keyring the storage encrypts the items with, if the encryption is
+  /// enabled
+  pub fn keyring(&self) -> Result<Option<storage::Keyring>, ConfigError> {
+    self
+      .encryption
+      .as_ref()
+      .map(EncryptionConfig::keyring)
+      .transpose()
+  }
+
+  /// The policy the storage is initialized with
+  pub fn bucket_policy(&self) -> Box<dyn storage::BucketPolicy> {
+    match &self.bucket_policy {
+      Some(policy) => policy.build(),
+      None => Box::new(storage::ItemCount::new(
+        self
+          .bucket_size
+          .unwrap_or(storage::constants::BUCKET_SIZE_MAX),
+      )),
+    }
+  }
+}
+
+/// The `[bucket_policy]` section of the [ServerConfig]:
+///
+/// ```toml
+/// [bucket_policy]
+/// kind = "total_bytes" # or "item_count", "daily", "monthly"
+/// max = 10737418240
+/// naming = "timestamp" # or "random"
+/// prefix = "archive-"
+/// ```
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum BucketPolicyConfig {
+  /// See [storage::ItemCount]
+  ItemCount {
+    max: usize,
+    #[serde(default)]
+    naming: BucketNamingConfig,
+    #[serde(default)]
+    prefix: String,
+  },
+
+  /// See [storage::TotalBytes]
+  TotalBytes {
+    max: u64,
+    #[serde(default)]
+    naming: BucketNamingConfig,
+    #[serde(default)]
+    prefix: String,
+  },
+
+  /// See [storage::Periodic::Daily]
+  Daily,
+
+  /// See [storage::Periodic::Monthly]
+  Monthly,
+}
+
+#[derive(Debug, Clone, Copy, Default, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BucketNamingConfig {
+  /// See [storage::RandomNaming]
+  #[default]
+  Random,
+
+  /// See [storage::TimestampNaming]
+  Timestamp,
+}
+
+impl BucketPolicyConfig {
+  fn validate(&self) -> Result<(), ConfigError> {
+    let max_is_zero = match self {
+      BucketPolicyConfig::ItemCount { max, .. } => *max == 0,
+      BucketPolicyConfig::TotalBytes { max, .. } => *max == 0,
+      BucketPolicyConfig::Daily | BucketPolicyConfig::Monthly => false,
+    };
+
+    if max_is_zero {
+      return Err(ConfigError::invalid(
+        "bucket_policy.max",
+        "must be greater than 0",
+      ));
+    }
+
+    let prefix = match self {
+      BucketPolicyConfig::ItemCount { prefix, .. } => prefix.as_str(),
+      BucketPolicyConfig::TotalBytes { prefix, .. } => prefix.as_str(),
+      BucketPolicyConfig::Daily | BucketPolicyConfig::Monthly => "",
+    };
+
+    if storage::BucketName::new(format!("{prefix}0")).is_err() {
+      return Err(ConfigError::invalid(
+        "bucket_policy.prefix",
+        "can only contain ASCII letters, digits, `-`, `_` and `.`, and cannot start with `.`",
+      ));
+    }
+
+    Ok(())
+  }
+
+  fn build(&self) -> Box<dyn storage::BucketPolicy> {
+    match self {
+      BucketPolicyConfig::ItemCount {
+        max,
+        naming,
+        prefix,
+      } => Box::new(storage::ItemCount::new(*max).with_naming(naming.build(prefix))),
+      BucketPolicyConfig::TotalBytes {
+        max,
+        naming,
+        prefix,
+      } => Box::new(storage::TotalBytes::new(*max).with_naming(naming.build(prefix))),
+      BucketPolicyConfig::Daily => Box::new(storage::Periodic::Daily),
+      BucketPolicyConfig::Monthly => Box::new(storage::Periodic::Monthly),
+    }
+  }
+}
+
+impl BucketNamingConfig {
+  fn build(self, prefix: &str) -> Box<dyn storage::BucketNaming> {
+    let prefix = prefix.to_owned();
+
+    match self {
+      BucketNamingConfig::Random => {
+        Box::new(move || format!("{prefix}{}", storage::RandomNaming.name()))
+      }
+      BucketNamingConfig::Timestamp => Box::new(storage::TimestampNaming { prefix }),
+    }
+  }
+}
+
+/// The `[encryption]` section of the [ServerConfig], see [storage::Keyring].
+/// The keys are 32 bytes encoded as 64 hex characters, `openssl rand -hex 32`
+/// generates one.
+///
+/// ```toml
+/// [encryption]
+/// master_key_file = "master.key"
+/// previous_key_files = ["master.key.old"] # until `shcs rotate-keys` is run
+/// ```
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EncryptionConfig {
+  /// The key that wraps the data keys of the new items, `master_key_file`
+  /// keeps it out of the configuration file
+  pub master_key: Option<String>,
+
+  /// Path to a file holding the master key
+  pub master_key_file: Option<PathBuf>,
+
+  /// The master keys of before the last rotations, only used to read the
+  /// items written before them
+  pub previous_keys: Vec<String>,
+
+  /// Paths to files holding previous master keys
+  pub previous_key_files: Vec<PathBuf>,
+}
+
+impl EncryptionConfig {
+  /// Override the master key of the section with the
+  /// `SHCS_ENCRYPTION_MASTER_KEY` or `SHCS_ENCRYPTION_MASTER_KEY_FILE`
+  /// environment variable if one is set.
+  pub(crate) fn apply_env(&mut self) {
+    if let Some(key) = env("SHCS_ENCRYPTION_MASTER_KEY") {
+      self.master_key = Some(key);
+      self.master_key_file = None;
+    }
+
+    if let Some(path) = env("SHCS_ENCRYPTION_MASTER_KEY_FILE") {
+      self.master_key = None;
+      self.master_key_file = Some(path.into());
+    }
+  }
+
+  /// Parse the keys of the section, and read the ones in files
+  pub fn keyring(&self) -> Result<storage::Keyring, ConfigError> {
+    let current = match (&self.master_key, &self.master_key_file) {
+      (Some(key), None) => parse_key("encryption.master_key", key)?,
+      (None, Some(path)) => read_key("encryption.master_key_file", path)?,
+      _ => {
+        return Err(ConfigError::invalid(
+          "encryption.master_key",
+          "exactly one of master_key and master_key_file is required",
+        ))
+      }
+    };
+
+    let mut keyring = storage::Keyring::new(current);
+
+    for key in &self.previous_keys {
+      keyring = keyring.with_previous(parse_key("encryption.previous_keys", key)?);
+    }
+
+    for path in &self.previous_key_files {
+      keyring = keyring.with_previous(read_key("encryption.previous_key_files", path)?);
+    }
+
+    Ok(keyring)
+  }
+}
+
+fn parse_key(field: &'static str, key: &str) -> Result<storage::MasterKey, ConfigError> {
+  key.parse().map_err(|e| ConfigError::invalid(field, e))
+}
+
+fn read_key(field: &'static str, path: &Path) -> Result<storage::MasterKey, ConfigError> {
+  let key = std::fs::read_to_string(path)
+    .map_err(|e| ConfigError::invalid(field, format!("could not read {path:?}: {e}")))?;
+
+  parse_key(field, &key)
+}
+
+fn validate_addresses(field: &'static str, addresses: &[String]) -> Result<(), ConfigError> {
+  for address in addresses {
+    if std::net::ToSocketAddrs::to_socket_addrs(address).is_err() {
+      return Err(ConfigError::invalid(
+        field,
+        format!("`{address}` is not a valid host:port address"),
+      ));
+    }
+  }
+
+  Ok(())
+}
+
+/// Returns the value of the environment variable `key` if it is set
+pub(crate) fn env(key: &str) -> Option<String> {
+  std::env::var(key).ok()
+}
+
+/// Returns the parsed value of the environment variable `key` if it is set
+pub(crate) fn env_parse<T>(key: &'static str) -> Result<Option<T>, ConfigError>
+where
+  T: std::str::FromStr,
+  T::Err: std::fmt::Display,
+{
+  env(key)
+    .map(|value| value.parse().map_err(|e| ConfigError::invalid(key, e)))
+    .transpose()
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+  Io(PathBuf, std::io::Error),
+  Toml(toml::de::Error),
+
+  /// The `field` holds a value the server cannot start with
+  Invalid {
+    field: &'static str,
+    reason: String,
+  },
+}
+
+impl ConfigError {
+  pub(crate) fn invalid(field: &'static str, reason: impl std::fmt::Display) -> Self {
+    Self::Invalid {
+      field,
+      reason: reason.to_string(),
+    }
+  }
+}
+
+impl From<toml::de::Error> for ConfigError {
+  fn from(value: toml::de::Error) -> Self {
+    Self::Toml(value)
+  }
+}
+
+impl std::fmt::Display for ConfigError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ConfigError::Io(path, e) => write!(f, "could not read config file {path:?}: {e}"),
+      ConfigError::Toml(e) => write!(f, "invalid config file: {e}"),
+      ConfigError::Invalid { field, reason } => {
+        write!(f, "invalid config value `{field}`: {reason}")
+      }
+    }
+  }
+}
+
+impl std::error::Error for ConfigError {}
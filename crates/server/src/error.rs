@@ -0,0 +1,39 @@
+use crate::config::ConfigError;
+
+/// The errors that prevent the server from starting
+#[derive(Debug)]
+pub enum ServerError {
+  Config(ConfigError),
+  Storage(storage::StorageError),
+  Io(std::io::Error),
+}
+
+impl From<ConfigError> for ServerError {
+  fn from(value: ConfigError) -> Self {
+    Self::Config(value)
+  }
+}
+
+impl From<storage::StorageError> for ServerError {
+  fn from(value: storage::StorageError) -> Self {
+    Self::Storage(value)
+  }
+}
+
+impl From<std::io::Error> for ServerError {
+  fn from(value: std::io::Error) -> Self {
+    Self::Io(value)
+  }
+}
+
+impl std::fmt::Display for ServerError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ServerError::Config(e) => write!(f, "{e}"),
+      ServerError::Storage(e) => write!(f, "storage error: {e}"),
+      ServerError::Io(e) => write!(f, "io error: {e}"),
+    }
+  }
+}
+
+impl std::error::Error for ServerError {}
@@ -1,4 +1,7 @@
+use std::sync::Arc;
+
 use actix_web::middleware::Logger;
+use actix_web::web::Data;
 use actix_web::{App, HttpServer};
 
 use actix_web::web::get;
@@ -6,34 +9,122 @@ use actix_web::web::scope;
 
 pub mod v1;
 
+mod config;
+pub use config::BucketNamingConfig;
+pub use config::BucketPolicyConfig;
+pub use config::ConfigError;
+pub use config::EncryptionConfig;
+pub use config::ServerConfig;
+
+mod error;
+pub use error::ServerError;
+
+mod shutdown;
+use shutdown::BackgroundTasks;
+
+mod tls;
+pub use tls::ClientCertificate;
+pub use tls::TlsConfig;
+
 pub use storage::StorageError;
 
-pub async fn launch_server(
-  port: u16, buckets_folder: impl Into<std::path::PathBuf>,
-) -> Result<(), storage::StorageError> {
-  let buckets_folder: std::path::PathBuf = buckets_folder.into();
-  let tempfolder = buckets_folder.clone().join("");
+#[cfg(test)]
+mod tests;
+
+/// The interval at which the items that outlived the TTL of their bucket are
+/// removed
+const EXPIRATION_INTERVAL: std::time::Duration = std::time::Duration::from_secs(600);
+
+pub async fn launch_server(config: ServerConfig) -> Result<(), ServerError> {
+  config.validate()?;
+
+  let tempfolder = shutdown::uploads_directory(config.tempdir());
+  std::fs::create_dir_all(&tempfolder)?;
+
+  let removed = shutdown::remove_orphaned_tempfiles(&tempfolder)?;
+  if removed > 0 {
+    println!("INFO: removed {removed} tempfiles left by interrupted uploads");
+  }
+
+  // set first, the index may be rebuilt from encrypted metadata files
+  storage::set_keyring(config.keyring()?)?;
+  storage::initialize_with_policy(&config.root, config.bucket_policy())?;
+  storage::set_metadata_format(config.metadata_format)?;
+
+  actix_web::rt::spawn(remove_expired_items());
 
-  storage::initialize(buckets_folder, None)?;
+  let v1_config = Data::new(config.v1.clone());
+  let background_tasks = Data::new(BackgroundTasks::new());
+  let shutdown_timeout = std::time::Duration::from_secs(config.shutdown_timeout);
 
-  let _ = HttpServer::new(move || {
+  let tls = match &config.tls {
+    Some(tls) => {
+      let resolver = Arc::new(tls::CertificateResolver::new(tls.clone())?);
+      actix_web::rt::spawn(tls::watch_certificates(resolver.clone()));
+
+      Some((tls, tls::server_config(tls, resolver)?))
+    }
+    None => None,
+  };
+
+  let server_background_tasks = background_tasks.clone();
+  let mut server = HttpServer::new(move || {
     let logger = Logger::default();
+    let v1_config = v1_config.clone();
 
     App::new()
       .wrap(logger)
+      .app_data(server_background_tasks.clone())
       .app_data(
         actix_multipart::form::tempfile::TempFileConfig::default().directory(tempfolder.clone()),
       )
       .route("robots.txt", get().to(robots_txt))
-      .service(scope("/v1").configure(v1::router))
+      .service(scope("/v1").configure(|cfg| v1::router(cfg, v1_config)))
   })
-  .bind(format!("127.0.0.1:{port}"))?
-  .run()
-  .await?;
+  .on_connect(tls::on_connect)
+  .shutdown_timeout(config.shutdown_timeout);
+
+  if let Some(workers) = config.workers {
+    server = server.workers(workers);
+  }
+
+  for address in &config.listen {
+    server = server.bind(address)?;
+  }
+
+  if let Some((tls, rustls_config)) = tls {
+    for address in &tls.listen {
+      server = server.bind_rustls_0_23(address, rustls_config.clone())?;
+    }
+  }
+
+  // the server handles the SIGTERM itself: it stops accepting connections and
+  // waits for the in-flight requests before returning.
+  server.run().await?;
+
+  let pending = background_tasks.wait(shutdown_timeout).await;
+  if pending > 0 {
+    println!("WARN: shutting down with {pending} unfinished background tasks");
+  }
 
   Ok(())
 }
 
+/// Remove the expired items periodically, runs until the server stops
+async fn remove_expired_items() {
+  let mut interval = tokio::time::interval(EXPIRATION_INTERVAL);
+
+  loop {
+    interval.tick().await;
+
+    match storage::nonblocking::buckets::remove_expired().await {
+      Ok(0) => {}
+      Ok(removed) => println!("INFO: removed {removed} expired items"),
+      Err(e) => println!("ERROR: expired items removal failure: {e}"),
+    };
+  }
+}
+
 /// This robots.txt disable everything from being indexed
 async fn robots_txt() -> &'static str {
   "User-agent: *
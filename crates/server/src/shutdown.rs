@@ -0,0 +1,86 @@
+//! Everything needed to stop the server without losing work: the background
+//! tasks the server waits for before exiting, and the cleanup of the uploads
+//! that were interrupted by a previous stop.
+use std::future::Future;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use tokio_util::task::TaskTracker;
+
+/// The prefix of the tempfiles created by the multipart extractor
+const TEMPFILE_PREFIX: &str = ".tmp";
+
+/// The directory of the `tempdir` the uploads are buffered in. Only the server
+/// writes in it, so that its leftovers can be removed without touching the
+/// files of other processes when the `tempdir` is shared, like `/tmp`.
+const UPLOADS_DIRECTORY: &str = ".shcs-uploads";
+
+/// The directory the multipart uploads are buffered in, inside the `tempdir`
+pub(crate) fn uploads_directory(tempdir: &Path) -> PathBuf {
+  tempdir.join(UPLOADS_DIRECTORY)
+}
+
+/// Runs tasks on the main runtime rather than on the runtime of the HTTP
+/// worker that spawned them, so that they keep running when the worker is
+/// stopped and can be waited for once the server is no longer accepting
+/// connections.
+#[derive(Debug, Clone)]
+pub(crate) struct BackgroundTasks {
+  handle: tokio::runtime::Handle,
+  tracker: TaskTracker,
+}
+
+impl BackgroundTasks {
+  /// Must be called from the main runtime of the server
+  pub(crate) fn new() -> Self {
+    Self {
+      handle: tokio::runtime::Handle::current(),
+      tracker: TaskTracker::new(),
+    }
+  }
+
+  pub(crate) fn spawn<F>(&self, task: F) -> tokio::task::JoinHandle<F::Output>
+  where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+  {
+    self.handle.spawn(self.tracker.track_future(task))
+  }
+
+  /// Wait up to `timeout` for the pending tasks to finish, returns the number
+  /// of tasks that were still running after it.
+  pub(crate) async fn wait(&self, timeout: Duration) -> usize {
+    self.tracker.close();
+
+    match tokio::time::timeout(timeout, self.tracker.wait()).await {
+      Ok(()) => 0,
+      Err(_) => self.tracker.len(),
+    }
+  }
+}
+
+/// Remove the tempfiles of the multipart uploads that were interrupted before
+/// they could be moved to their bucket, returns the number of removed files.
+/// `directory` is the [uploads_directory], the rest of the `tempdir` is left
+/// untouched.
+///
+/// Must only be called before the server starts accepting uploads.
+pub(crate) fn remove_orphaned_tempfiles(directory: &Path) -> std::io::Result<usize> {
+  let mut removed = 0;
+
+  for entry in std::fs::read_dir(directory)? {
+    let entry = entry?;
+    let is_tempfile = entry
+      .file_name()
+      .to_str()
+      .is_some_and(|name| name.starts_with(TEMPFILE_PREFIX));
+
+    if is_tempfile && entry.file_type()?.is_file() {
+      std::fs::remove_file(entry.path())?;
+      removed += 1;
+    }
+  }
+
+  Ok(removed)
+}
@@ -0,0 +1,168 @@
+use crate::ConfigError;
+use crate::ServerConfig;
+
+/// The environment is global to the process, the tests that read or change it
+/// run one at a time
+static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Returns the configuration read from a file holding the `content`
+fn config_file(content: &str) -> Result<ServerConfig, ConfigError> {
+  let file = tempfile::NamedTempFile::new().expect("the tempfile is created");
+  std::fs::write(file.path(), content).expect("the tempfile is written");
+
+  ServerConfig::from_path(file.path())
+}
+
+fn invalid_field(result: Result<(), ConfigError>) -> Option<&'static str> {
+  match result {
+    Err(ConfigError::Invalid { field, .. }) => Some(field),
+    _ => None,
+  }
+}
+
+#[test]
+fn test_config_parsing() -> Result<(), ConfigError> {
+  let config = config_file(
+    r#"
+    listen = ["0.0.0.0:4000", "127.0.0.1:4001"]
+    root = "data"
+    tempdir = "uploads"
+    workers = 2
+
+    [bucket_policy]
+    kind = "total_bytes"
+    max = 1024
+    naming = "timestamp"
+    prefix = "archive-"
+
+    [v1]
+    enabled = true
+    authentication_endpoint = "http://localhost:5000/auth"
+    completion_endpoint = "http://localhost:5000/finish"
+    multipart_total_limit = 1048576
+    "#,
+  )?;
+
+  assert_eq!(config.listen, ["0.0.0.0:4000", "127.0.0.1:4001"]);
+  assert_eq!(config.root, std::path::Path::new("data"));
+  assert_eq!(config.tempdir(), std::path::Path::new("uploads"));
+  assert_eq!(config.workers, Some(2));
+  assert!(matches!(
+    config.bucket_policy,
+    Some(crate::BucketPolicyConfig::TotalBytes { max: 1024, .. })
+  ));
+  assert_eq!(config.v1.multipart_total_limit(), Some(1048576));
+  config.validate()?;
+
+  // the missing values fall back to their defaults
+  let config = config_file("root = \"data\"")?;
+  assert_eq!(config.listen, ["127.0.0.1:8080"]);
+  assert_eq!(config.tempdir(), std::path::Path::new("data"));
+  assert_eq!(config.shutdown_timeout, 30);
+  assert!(!config.v1.enabled());
+
+  assert!(matches!(
+    config_file("port = 4000"),
+    Err(ConfigError::Toml(_))
+  ));
+  assert!(matches!(
+    ServerConfig::from_path("missing.toml"),
+    Err(ConfigError::Io(..))
+  ));
+
+  Ok(())
+}
+
+#[test]
+fn test_config_env_overrides() -> Result<(), ConfigError> {
+  let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+  let mut config = config_file("root = \"data\"")?;
+
+  std::env::set_var("SHCS_LISTEN", "127.0.0.1:4000, 127.0.0.1:4001");
+  std::env::set_var("SHCS_ROOT", "overridden");
+  std::env::set_var("SHCS_V1_MULTIPART_TOTAL_LIMIT", "42");
+  std::env::set_var("SHCS_V1_EXTRACT_MAX_ENTRIES", "7");
+  let result = config.apply_env();
+  std::env::remove_var("SHCS_LISTEN");
+  std::env::remove_var("SHCS_ROOT");
+  std::env::remove_var("SHCS_V1_MULTIPART_TOTAL_LIMIT");
+  std::env::remove_var("SHCS_V1_EXTRACT_MAX_ENTRIES");
+  result?;
+
+  assert_eq!(config.listen, ["127.0.0.1:4000", "127.0.0.1:4001"]);
+  assert_eq!(config.root, std::path::Path::new("overridden"));
+  assert_eq!(config.v1.multipart_total_limit(), Some(42));
+  assert_eq!(config.v1.extract_limits().max_entries, 7);
+
+  // a value that cannot be parsed names its variable
+  std::env::set_var("SHCS_WORKERS", "many");
+  let result = config.apply_env();
+  std::env::remove_var("SHCS_WORKERS");
+  assert_eq!(invalid_field(result), Some("SHCS_WORKERS"));
+
+  Ok(())
+}
+
+#[test]
+fn test_config_validation() -> Result<(), ConfigError> {
+  let cases = [
+    ("listen = []", "listen"),
+    ("listen = [\"localhost\"]", "listen"),
+    ("root = \"\"", "root"),
+    ("bucket_size = 0", "bucket_size"),
+    ("workers = 0", "workers"),
+    (
+      "bucket_size = 10\n[bucket_policy]\nkind = \"daily\"",
+      "bucket_size",
+    ),
+    (
+      "[bucket_policy]\nkind = \"item_count\"\nmax = 0",
+      "bucket_policy.max",
+    ),
+    (
+      "[bucket_policy]\nkind = \"item_count\"\nmax = 10\nprefix = \"../\"",
+      "bucket_policy.prefix",
+    ),
+    ("[encryption]", "encryption.master_key"),
+    (
+      "[v1]\nenabled = true\nauthentication_endpoint = \"not a url\"",
+      "v1.authentication_endpoint",
+    ),
+    ("[v1]\nextract_max_ratio = 0", "v1.extract_max_ratio"),
+    ("[v1]\nthumbnail_cache_size = 0", "v1.thumbnail_cache_size"),
+  ];
+
+  for (content, field) in cases {
+    assert_eq!(
+      invalid_field(config_file(content)?.validate()),
+      Some(field),
+      "{content:?}"
+    );
+  }
+
+  // a disabled API doesn't need its endpoints
+  config_file("[v1]\nenabled = false")?.validate()?;
+
+  Ok(())
+}
+
+#[test]
+fn test_orphaned_tempfiles_removal() -> std::io::Result<()> {
+  use crate::shutdown;
+
+  let tempdir = tempfile::tempdir()?;
+  let uploads = shutdown::uploads_directory(tempdir.path());
+  std::fs::create_dir_all(&uploads)?;
+
+  // the tempfiles of another process, in the shared tempdir
+  let foreign = tempdir.path().join(".tmpforeign");
+  let orphan = uploads.join(".tmporphan");
+  std::fs::write(&foreign, "")?;
+  std::fs::write(&orphan, "")?;
+
+  assert_eq!(shutdown::remove_orphaned_tempfiles(&uploads)?, 1);
+  assert!(foreign.exists());
+  assert!(!orphan.exists());
+
+  Ok(())
+}
@@ -0,0 +1,288 @@
+//! Native HTTPS support. The certificates are reloaded whenever the process
+//! receives a SIGHUP, or when the files change on disk, without having to
+//! restart the server.
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::RwLock;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use actix_tls::accept::rustls_0_23::TlsStream;
+use actix_web::dev::Extensions;
+use actix_web::rt::net::TcpStream;
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::CertificateDer;
+use rustls::pki_types::PrivateKeyDer;
+use rustls::server::ClientHello;
+use rustls::server::ResolvesServerCert;
+use rustls::server::WebPkiClientVerifier;
+use rustls::sign::CertifiedKey;
+
+use crate::config::env;
+use crate::config::ConfigError;
+
+/// The `[tls]` section of the [crate::ServerConfig]
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+  /// The `host:port` addresses the server listens on for HTTPS connections
+  pub listen: Vec<String>,
+
+  /// Path to the PEM encoded certificate chain
+  pub certificate: PathBuf,
+
+  /// Path to the PEM encoded private key of the certificate
+  pub key: PathBuf,
+
+  /// Path to the PEM encoded authorities that sign the client certificates.
+  /// Setting it enables mutual TLS, and the subject of a verified client
+  /// certificate is then used as the authenticated identifier of the requests
+  /// instead of calling the `authentication_endpoint`.
+  #[serde(default)]
+  pub client_ca: Option<PathBuf>,
+
+  /// Refuse the clients that don't present a certificate during the handshake,
+  /// only used alongside `client_ca`
+  #[serde(default)]
+  pub client_auth_required: bool,
+
+  /// The interval, in seconds, at which the certificate files are checked for
+  /// changes
+  #[serde(default = "TlsConfig::default_reload_interval")]
+  pub reload_interval: u64,
+}
+
+impl TlsConfig {
+  fn default_reload_interval() -> u64 {
+    60
+  }
+
+  /// Override the values of the section with the `SHCS_TLS_*` environment
+  /// variables that are set.
+  pub(crate) fn apply_env(&mut self) {
+    if let Some(listen) = env("SHCS_TLS_LISTEN") {
+      self.listen = listen.split(',').map(|s| s.trim().to_owned()).collect();
+    }
+
+    if let Some(certificate) = env("SHCS_TLS_CERTIFICATE") {
+      self.certificate = certificate.into();
+    }
+
+    if let Some(key) = env("SHCS_TLS_KEY") {
+      self.key = key.into();
+    }
+
+    if let Some(client_ca) = env("SHCS_TLS_CLIENT_CA") {
+      self.client_ca = Some(client_ca.into());
+    }
+  }
+
+  pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+    if self.listen.is_empty() {
+      return Err(ConfigError::invalid(
+        "tls.listen",
+        "at least one address is required",
+      ));
+    }
+
+    // loading the files is the only way to know they can be used
+    load_certified_key(self)?;
+
+    if let Some(path) = &self.client_ca {
+      load_roots(path)?;
+    }
+
+    Ok(())
+  }
+}
+
+/// The subject of the verified certificate the client presented during the
+/// handshake, stored in the connection data by [on_connect]
+#[derive(Debug, Clone)]
+pub struct ClientCertificate(pub String);
+
+/// Serves the latest certificate that was successfully loaded from the disk
+#[derive(Debug)]
+pub(crate) struct CertificateResolver {
+  config: TlsConfig,
+  certified_key: RwLock<Arc<CertifiedKey>>,
+  modified: RwLock<Option<SystemTime>>,
+}
+
+impl CertificateResolver {
+  pub(crate) fn new(config: TlsConfig) -> Result<Self, ConfigError> {
+    let certified_key = load_certified_key(&config)?;
+    let modified = last_modified(&config);
+
+    Ok(Self {
+      config,
+      certified_key: RwLock::new(Arc::new(certified_key)),
+      modified: RwLock::new(modified),
+    })
+  }
+
+  /// Load the certificate files again, the currently served certificate is
+  /// kept if they are invalid.
+  fn reload(&self) -> Result<(), ConfigError> {
+    let modified = last_modified(&self.config);
+    let certified_key = load_certified_key(&self.config)?;
+
+    if let Ok(mut current) = self.certified_key.write() {
+      *current = Arc::new(certified_key);
+    }
+
+    if let Ok(mut current) = self.modified.write() {
+      *current = modified;
+    }
+
+    Ok(())
+  }
+
+  fn files_changed(&self) -> bool {
+    let modified = last_modified(&self.config);
+
+    self
+      .modified
+      .read()
+      .map(|current| *current != modified)
+      .unwrap_or_default()
+  }
+}
+
+impl ResolvesServerCert for CertificateResolver {
+  fn resolve(&self, _: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+    self.certified_key.read().ok().map(|key| key.clone())
+  }
+}
+
+/// Build the rustls configuration the HTTPS listeners are bound with
+pub(crate) fn server_config(
+  config: &TlsConfig, resolver: Arc<CertificateResolver>,
+) -> Result<rustls::ServerConfig, ConfigError> {
+  let builder = rustls::ServerConfig::builder_with_provider(provider())
+    .with_safe_default_protocol_versions()
+    .map_err(|e| ConfigError::invalid("tls", e))?;
+
+  let builder = match &config.client_ca {
+    Some(path) => {
+      let mut verifier = WebPkiClientVerifier::builder_with_provider(load_roots(path)?, provider());
+
+      if !config.client_auth_required {
+        verifier = verifier.allow_unauthenticated();
+      }
+
+      let verifier = verifier
+        .build()
+        .map_err(|e| ConfigError::invalid("tls.client_ca", e))?;
+
+      builder.with_client_cert_verifier(verifier)
+    }
+    None => builder.with_no_client_auth(),
+  };
+
+  Ok(builder.with_cert_resolver(resolver))
+}
+
+/// Reload the certificates whenever a SIGHUP is received or the files are
+/// modified, runs until the server stops.
+pub(crate) async fn watch_certificates(resolver: Arc<CertificateResolver>) {
+  let period = Duration::from_secs(resolver.config.reload_interval.max(1));
+  let mut interval = tokio::time::interval(period);
+
+  #[cfg(unix)]
+  let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).ok();
+
+  loop {
+    #[cfg(unix)]
+    let forced = tokio::select! {
+      _ = interval.tick() => false,
+      Some(_) = async { hangup.as_mut()?.recv().await } => true,
+    };
+
+    #[cfg(not(unix))]
+    let forced = {
+      interval.tick().await;
+      false
+    };
+
+    if !forced && !resolver.files_changed() {
+      continue;
+    }
+
+    match resolver.reload() {
+      Ok(()) => println!("INFO: tls certificates reloaded"),
+      Err(e) => println!("ERROR: tls certificates reload failure: {e}"),
+    };
+  }
+}
+
+/// Store the subject of the client certificate, if any, in the connection data
+/// so that it is available to every request of the connection.
+pub(crate) fn on_connect(connection: &dyn std::any::Any, data: &mut Extensions) {
+  let Some(stream) = connection.downcast_ref::<TlsStream<TcpStream>>() else {
+    return;
+  };
+
+  let (_, session) = stream.get_ref();
+  let subject = session
+    .peer_certificates()
+    .and_then(|certificates| certificates.first())
+    .and_then(|certificate| x509_parser::parse_x509_certificate(certificate).ok())
+    .map(|(_, certificate)| certificate.subject().to_string());
+
+  if let Some(subject) = subject {
+    data.insert(ClientCertificate(subject));
+  }
+}
+
+fn provider() -> Arc<CryptoProvider> {
+  Arc::new(rustls::crypto::ring::default_provider())
+}
+
+fn load_certified_key(config: &TlsConfig) -> Result<CertifiedKey, ConfigError> {
+  let certificates = CertificateDer::pem_file_iter(&config.certificate)
+    .and_then(|certificates| certificates.collect::<Result<Vec<_>, _>>())
+    .map_err(|e| ConfigError::invalid("tls.certificate", e))?;
+
+  if certificates.is_empty() {
+    return Err(ConfigError::invalid(
+      "tls.certificate",
+      "no certificate found in the file",
+    ));
+  }
+
+  let key =
+    PrivateKeyDer::from_pem_file(&config.key).map_err(|e| ConfigError::invalid("tls.key", e))?;
+
+  let key = provider()
+    .key_provider
+    .load_private_key(key)
+    .map_err(|e| ConfigError::invalid("tls.key", e))?;
+
+  Ok(CertifiedKey::new(certificates, key))
+}
+
+fn load_roots(path: &Path) -> Result<Arc<rustls::RootCertStore>, ConfigError> {
+  let mut roots = rustls::RootCertStore::empty();
+
+  for certificate in
+    CertificateDer::pem_file_iter(path).map_err(|e| ConfigError::invalid("tls.client_ca", e))?
+  {
+    let certificate = certificate.map_err(|e| ConfigError::invalid("tls.client_ca", e))?;
+
+    roots
+      .add(certificate)
+      .map_err(|e| ConfigError::invalid("tls.client_ca", e))?;
+  }
+
+  Ok(Arc::new(roots))
+}
+
+/// Returns the most recent modification time of the certificate files
+fn last_modified(config: &TlsConfig) -> Option<SystemTime> {
+  let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+
+  modified(&config.certificate).max(modified(&config.key))
+}
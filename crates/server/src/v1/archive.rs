@@ -0,0 +1,262 @@
+//! The endpoint streaming a set of items as a single archive, that is written
+//! by a blocking task as the response is sent so it is never held in full on
+//! disk or in memory. The decompressed size of a compressed item, that a tar
+//! header needs up front, is the one recorded in its system metadata
+use std::collections::HashSet;
+use std::io::Read;
+use std::io::Write;
+
+use actix_web::http::header::ContentDisposition;
+use actix_web::http::header::DispositionParam;
+use actix_web::http::header::DispositionType;
+use actix_web::web::Data;
+use actix_web::web::Json;
+use actix_web::HttpResponse;
+use storage::ItemName;
+use storage::StoragePath;
+
+use super::sdk;
+use super::sdk::ArchiveFormat;
+use super::ApiError;
+use super::BearerToken;
+use super::Config;
+use super::Metadata;
+
+/// An item of the archive
+pub(super) struct Entry {
+  /// The name of the file in the archive
+  pub(super) name: String,
+  pub(super) path: StoragePath,
+}
+
+/// The content of an [Entry] opened as it is written to the archive
+struct Opened {
+  /// The size of the decrypted and decompressed content
+  size: u64,
+  modified: std::time::SystemTime,
+  content: Box<dyn Read + Send>,
+}
+
+pub(super) async fn download_archive(
+  body: Json<sdk::ArchiveRequest>, token: BearerToken, config: Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+  let identifier = token.authenticate(&config, sdk::Operation::Archive).await?;
+
+  let request = body.into_inner();
+  let entries = entries(selected_paths(&request).await?).await?;
+
+  token.complete(&config, identifier).await?;
+
+  let format = request.format;
+  let stream = super::stream::blocking_stream(move |writer| write_archive(format, entries, writer));
+
+  Ok(
+    HttpResponse::Ok()
+      .content_type(format.content_type())
+      .insert_header(ContentDisposition {
+        disposition: DispositionType::Attachment,
+        parameters: vec![DispositionParam::Filename(format!(
+          "archive.{}",
+          format.extension()
+        ))],
+      })
+      .streaming(stream),
+  )
+}
+
+/// The storage paths of the items the `request` selects
+async fn selected_paths(request: &sdk::ArchiveRequest) -> Result<Vec<StoragePath>, ApiError> {
+  let Some(bucket) = &request.bucket else {
+    if request.prefix.is_some() {
+      return Err(ApiError::BadRequest("a prefix requires a bucket"));
+    }
+
+    if request.paths.is_empty() {
+      return Err(ApiError::BadRequest("select either paths or a bucket"));
+    }
+
+    return Ok(request.paths.clone());
+  };
+
+  if !request.paths.is_empty() {
+    return Err(ApiError::BadRequest("select either paths or a bucket"));
+  }
+
+  let prefix = request.prefix.as_deref().unwrap_or_default();
+  let items = storage::nonblocking::buckets::items(bucket).await?;
+
+  Ok(
+    items
+      .into_iter()
+      .filter(|item| item.as_str().starts_with(prefix))
+      .map(|item| StoragePath::new(bucket.clone(), item))
+      .collect(),
+  )
+}
+
+/// Name the item at every path after its alias, without clashes between the
+/// names, and skip the paths of the missing items
+pub(super) async fn entries(paths: Vec<StoragePath>) -> Result<Vec<Entry>, ApiError> {
+  let mut taken = HashSet::new();
+  let mut entries = Vec::with_capacity(paths.len());
+
+  for path in paths {
+    if !storage::nonblocking::exists(&path).await? {
+      continue;
+    }
+
+    let metadata: Option<Metadata> = storage::nonblocking::deserialize_metadata(&path).await?;
+    let alias = metadata.map(|m| m.alias).unwrap_or_default();
+    let name = deduplicate(entry_name(&alias, &path.item), &mut taken);
+
+    entries.push(Entry { name, path });
+  }
+
+  Ok(entries)
+}
+
+/// The `alias` without the characters that could place the entry outside of
+/// the folder the archive is extracted to, or the `item` name when nothing is
+/// left of it
+fn entry_name(alias: &str, item: &ItemName) -> String {
+  let name: String = alias
+    .chars()
+    .map(|c| match c {
+      '/' | '\\' => '_',
+      c if c.is_control() => '_',
+      c => c,
+    })
+    .collect();
+
+  match name.trim() {
+    "" | "." | ".." => item.to_string(),
+    _ => name,
+  }
+}
+
+/// Number the `name` like `report (1).pdf` until it isn't `taken`
+fn deduplicate(name: String, taken: &mut HashSet<String>) -> String {
+  if taken.insert(name.clone()) {
+    return name;
+  }
+
+  let (stem, extension) = match name.rfind('.') {
+    Some(dot) if dot > 0 => name.split_at(dot),
+    _ => (name.as_str(), ""),
+  };
+
+  let mut n = 1;
+  loop {
+    let candidate = format!("{stem} ({n}){extension}");
+
+    if taken.insert(candidate.clone()) {
+      return candidate;
+    }
+
+    n += 1;
+  }
+}
+
+/// Write the `entries` to the `writer`, skipping the items that expired or were
+/// removed since the archive was requested
+pub(super) fn write_archive(
+  format: ArchiveFormat, entries: Vec<Entry>, writer: impl Write,
+) -> std::io::Result<()> {
+  match format {
+    ArchiveFormat::Zip => {
+      let mut zip = zip::ZipWriter::new_stream(writer);
+
+      for entry in entries {
+        let Some(Opened {
+          size,
+          modified,
+          mut content,
+        }) = entry.open()?
+        else {
+          continue;
+        };
+        let modified = chrono::DateTime::<chrono::Utc>::from(modified);
+
+        let mut options = zip::write::SimpleFileOptions::default()
+          .compression_method(zip::CompressionMethod::Deflated)
+          .large_file(size > u32::MAX as u64);
+        if let Ok(modified) = zip::DateTime::try_from(modified.naive_utc()) {
+          options = options.last_modified_time(modified);
+        }
+
+        zip.start_file(entry.name, options)?;
+        std::io::copy(&mut content, &mut zip)?;
+      }
+
+      zip.finish()?;
+    }
+    ArchiveFormat::TarGz => {
+      let encoder = flate2::write::GzEncoder::new(writer, flate2::Compression::default());
+      let mut tar = tar::Builder::new(encoder);
+
+      for entry in entries {
+        let Some(Opened {
+          size,
+          modified,
+          content,
+        }) = entry.open()?
+        else {
+          continue;
+        };
+
+        let mut header = tar::Header::new_gnu();
+        header.set_size(size);
+        header.set_mode(0o644);
+        header.set_mtime(super::metadata::unix_timestamp(modified).max(0) as u64);
+
+        // exactly the size of the header is appended, so that a recorded size
+        // that went stale cannot shift the following entries
+        let content = content.chain(std::io::repeat(0)).take(size);
+        tar.append_data(&mut header, &entry.name, content)?;
+      }
+
+      tar.into_inner()?.finish()?;
+    }
+  }
+
+  Ok(())
+}
+
+impl Entry {
+  /// The last modification of the content of the item, with a reader of its
+  /// decrypted and decompressed content, or None when the item expired or was
+  /// removed. The size is the one of the opened file, or the decompressed size
+  /// recorded in the system metadata of a compressed item, so that its content
+  /// never has to be read twice nor written to disk decompressed
+  fn open(&self) -> std::io::Result<Option<Opened>> {
+    let content = match storage::open(&self.path) {
+      Ok((content, _)) => content,
+      Err(storage::StorageError::Io(error)) if error.kind() == std::io::ErrorKind::NotFound => {
+        return Ok(None);
+      }
+      Err(error) => return Err(std::io::Error::other(error)),
+    };
+    let modified = content.file().metadata()?.modified()?;
+
+    let metadata: Option<Metadata> =
+      storage::deserialize_metadata(&self.path).map_err(std::io::Error::other)?;
+
+    // the encoding is only ever recorded alongside the decompressed size
+    match metadata.and_then(|metadata| metadata.system) {
+      Some(super::SystemMetadata {
+        encoding: Some(encoding),
+        size,
+        ..
+      }) => Ok(Some(Opened {
+        size,
+        modified,
+        content: encoding.decoder(content).map_err(std::io::Error::other)?,
+      })),
+      _ => Ok(Some(Opened {
+        size: content.size().map_err(std::io::Error::other)?,
+        modified,
+        content: Box::new(content),
+      })),
+    }
+  }
+}
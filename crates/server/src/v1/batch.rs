@@ -0,0 +1,102 @@
+//! The endpoint running many operations on the items with a single
+//! authentication
+use actix_web::http::header::EntityTag;
+use actix_web::http::header::IfMatch;
+use actix_web::web::Data;
+use actix_web::web::Json;
+use actix_web::HttpResponse;
+use actix_web::ResponseError;
+use futures_util::StreamExt;
+
+use super::sdk;
+use super::sdk::BatchOperation;
+use super::sdk::BatchResult;
+use super::ApiError;
+use super::BearerToken;
+use super::Config;
+
+/// The maximum number of operations in a batch
+const MAX_OPERATIONS: usize = 1_000;
+
+/// The number of operations of a batch that run at the same time
+const CONCURRENCY: usize = 16;
+
+pub(super) async fn run_batch(
+  operations: Json<Vec<BatchOperation>>, token: BearerToken, config: Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+  let identifier = token.authenticate(&config, sdk::Operation::Batch).await?;
+
+  let results = run_operations(operations.into_inner()).await?;
+
+  token.complete(&config, identifier).await?;
+  Ok(HttpResponse::Ok().json(results))
+}
+
+/// Run the `operations`, a few at a time, and return their results in the
+/// order of the operations
+pub(super) async fn run_operations(
+  operations: Vec<BatchOperation>,
+) -> Result<Vec<BatchResult>, ApiError> {
+  if operations.len() > MAX_OPERATIONS {
+    return Err(ApiError::BadRequest(
+      "a batch holds at most 1000 operations",
+    ));
+  }
+
+  Ok(
+    futures_util::stream::iter(operations)
+      .map(run)
+      .buffered(CONCURRENCY)
+      .collect()
+      .await,
+  )
+}
+
+async fn run(operation: BatchOperation) -> BatchResult {
+  let result = match operation {
+    BatchOperation::Delete { path } => storage::nonblocking::remove(&path)
+      .await
+      .map(|_| None)
+      .map_err(ApiError::from),
+    BatchOperation::MetadataSet {
+      path,
+      custom,
+      revision,
+    } => {
+      let if_match = revision.map(|r| IfMatch::Items(vec![EntityTag::new_strong(r.to_string())]));
+
+      super::update_metadata(&path, if_match, move |metadata| {
+        metadata.custom = custom;
+        metadata.revision += 1;
+
+        Ok(())
+      })
+      .await
+      .map(|metadata| Some(serde_json::json!({ "revision": metadata.revision })))
+    }
+    BatchOperation::MetadataGet { path } => super::existing_metadata(&path)
+      .await
+      .map(|metadata| Some(metadata.and_then(|m| m.custom).unwrap_or_default())),
+    BatchOperation::Copy { path, destination } => storage::nonblocking::copy(&path, &destination)
+      .await
+      .map(|_| None)
+      .map_err(ApiError::from),
+    BatchOperation::Stat { path } => super::file_stat(&path)
+      .await
+      .and_then(|stat| serde_json::to_value(stat).map_err(|_| ApiError::InternalServerError))
+      .map(Some),
+  };
+
+  match result {
+    Ok(body) => BatchResult {
+      status: 200,
+      body,
+      error: None,
+    },
+    Err(e) => BatchResult {
+      status: e.status_code().as_u16(),
+      body: None,
+      error: Some(e.to_string()),
+    },
+  }
+}
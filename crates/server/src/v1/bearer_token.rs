@@ -6,25 +6,53 @@ use actix_web::HttpRequest;
 
 use super::ApiError;
 use super::Config;
+use crate::BackgroundTasks;
+use crate::ClientCertificate;
 
-/// A user extracted from the `Authorization` Bearer token
+/// A user extracted from the `Authorization` Bearer token, or from the client
+/// certificate of the connection when mutual TLS is enabled.
 #[derive(Debug)]
 pub struct BearerToken {
-  authorization: reqwest::header::HeaderValue,
+  authorization: Option<reqwest::header::HeaderValue>,
+  client_certificate: Option<ClientCertificate>,
+
+  /// Where the completion callback runs so that it outlives the HTTP worker
+  background_tasks: Option<actix_web::web::Data<BackgroundTasks>>,
 }
 
 /// Holds the response from the successful authententication of the [BearerToken]
 #[derive(Debug, Clone)]
 pub struct AuthenticatedBearerIdentifier(String);
 
+impl AuthenticatedBearerIdentifier {
+  /// The identifier returned by the authentication endpoint, or the subject of
+  /// the client certificate
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+}
+
 impl FromRequest for BearerToken {
   type Error = ApiError;
   type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
 
   fn from_request(req: &HttpRequest, _: &mut actix_web::dev::Payload) -> Self::Future {
     let token_header = req.headers().get("Authorization").cloned();
+    let client_certificate = req.conn_data::<ClientCertificate>().cloned();
+    let background_tasks = req
+      .app_data::<actix_web::web::Data<BackgroundTasks>>()
+      .cloned();
 
     Box::pin(async move {
+      // a verified client certificate is enough to identify the user
+      if client_certificate.is_some() && token_header.is_none() {
+        return Ok(Self {
+          authorization: None,
+          client_certificate,
+          background_tasks,
+        });
+      }
+
       token_header
         .ok_or(ApiError::Unauthorized)
         .and_then(|header| {
@@ -34,7 +62,11 @@ impl FromRequest for BearerToken {
             .map(|s| s.to_owned())
         })
         .and_then(|s| s.parse().map_err(|_| ApiError::Unauthorized))
-        .map(|s| Self { authorization: s })
+        .map(|s| Self {
+          authorization: Some(s),
+          client_certificate,
+          background_tasks,
+        })
     })
   }
 }
@@ -45,9 +77,22 @@ impl BearerToken {
   ///
   /// Any error or any response other than a 200: OK yields an UNAUTHORIZED
   /// error.
+  ///
+  /// If the connection presented a client certificate that was verified during
+  /// the TLS handshake then its subject is used as the identifier, and the
+  /// authentication endpoint is not contacted.
   pub async fn authenticate(
     &self, config: &Config, action: super::sdk::Operation,
   ) -> Result<AuthenticatedBearerIdentifier, ApiError> {
+    if let Some(ClientCertificate(subject)) = &self.client_certificate {
+      println!(
+        "{} - {} - authenticated by client certificate",
+        subject, action
+      );
+
+      return Ok(AuthenticatedBearerIdentifier(subject.clone()));
+    }
+
     match self.is_authorized(config, action).await {
       Ok(Some(identifier)) => Ok(identifier),
       _ => Err(ApiError::Unauthorized),
@@ -57,10 +102,14 @@ impl BearerToken {
   async fn is_authorized(
     &self, config: &Config, action: super::sdk::Operation,
   ) -> Result<Option<AuthenticatedBearerIdentifier>, ApiError> {
+    let Some(authorization) = self.authorization.clone() else {
+      return Ok(None);
+    };
+
     let url = config.authentication_endpoint();
 
     let mut headers = reqwest::header::HeaderMap::new();
-    headers.append("Authorization", self.authorization.clone());
+    headers.append("Authorization", authorization);
 
     let uuid = self.uuid();
     let body = serde_json::to_string(&action).map_err(|_| ApiError::InternalServerError)?;
@@ -91,20 +140,24 @@ impl BearerToken {
       false => None,
     };
 
-    Ok(identifier.map(|s| AuthenticatedBearerIdentifier(s)))
+    Ok(identifier.map(AuthenticatedBearerIdentifier))
   }
 
+  /// Notify the configured completion endpoint that the operation succeeded.
+  ///
+  /// The notification runs as a background task of the server, so it is still
+  /// sent during a graceful shutdown even if the HTTP worker is stopped first.
   pub async fn complete(
     self, config: &Config, identifier: AuthenticatedBearerIdentifier,
   ) -> Result<(), ApiError> {
     async fn internal(
-      config: &Config, authorization: reqwest::header::HeaderValue,
+      url: String, authorization: Option<reqwest::header::HeaderValue>,
       identifier: AuthenticatedBearerIdentifier,
     ) -> Result<reqwest::Response, ApiError> {
-      let url = config.completion_endpoint();
-
       let mut headers = reqwest::header::HeaderMap::new();
-      headers.append("Authorization", authorization);
+      if let Some(authorization) = authorization {
+        headers.append("Authorization", authorization);
+      }
 
       let body = identifier.0;
       let client = reqwest::Client::new();
@@ -119,12 +172,22 @@ impl BearerToken {
       Ok(resp)
     }
 
-    // catch any kind of error that may happen:
-    match internal(config, self.authorization, identifier.clone()).await {
-      Err(e) => {
-        println!("{identifier:?} error: {e:?}");
+    let url = config.completion_endpoint().to_owned();
+    let notification = async move {
+      // catch any kind of error that may happen:
+      match internal(url, self.authorization, identifier.clone()).await {
+        Err(e) => {
+          println!("{identifier:?} error: {e:?}");
+        }
+        Ok(_resp) => {}
+      };
+    };
+
+    match self.background_tasks {
+      Some(tasks) => {
+        let _ = tasks.spawn(notification).await;
       }
-      Ok(_resp) => {}
+      None => notification.await,
     };
 
     Ok(())
@@ -0,0 +1,146 @@
+//! The endpoints to manage the buckets explicitly
+use actix_web::web::Data;
+use actix_web::web::Json;
+use actix_web::web::Path;
+use actix_web::web::Query;
+use actix_web::HttpResponse;
+
+use storage::BucketName;
+
+use super::bearer_token::AuthenticatedBearerIdentifier;
+use super::sdk;
+use super::ApiError;
+use super::BearerToken;
+use super::Config;
+
+/// The names that would be shadowed by the other routes of the API
+const RESERVED_NAMES: [&str; 2] = ["active", "buckets"];
+
+#[derive(Debug, serde::Deserialize)]
+pub(super) struct RenameBucketBody {
+  name: BucketName,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub(super) struct DeleteBucketQuery {
+  #[serde(default)]
+  force: bool,
+}
+
+fn ensure_not_reserved(name: &BucketName) -> Result<(), ApiError> {
+  match RESERVED_NAMES.contains(&name.as_str()) {
+    true => Err(ApiError::BadRequest("reserved bucket name")),
+    false => Ok(()),
+  }
+}
+
+/// Authenticate the read of an item of the `bucket` if the bucket is private.
+/// Returns the token and its identifier so that the operation can be completed
+/// once the item is served.
+pub(super) async fn authorize_read(
+  bucket: &BucketName, token: Option<BearerToken>, config: &Config,
+) -> Result<Option<(BearerToken, AuthenticatedBearerIdentifier)>, ApiError> {
+  let settings = storage::nonblocking::buckets::settings(bucket).await?;
+
+  if settings.visibility == storage::Visibility::Public {
+    return Ok(None);
+  }
+
+  let token = token.ok_or(ApiError::Unauthorized)?;
+  let identifier = token.authenticate(config, sdk::Operation::Read).await?;
+
+  Ok(Some((token, identifier)))
+}
+
+pub(super) async fn create_bucket(
+  path: Path<BucketName>, settings: Option<Json<storage::BucketSettings>>, token: BearerToken,
+  config: Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+  let identifier = token
+    .authenticate(&config, sdk::Operation::BucketCreate)
+    .await?;
+
+  let name = path.into_inner();
+  ensure_not_reserved(&name)?;
+
+  let settings = settings.map(|s| s.into_inner()).unwrap_or_default();
+  storage::nonblocking::buckets::create(&name, settings).await?;
+
+  token.complete(&config, identifier).await?;
+  Ok(HttpResponse::Created().finish())
+}
+
+pub(super) async fn list_buckets(
+  token: BearerToken, config: Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+  let identifier = token
+    .authenticate(&config, sdk::Operation::BucketDescribe)
+    .await?;
+
+  let buckets = storage::nonblocking::buckets::list().await?;
+
+  token.complete(&config, identifier).await?;
+  Ok(HttpResponse::Ok().json(buckets))
+}
+
+pub(super) async fn describe_bucket(
+  path: Path<BucketName>, token: BearerToken, config: Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+  let identifier = token
+    .authenticate(&config, sdk::Operation::BucketDescribe)
+    .await?;
+
+  let name = path.into_inner();
+  let info = storage::nonblocking::buckets::describe(&name).await?;
+
+  token.complete(&config, identifier).await?;
+  Ok(HttpResponse::Ok().json(info))
+}
+
+pub(super) async fn update_bucket_settings(
+  path: Path<BucketName>, settings: Json<storage::BucketSettings>, token: BearerToken,
+  config: Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+  let identifier = token
+    .authenticate(&config, sdk::Operation::BucketUpdate)
+    .await?;
+
+  let name = path.into_inner();
+  let settings = settings.into_inner();
+  storage::nonblocking::buckets::set_settings(&name, settings).await?;
+
+  token.complete(&config, identifier).await?;
+  Ok(HttpResponse::Ok().finish())
+}
+
+pub(super) async fn rename_bucket(
+  path: Path<BucketName>, body: Json<RenameBucketBody>, token: BearerToken, config: Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+  let identifier = token
+    .authenticate(&config, sdk::Operation::BucketRename)
+    .await?;
+
+  let from = path.into_inner();
+  let to = body.into_inner().name;
+  ensure_not_reserved(&to)?;
+
+  storage::nonblocking::buckets::rename(&from, &to).await?;
+
+  token.complete(&config, identifier).await?;
+  Ok(HttpResponse::Ok().finish())
+}
+
+pub(super) async fn delete_bucket(
+  path: Path<BucketName>, query: Query<DeleteBucketQuery>, token: BearerToken, config: Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+  let identifier = token
+    .authenticate(&config, sdk::Operation::BucketDelete)
+    .await?;
+
+  let name = path.into_inner();
+  let force = query.force;
+  storage::nonblocking::buckets::delete(&name, force).await?;
+
+  token.complete(&config, identifier).await?;
+  Ok(HttpResponse::Ok().finish())
+}
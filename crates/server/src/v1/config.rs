@@ -1,6 +1,10 @@
-use super::ApiError;
+use crate::config::env;
+use crate::config::env_parse;
+use crate::config::ConfigError;
 
-#[derive(Debug, serde::Deserialize, Default)]
+/// The `[v1]` section of the [crate::ServerConfig]
+#[derive(Debug, Clone, serde::Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
   enabled: bool,
 
@@ -12,25 +16,190 @@ pub struct Config {
   /// completed operation.
   completion_endpoint: String,
 
-  multipart_total_limit: Option<i64>,
+  multipart_total_limit: Option<usize>,
+
+  /// The maximum width of the thumbnails, in pixels
+  thumbnail_max_width: Option<u32>,
+
+  /// The maximum height of the thumbnails, in pixels
+  thumbnail_max_height: Option<u32>,
+
+  /// The maximum number of thumbnails cached per item
+  thumbnail_cache_size: Option<usize>,
+
+  /// The maximum size of the images thumbnails are made of, in bytes
+  thumbnail_max_source_bytes: Option<u64>,
+
+  /// The maximum number of files in an extracted archive
+  extract_max_entries: Option<usize>,
+
+  /// The maximum size of the files of an extracted archive, in bytes
+  extract_max_bytes: Option<u64>,
+
+  /// The maximum ratio between the size of the extracted files and the size of
+  /// the archive
+  extract_max_ratio: Option<u64>,
 }
 
+/// The maximum width and height of the thumbnails when they aren't configured
+const THUMBNAIL_MAX_SIZE_DEFAULT: u32 = 2048;
+
+/// The number of thumbnails cached per item when it isn't configured
+const THUMBNAIL_CACHE_SIZE_DEFAULT: usize = 16;
+
+/// The maximum size of the images thumbnails are made of when it isn't
+/// configured, 50MiB
+const THUMBNAIL_MAX_SOURCE_BYTES_DEFAULT: u64 = 50 * 1024 * 1024;
+
 impl Config {
-  /// Obtain a version of the configuration file from the disk
-  pub fn from_disk() -> Result<Self, ApiError> {
-    let content = std::fs::read_to_string("v1.shcs.toml")?;
+  /// Override the values of the section with the `SHCS_V1_*` environment
+  /// variables that are set.
+  pub(crate) fn apply_env(&mut self) -> Result<(), ConfigError> {
+    if let Some(enabled) = env_parse("SHCS_V1_ENABLED")? {
+      self.enabled = enabled;
+    }
+
+    if let Some(endpoint) = env("SHCS_V1_AUTHENTICATION_ENDPOINT") {
+      self.authentication_endpoint = endpoint;
+    }
+
+    if let Some(endpoint) = env("SHCS_V1_COMPLETION_ENDPOINT") {
+      self.completion_endpoint = endpoint;
+    }
+
+    if let Some(limit) = env_parse("SHCS_V1_MULTIPART_TOTAL_LIMIT")? {
+      self.multipart_total_limit = Some(limit);
+    }
+
+    if let Some(width) = env_parse("SHCS_V1_THUMBNAIL_MAX_WIDTH")? {
+      self.thumbnail_max_width = Some(width);
+    }
+
+    if let Some(height) = env_parse("SHCS_V1_THUMBNAIL_MAX_HEIGHT")? {
+      self.thumbnail_max_height = Some(height);
+    }
+
+    if let Some(size) = env_parse("SHCS_V1_THUMBNAIL_CACHE_SIZE")? {
+      self.thumbnail_cache_size = Some(size);
+    }
+
+    if let Some(bytes) = env_parse("SHCS_V1_THUMBNAIL_MAX_SOURCE_BYTES")? {
+      self.thumbnail_max_source_bytes = Some(bytes);
+    }
+
+    if let Some(entries) = env_parse("SHCS_V1_EXTRACT_MAX_ENTRIES")? {
+      self.extract_max_entries = Some(entries);
+    }
+
+    if let Some(bytes) = env_parse("SHCS_V1_EXTRACT_MAX_BYTES")? {
+      self.extract_max_bytes = Some(bytes);
+    }
 
-    Ok(toml::from_str(&content)?)
+    if let Some(ratio) = env_parse("SHCS_V1_EXTRACT_MAX_RATIO")? {
+      self.extract_max_ratio = Some(ratio);
+    }
+
+    Ok(())
+  }
+
+  pub(crate) fn validate(&self) -> Result<(), ConfigError> {
+    if self.thumbnail_cache_size == Some(0) {
+      return Err(ConfigError::invalid(
+        "v1.thumbnail_cache_size",
+        "must be greater than 0",
+      ));
+    }
+
+    if self.extract_max_entries == Some(0) {
+      return Err(ConfigError::invalid(
+        "v1.extract_max_entries",
+        "must be greater than 0",
+      ));
+    }
+
+    if self.extract_max_bytes == Some(0) {
+      return Err(ConfigError::invalid(
+        "v1.extract_max_bytes",
+        "must be greater than 0",
+      ));
+    }
+
+    if self.extract_max_ratio == Some(0) {
+      return Err(ConfigError::invalid(
+        "v1.extract_max_ratio",
+        "must be greater than 0",
+      ));
+    }
+
+    // a disabled API doesn't need its endpoints
+    if !self.enabled {
+      return Ok(());
+    }
+
+    if reqwest::Url::parse(&self.authentication_endpoint).is_err() {
+      return Err(ConfigError::invalid(
+        "v1.authentication_endpoint",
+        "must be a valid URL",
+      ));
+    }
+
+    if reqwest::Url::parse(&self.completion_endpoint).is_err() {
+      return Err(ConfigError::invalid(
+        "v1.completion_endpoint",
+        "must be a valid URL",
+      ));
+    }
+
+    Ok(())
   }
 
-  pub fn enabled() -> Result<bool, ApiError> {
-    Self::from_disk().map(|c| c.enabled)
+  pub fn enabled(&self) -> bool {
+    self.enabled
   }
 
-  pub fn multipart_total_limit(&self) -> Option<i64> {
+  pub fn multipart_total_limit(&self) -> Option<usize> {
     self.multipart_total_limit
   }
 
+  /// The maximum width and height of the thumbnails, in pixels
+  pub fn thumbnail_max_size(&self) -> (u32, u32) {
+    (
+      self
+        .thumbnail_max_width
+        .unwrap_or(THUMBNAIL_MAX_SIZE_DEFAULT),
+      self
+        .thumbnail_max_height
+        .unwrap_or(THUMBNAIL_MAX_SIZE_DEFAULT),
+    )
+  }
+
+  /// The maximum number of thumbnails cached per item, the oldest ones are
+  /// evicted past it
+  pub fn thumbnail_cache_size(&self) -> usize {
+    self
+      .thumbnail_cache_size
+      .unwrap_or(THUMBNAIL_CACHE_SIZE_DEFAULT)
+  }
+
+  /// The maximum size of the images thumbnails are made of, in bytes
+  pub fn thumbnail_max_source_bytes(&self) -> u64 {
+    self
+      .thumbnail_max_source_bytes
+      .unwrap_or(THUMBNAIL_MAX_SOURCE_BYTES_DEFAULT)
+  }
+
+  /// The limits of the archives expanded by the uploads with `?extract=true`
+  pub fn extract_limits(&self) -> super::extract::ExtractLimits {
+    let defaults = super::extract::ExtractLimits::default();
+
+    super::extract::ExtractLimits {
+      max_entries: self.extract_max_entries.unwrap_or(defaults.max_entries),
+      max_bytes: self.extract_max_bytes.unwrap_or(defaults.max_bytes),
+      max_ratio: self.extract_max_ratio.unwrap_or(defaults.max_ratio),
+      ..defaults
+    }
+  }
+
   pub fn authentication_endpoint(&self) -> &str {
     &self.authentication_endpoint
   }
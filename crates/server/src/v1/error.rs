@@ -1,10 +1,26 @@
 use actix_web::HttpResponse;
 
+use super::sdk;
+
 #[derive(Debug)]
 pub enum ApiError {
   Storage(storage::StorageError),
   InternalServerError,
   Unauthorized,
+  NotFound,
+  BadRequest(&'static str),
+
+  /// The JSON body or the query string of the request couldn't be parsed
+  InvalidRequest(String),
+
+  /// The MIME type of the upload is not allowed by the bucket
+  UnsupportedMediaType,
+
+  /// The `If-Match` header doesn't match the current revision
+  PreconditionFailed,
+
+  /// The upload exceeds a limit of the server
+  PayloadTooLarge(&'static str),
 }
 
 impl From<storage::StorageError> for ApiError {
@@ -15,6 +31,12 @@ impl From<storage::StorageError> for ApiError {
   }
 }
 
+impl From<storage::ParseError> for ApiError {
+  fn from(value: storage::ParseError) -> Self {
+    Self::from(storage::StorageError::from(value))
+  }
+}
+
 impl From<std::io::Error> for ApiError {
   fn from(value: std::io::Error) -> Self {
     println!("io error: {value}");
@@ -31,14 +53,6 @@ impl From<actix_web::error::BlockingError> for ApiError {
   }
 }
 
-impl From<toml::de::Error> for ApiError {
-  fn from(value: toml::de::Error) -> Self {
-    println!("v1 config deserialize error: {value}");
-
-    Self::InternalServerError
-  }
-}
-
 impl From<reqwest::Error> for ApiError {
   fn from(value: reqwest::Error) -> Self {
     println!("reqwest error: {value}");
@@ -53,24 +67,85 @@ impl std::fmt::Display for ApiError {
       ApiError::Storage(e) => write!(f, "storage error: {e}"),
       Self::Unauthorized => write!(f, "unauthorized"),
       Self::InternalServerError => write!(f, "internal server error"),
+      Self::NotFound => write!(f, "not found"),
+      Self::BadRequest(reason) => write!(f, "bad request: {reason}"),
+      Self::InvalidRequest(reason) => write!(f, "invalid request: {reason}"),
+      Self::UnsupportedMediaType => write!(f, "unsupported media type"),
+      Self::PreconditionFailed => write!(f, "precondition failed"),
+      Self::PayloadTooLarge(reason) => write!(f, "payload too large: {reason}"),
     }
   }
 }
 
 impl actix_web::ResponseError for ApiError {
   fn status_code(&self) -> actix_web::http::StatusCode {
+    use actix_web::http::StatusCode;
+    use storage::StorageError;
+
     match self {
-      ApiError::Storage(_) => actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
-      ApiError::Unauthorized => actix_web::http::StatusCode::UNAUTHORIZED,
-      ApiError::InternalServerError => actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+      ApiError::Storage(StorageError::BucketNotFound) => StatusCode::NOT_FOUND,
+      ApiError::Storage(StorageError::BucketAlreadyExists) => StatusCode::CONFLICT,
+      ApiError::Storage(StorageError::BucketNotEmpty) => StatusCode::CONFLICT,
+      ApiError::Storage(StorageError::BucketFull) => StatusCode::PAYLOAD_TOO_LARGE,
+      ApiError::Storage(StorageError::InvalidBucketName) => StatusCode::BAD_REQUEST,
+      ApiError::Storage(StorageError::InvalidItemName) => StatusCode::BAD_REQUEST,
+      ApiError::Storage(StorageError::MissingBucketName) => StatusCode::BAD_REQUEST,
+      ApiError::Storage(StorageError::MissingItemName) => StatusCode::BAD_REQUEST,
+      ApiError::Storage(StorageError::TrailingPathSegments) => StatusCode::BAD_REQUEST,
+      ApiError::Storage(StorageError::InvalidQuery(_)) => StatusCode::BAD_REQUEST,
+      ApiError::Storage(StorageError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+        StatusCode::NOT_FOUND
+      }
+      ApiError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+      ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+      ApiError::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
+      ApiError::NotFound => StatusCode::NOT_FOUND,
+      ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+      ApiError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+      ApiError::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+      ApiError::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+      ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
     }
   }
 
+  /// Answers an `application/problem+json` body, see [sdk::Problem]
   fn error_response(&self) -> actix_web::HttpResponse<actix_web::body::BoxBody> {
+    let status = self.status_code();
+    let problem = sdk::Problem {
+      status: status.as_u16(),
+      title: status.canonical_reason().unwrap_or_default().to_owned(),
+      detail: self.detail(),
+    };
+
+    HttpResponse::build(status)
+      .content_type("application/problem+json")
+      .body(serde_json::to_string(&problem).unwrap_or_default())
+  }
+}
+
+impl ApiError {
+  /// What the client did wrong, the internal errors are not detailed
+  fn detail(&self) -> Option<String> {
+    use actix_web::ResponseError;
+    use storage::StorageError;
+
     match self {
-      ApiError::Storage(_) => HttpResponse::InternalServerError().finish(),
-      ApiError::Unauthorized => HttpResponse::Unauthorized().finish(),
-      ApiError::InternalServerError => HttpResponse::InternalServerError().finish(),
+      ApiError::BadRequest(reason) | ApiError::PayloadTooLarge(reason) => Some(reason.to_string()),
+      ApiError::InvalidRequest(reason) => Some(reason.clone()),
+      ApiError::Storage(StorageError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+        Some("item not found".to_owned())
+      }
+      ApiError::Storage(e) if self.status_code().is_client_error() => Some(e.to_string()),
+      ApiError::UnsupportedMediaType => {
+        Some("the bucket doesn't accept this type of file".to_owned())
+      }
+      ApiError::PreconditionFailed => {
+        Some("the metadata changed since the given revision".to_owned())
+      }
+      ApiError::Storage(_)
+      | ApiError::InternalServerError
+      | ApiError::Unauthorized
+      | ApiError::NotFound => None,
     }
   }
 }
@@ -0,0 +1,288 @@
+//! The upload mode that expands a ZIP or tar archive into one item per file,
+//! with the path of each file in the archive as the alias of its item
+use std::io::Read;
+use std::io::Write;
+use std::path::PathBuf;
+
+use sha2::Digest;
+use storage::BucketName;
+
+use super::bearer_token::AuthenticatedBearerIdentifier;
+use super::sdk;
+use super::upload_body::unique_item_name;
+use super::ApiError;
+use super::Metadata;
+use super::SystemMetadata;
+use super::UploadFileBody;
+
+/// The limits of an extracted archive, past which nothing is stored
+#[derive(Debug, Clone, Copy)]
+pub struct ExtractLimits {
+  /// The maximum number of files in an archive
+  pub max_entries: usize,
+
+  /// The maximum size of the files of an archive once extracted
+  pub max_bytes: u64,
+
+  /// The maximum ratio between the size of the extracted files and the size of
+  /// the archive, above which it is considered a zip bomb
+  pub max_ratio: u64,
+
+  /// The size of the extracted files that is allowed whatever the compression
+  /// ratio, as small archives of text compress well
+  pub ratio_free_bytes: u64,
+}
+
+impl Default for ExtractLimits {
+  fn default() -> Self {
+    Self {
+      max_entries: 10_000,
+      max_bytes: 4 * 1024 * 1024 * 1024,
+      max_ratio: 100,
+      ratio_free_bytes: 16 * 1024 * 1024,
+    }
+  }
+}
+
+/// Expand the archive of the `form` into new items of the `bucket`, or of the
+/// active bucket if None, and return the storage path of the item of every
+/// file. The custom metadata of the form is given to every item.
+///
+/// Every file is extracted before any item is stored, and the items that were
+/// stored are removed if one of them can't be, so the upload either stores the
+/// whole archive or nothing.
+pub(super) async fn extract_archive(
+  form: UploadFileBody, bucket: Option<&BucketName>, uploader: &AuthenticatedBearerIdentifier,
+  limits: ExtractLimits,
+) -> Result<Vec<sdk::ExtractedItem>, ApiError> {
+  let archive = form.file.file;
+  let extracted = actix_web::web::block(move || extract(archive, limits)).await??;
+  let custom = form.metadata.map(|json| json.0);
+
+  store_all(extracted, bucket, uploader.as_str(), custom).await
+}
+
+/// Store the `extracted` files in the `bucket`, or none of them if one can't be.
+/// Without a bucket, the active bucket is resolved for every file so that the
+/// [storage::BucketPolicy] rotates it as it fills up.
+pub(super) async fn store_all(
+  extracted: Vec<ExtractedFile>, bucket: Option<&BucketName>, uploader: &str,
+  custom: Option<serde_json::Value>,
+) -> Result<Vec<sdk::ExtractedItem>, ApiError> {
+  let mut manifest = Vec::with_capacity(extracted.len());
+
+  for file in extracted {
+    match store(file, bucket, uploader, custom.clone()).await {
+      Ok(item) => manifest.push(item),
+      Err(e) => {
+        for item in manifest {
+          let _ = storage::nonblocking::remove(&item.path).await;
+        }
+
+        return Err(e);
+      }
+    }
+  }
+
+  Ok(manifest)
+}
+
+async fn store(
+  file: ExtractedFile, bucket: Option<&BucketName>, uploader: &str,
+  custom: Option<serde_json::Value>,
+) -> Result<sdk::ExtractedItem, ApiError> {
+  let bucket = match bucket {
+    Some(bucket) => bucket.clone(),
+    None => storage::nonblocking::active_bucket().await?,
+  };
+  let bucket = &bucket;
+
+  let settings = storage::nonblocking::buckets::settings(bucket).await?;
+  if !settings.allows_mime_type(&file.content_type) {
+    return Err(ApiError::UnsupportedMediaType);
+  }
+
+  let filename = file.entry.rsplit('/').next().map(str::to_owned);
+  let (_, name) = unique_item_name(bucket, filename.as_deref()).await?;
+  let (tempfile, encoding) =
+    storage::nonblocking::compress_tempfile(bucket, file.file, &file.content_type).await?;
+
+  let now = super::metadata::unix_timestamp(std::time::SystemTime::now());
+  let metadata = Metadata {
+    alias: file.entry.clone(),
+    custom,
+    system: Some(SystemMetadata {
+      content_type: file.content_type,
+      size: file.size,
+      checksum: Some(file.checksum),
+      created_at: now,
+      modified_at: now,
+      uploader: Some(uploader.to_owned()),
+      filename,
+      encoding,
+    }),
+    revision: 0,
+  };
+
+  let path = storage::nonblocking::persist_tempfile_in(bucket, &name, tempfile, metadata).await?;
+
+  Ok(sdk::ExtractedItem {
+    entry: file.entry,
+    path,
+  })
+}
+
+/// A file of the archive, extracted next to it
+pub(super) struct ExtractedFile {
+  /// The path of the file in the archive
+  pub(super) entry: String,
+  file: tempfile::NamedTempFile,
+  size: u64,
+  checksum: String,
+  content_type: String,
+}
+
+/// Extract the regular files of the ZIP, tar or tar.gz `archive` within the
+/// `limits`, the other kinds of entries are skipped
+pub(super) fn extract(
+  archive: tempfile::NamedTempFile, limits: ExtractLimits,
+) -> Result<Vec<ExtractedFile>, ApiError> {
+  let kind = infer::get_from_path(archive.path())?.map(|kind| kind.mime_type());
+  let mut extraction = Extraction {
+    directory: archive
+      .path()
+      .parent()
+      .map(|parent| parent.to_path_buf())
+      .unwrap_or_default(),
+    budget: archive
+      .as_file()
+      .metadata()?
+      .len()
+      .saturating_mul(limits.max_ratio)
+      .max(limits.ratio_free_bytes)
+      .min(limits.max_bytes),
+    max_entries: limits.max_entries,
+    files: Vec::new(),
+  };
+
+  match kind {
+    Some("application/zip") => {
+      let mut zip = zip::ZipArchive::new(archive.reopen()?).map_err(invalid_archive)?;
+
+      if zip.len() > limits.max_entries {
+        return Err(too_many_entries());
+      }
+
+      for index in 0..zip.len() {
+        let mut entry = zip.by_index(index).map_err(invalid_archive)?;
+
+        if entry.is_file() {
+          let name = entry.name().map_err(invalid_archive)?.into_owned();
+          extraction.add(name, &mut entry)?;
+        }
+      }
+    }
+    Some("application/gzip") => {
+      let decoder = flate2::read::GzDecoder::new(archive.reopen()?);
+      extraction.add_tar(tar::Archive::new(decoder))?;
+    }
+    Some("application/x-tar") => {
+      extraction.add_tar(tar::Archive::new(archive.reopen()?))?;
+    }
+    _ => return Err(ApiError::UnsupportedMediaType),
+  }
+
+  Ok(extraction.files)
+}
+
+struct Extraction {
+  /// Where the files are extracted
+  directory: PathBuf,
+
+  /// The number of bytes that can still be extracted
+  budget: u64,
+
+  max_entries: usize,
+
+  files: Vec<ExtractedFile>,
+}
+
+impl Extraction {
+  fn add_tar(&mut self, mut archive: tar::Archive<impl Read>) -> Result<(), ApiError> {
+    for entry in archive.entries().map_err(invalid_archive)? {
+      let mut entry = entry.map_err(invalid_archive)?;
+
+      if entry.header().entry_type().is_file() {
+        let name = entry.path().map_err(invalid_archive)?;
+        let name = name.to_string_lossy().into_owned();
+        self.add(name, &mut entry)?;
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Extract the content of the `entry`, as long as it fits in the budget
+  fn add(&mut self, entry: String, content: &mut impl Read) -> Result<(), ApiError> {
+    if self.files.len() >= self.max_entries {
+      return Err(too_many_entries());
+    }
+
+    let mut file = tempfile::NamedTempFile::new_in(&self.directory)?;
+    let mut hasher = sha2::Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    let mut size = 0;
+
+    // the sizes announced by the archive aren't trusted, the bytes are counted
+    // as they are extracted
+    loop {
+      let read = content.read(&mut buffer).map_err(invalid_archive)?;
+      if read == 0 {
+        break;
+      }
+
+      size += read as u64;
+      if size > self.budget {
+        return Err(ApiError::PayloadTooLarge(
+          "the extracted files exceed the size allowed for the archive",
+        ));
+      }
+
+      hasher.update(&buffer[..read]);
+      file.write_all(&buffer[..read])?;
+    }
+
+    self.budget -= size;
+
+    let extension = std::path::Path::new(&entry)
+      .extension()
+      .and_then(|ext| ext.to_str())
+      .unwrap_or_default();
+    let content_type = match infer::get_from_path(file.path())? {
+      Some(kind) => kind.mime_type().to_owned(),
+      None => actix_files::file_extension_to_mime(extension)
+        .essence_str()
+        .to_owned(),
+    };
+
+    self.files.push(ExtractedFile {
+      entry,
+      file,
+      size,
+      checksum: format!("{:x}", hasher.finalize()),
+      content_type,
+    });
+
+    Ok(())
+  }
+}
+
+fn invalid_archive(e: impl std::fmt::Display) -> ApiError {
+  println!("archive error: {e}");
+
+  ApiError::BadRequest("the archive is invalid")
+}
+
+fn too_many_entries() -> ApiError {
+  ApiError::PayloadTooLarge("the archive holds too many files")
+}
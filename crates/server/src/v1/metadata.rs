@@ -1,8 +1,205 @@
+use actix_web::http::header::EntityTag;
+use actix_web::http::header::IfMatch;
 use serde::Deserialize;
 use serde::Serialize;
 
+use super::ApiError;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Metadata {
   pub alias: String,
   pub custom: Option<serde_json::Value>,
+
+  /// Recorded by the server when the content of the item is written, it is
+  /// missing from the metadata files written before it existed
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub system: Option<SystemMetadata>,
+
+  /// Incremented every time the metadata changes, it is the `ETag` of the
+  /// metadata endpoints
+  #[serde(default)]
+  pub revision: u64,
+}
+
+/// The description of the content of an item, that clients cannot edit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemMetadata {
+  pub content_type: String,
+
+  /// The size of the item in bytes
+  pub size: u64,
+
+  /// The hex encoded SHA-256 of the content
+  #[serde(default)]
+  pub checksum: Option<String>,
+
+  /// The unix timestamp of the first upload of the item
+  pub created_at: i64,
+
+  /// The unix timestamp of the last time the content of the item was written
+  pub modified_at: i64,
+
+  /// The identifier of the user who uploaded the item
+  pub uploader: Option<String>,
+
+  /// The name of the uploaded file
+  pub filename: Option<String>,
+
+  /// How the content is compressed at rest, the size and checksum are those of
+  /// the decompressed content
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub encoding: Option<storage::Encoding>,
+}
+
+impl SystemMetadata {
+  /// Describe an item that has no system metadata from its file, the content
+  /// type is guessed from its extension
+  pub fn from_file(item: &str, file: &std::fs::Metadata) -> std::io::Result<Self> {
+    let modified_at = unix_timestamp(file.modified()?);
+
+    Ok(Self {
+      content_type: actix_files::file_extension_to_mime(
+        std::path::Path::new(item)
+          .extension()
+          .and_then(|ext| ext.to_str())
+          .unwrap_or_default(),
+      )
+      .to_string(),
+      size: file.len(),
+      checksum: None,
+      created_at: modified_at,
+      modified_at,
+      uploader: None,
+      filename: None,
+      encoding: None,
+    })
+  }
+}
+
+/// The part of the [Metadata] clients can edit, the document the merge patches
+/// apply to
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EditableMetadata {
+  pub alias: String,
+  pub custom: Option<serde_json::Value>,
+}
+
+impl Metadata {
+  /// The metadata of an item that has no metadata file yet
+  pub fn new(alias: String) -> Self {
+    Self {
+      alias,
+      custom: None,
+      system: None,
+      revision: 0,
+    }
+  }
+
+  /// Carry over what outlives a replacement of the content from the
+  /// `previous` metadata of the item: the revision keeps increasing and the
+  /// creation time is kept
+  pub fn replaces(&mut self, previous: Option<Metadata>) {
+    let Some(previous) = previous else {
+      return;
+    };
+
+    self.revision = previous.revision + 1;
+
+    if let (Some(system), Some(previous)) = (&mut self.system, previous.system) {
+      system.created_at = previous.created_at;
+    }
+  }
+
+  /// The recorded content type of the item, if it is a valid one
+  pub fn content_type(&self) -> Option<actix_web::mime::Mime> {
+    self.system.as_ref()?.content_type.parse().ok()
+  }
+
+  pub fn etag(&self) -> EntityTag {
+    EntityTag::new_strong(self.revision.to_string())
+  }
+
+  /// Fails with a `412 Precondition Failed` unless the `If-Match` header, when
+  /// there is one, lists the current revision
+  pub fn check_revision(&self, if_match: Option<&IfMatch>) -> Result<(), ApiError> {
+    // a missing header is extracted as an empty list of tags
+    match if_match {
+      Some(IfMatch::Items(tags))
+        if !tags.is_empty() && !tags.iter().any(|tag| tag.strong_eq(&self.etag())) =>
+      {
+        Err(ApiError::PreconditionFailed)
+      }
+      _ => Ok(()),
+    }
+  }
+
+  /// Apply the RFC 7396 merge `patch` to the alias and custom metadata, and
+  /// move to the next revision
+  pub fn merge_patch(&mut self, patch: &serde_json::Value) -> Result<(), ApiError> {
+    let editable = EditableMetadata {
+      alias: std::mem::take(&mut self.alias),
+      custom: self.custom.take(),
+    };
+
+    let mut document = serde_json::to_value(editable).map_err(|_| ApiError::InternalServerError)?;
+    merge_patch(&mut document, patch);
+
+    let EditableMetadata { alias, custom } = serde_json::from_value(document).map_err(|_| {
+      ApiError::BadRequest(
+        "only the alias and custom metadata can be patched, and the alias must stay a string",
+      )
+    })?;
+
+    self.alias = alias;
+    self.custom = custom;
+    self.revision += 1;
+
+    Ok(())
+  }
+
+  pub fn editable(&self) -> EditableMetadata {
+    EditableMetadata {
+      alias: self.alias.clone(),
+      custom: self.custom.clone(),
+    }
+  }
+}
+
+/// Merge the `patch` into the `target` as described by RFC 7396: the `null`
+/// members of the patch are removed from the target, its objects are merged
+/// recursively and any other value replaces the target.
+pub(super) fn merge_patch(target: &mut serde_json::Value, patch: &serde_json::Value) {
+  let serde_json::Value::Object(patch) = patch else {
+    *target = patch.clone();
+    return;
+  };
+
+  if !target.is_object() {
+    *target = serde_json::Value::Object(Default::default());
+  }
+
+  let serde_json::Value::Object(target) = target else {
+    return;
+  };
+
+  for (key, value) in patch {
+    match value {
+      serde_json::Value::Null => {
+        target.remove(key);
+      }
+      value => merge_patch(
+        target.entry(key.clone()).or_insert(serde_json::Value::Null),
+        value,
+      ),
+    }
+  }
+}
+
+/// The number of seconds between the unix epoch and the `time`
+pub fn unix_timestamp(time: std::time::SystemTime) -> i64 {
+  match time.duration_since(std::time::UNIX_EPOCH) {
+    Ok(duration) => duration.as_secs() as i64,
+    Err(e) => -(e.duration().as_secs() as i64),
+  }
 }
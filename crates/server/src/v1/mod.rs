@@ -1,22 +1,39 @@
+use actix_web::body::SizedStream;
+use actix_web::http::header;
+use actix_web::http::header::AcceptEncoding;
 use actix_web::http::header::ContentDisposition;
+use actix_web::http::header::ContentEncoding;
 use actix_web::http::header::DispositionParam;
+use actix_web::http::header::ETag;
+use actix_web::http::header::EntityTag;
+use actix_web::http::header::HttpDate;
+use actix_web::http::header::IfMatch;
+use actix_web::http::header::LastModified;
 use actix_web::web;
 use actix_web::web::delete;
 use actix_web::web::get;
+use actix_web::web::head;
+use actix_web::web::patch;
 use actix_web::web::post;
 use actix_web::web::put;
 use actix_web::web::Data;
+use actix_web::web::Header;
 use actix_web::web::Json;
 use actix_web::web::Path;
+use actix_web::HttpRequest;
 use actix_web::HttpResponse;
 
 use actix_multipart::form::MultipartForm;
+use storage::BucketName;
+use storage::ItemName;
+use storage::StoragePath;
 
 mod config;
-use config::Config;
+pub use config::Config;
 
 mod metadata;
 use metadata::Metadata;
+use metadata::SystemMetadata;
 
 mod error;
 pub use error::ApiError;
@@ -26,213 +43,649 @@ use bearer_token::BearerToken;
 
 mod upload_body;
 use upload_body::UploadFileBody;
+use upload_body::UploadQuery;
+
+mod archive;
+
+mod batch;
+
+mod extract;
+
+mod stream;
+
+mod thumbnail;
+
+mod buckets;
+
+mod versions;
 
 pub mod sdk;
 
-pub fn router(cfg: &mut web::ServiceConfig) {
-  if !Config::enabled().unwrap_or_default() {
+#[cfg(test)]
+mod tests;
+
+pub fn router(cfg: &mut web::ServiceConfig, config: Data<Config>) {
+  if !config.enabled() {
     println!("INFO: v1 api disabled");
 
     return;
   }
 
-  let mut multipart_config = actix_multipart::form::MultipartFormConfig::default();
-
-  if let Some(limit) = Config::from_disk()
-    .ok()
-    .and_then(|c| c.multipart_total_limit())
-    .map(|l| {
-      l.try_into()
-        .expect("failure converting the v1::config.multipart_total_limit into a usize")
-    })
-  {
+  let mut multipart_config =
+    actix_multipart::form::MultipartFormConfig::default().error_handler(|e, _| {
+      match actix_web::ResponseError::status_code(&e) {
+        actix_web::http::StatusCode::PAYLOAD_TOO_LARGE => {
+          ApiError::PayloadTooLarge("the upload is too large")
+        }
+        _ => ApiError::InvalidRequest(e.to_string()),
+      }
+      .into()
+    });
+
+  if let Some(limit) = config.multipart_total_limit() {
     multipart_config = multipart_config.total_limit(limit);
   }
 
+  // the bucket and item segments are only extracted as validated names, so a
+  // name that could escape the storage root never reaches the handlers
+  let path_config = web::PathConfig::default()
+    .error_handler(|_, _| ApiError::BadRequest("invalid bucket or item name").into());
+  let json_config =
+    web::JsonConfig::default().error_handler(|e, _| ApiError::InvalidRequest(e.to_string()).into());
+  let query_config = web::QueryConfig::default()
+    .error_handler(|e, _| ApiError::InvalidRequest(e.to_string()).into());
+
   cfg
-    .app_data(Data::new(
-      Config::from_disk().expect("failure reading v1::Config from disk"),
-    ))
+    .app_data(config)
     .app_data(actix_web::web::Data::new(multipart_config))
+    .app_data(path_config)
+    .app_data(json_config)
+    .app_data(query_config)
     .route("", put().to(upload_file))
+    .route("/query", post().to(query_items))
+    .route("/batch", post().to(batch::run_batch))
+    .route("/archive", post().to(archive::download_archive))
+    .route("/buckets", get().to(buckets::list_buckets))
+    .route("/buckets/{bucket}", put().to(buckets::create_bucket))
+    .route("/buckets/{bucket}", get().to(buckets::describe_bucket))
+    .route("/buckets/{bucket}", delete().to(buckets::delete_bucket))
+    .route(
+      "/buckets/{bucket}/settings",
+      post().to(buckets::update_bucket_settings),
+    )
+    .route(
+      "/buckets/{bucket}/rename",
+      post().to(buckets::rename_bucket),
+    )
     .route(
       "/active/{filename}",
       post().to(replace_file_in_active_bucket),
     )
+    .route("/{bucket}", put().to(upload_file_in_bucket))
+    .route("/{bucket}/{filename}", get().to(serve_file))
+    .route("/{bucket}/{filename}", head().to(describe_file))
     .route("/{bucket}/{filename}", post().to(replace_file))
     .route("/{bucket}/{filename}/aliased", get().to(serve_aliased_file))
     .route("/{bucket}/{filename}/metadata", get().to(get_file_metadata))
     .route("/{bucket}/{filename}/alias", get().to(get_file_alias))
-    .route("}/{bucket}/{filename}/size", get().to(get_file_size))
+    .route("/{bucket}/{filename}/size", get().to(get_file_size))
+    .route("/{bucket}/{filename}/stat", get().to(get_file_stat))
+    .route(
+      "/{bucket}/{filename}/thumbnail",
+      get().to(thumbnail::get_thumbnail),
+    )
+    .route("/{bucket}/{filename}/copy", post().to(copy_file))
+    .route("/{bucket}/{filename}/move", post().to(move_file))
+    .route(
+      "/{bucket}/{filename}/versions",
+      get().to(versions::list_versions),
+    )
+    .route(
+      "/{bucket}/{filename}/versions",
+      delete().to(versions::purge_versions),
+    )
+    .route(
+      "/{bucket}/{filename}/versions/{version}/restore",
+      post().to(versions::restore_version),
+    )
     .route(
       "/{bucket}/{filename}/metadata",
       post().to(set_file_metadata),
     )
-    .route("/{bucket}/{filename}", delete().to(delete_file))
-    .service(actix_files::Files::new(
-      "/",
-      storage::internal::root().expect("failure getting storage root path"),
-    ));
+    .route(
+      "/{bucket}/{filename}/metadata",
+      patch().to(patch_file_metadata),
+    )
+    .route("/{bucket}/{filename}", delete().to(delete_file));
 }
 
 async fn upload_file(
-  MultipartForm(form): MultipartForm<UploadFileBody>, token: BearerToken, config: Data<Config>,
+  MultipartForm(form): MultipartForm<UploadFileBody>, query: web::Query<UploadQuery>,
+  token: BearerToken, config: Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+  let identifier = token.authenticate(&config, sdk::Operation::Upload).await?;
+
+  if query.extract {
+    let manifest =
+      extract::extract_archive(form, None, &identifier, config.extract_limits()).await?;
+
+    token.complete(&config, identifier).await?;
+    return Ok(HttpResponse::Created().json(manifest));
+  }
+
+  let bucket = storage::nonblocking::active_bucket().await?;
+  let (metadata, unique_id, tempfile) = form.into_metadata(&bucket, &identifier).await?;
+
+  // the settings and the names checked are the ones of this bucket, even if
+  // the active bucket rotated since
+  let storage_path =
+    storage::nonblocking::persist_tempfile_in(&bucket, &unique_id, tempfile, metadata).await?;
+
+  token.complete(&config, identifier).await?;
+  Ok(HttpResponse::Created().body(storage_path.to_string()))
+}
+
+async fn upload_file_in_bucket(
+  path: Path<BucketName>, MultipartForm(form): MultipartForm<UploadFileBody>,
+  query: web::Query<UploadQuery>, token: BearerToken, config: Data<Config>,
 ) -> Result<HttpResponse, ApiError> {
   let identifier = token.authenticate(&config, sdk::Operation::Upload).await?;
 
-  let (metadata, unique_id, tempfile) = form.into_metadata(&storage::internal::active_bucket()?)?;
+  let bucket = path.into_inner();
+  if query.extract {
+    let manifest =
+      extract::extract_archive(form, Some(&bucket), &identifier, config.extract_limits()).await?;
+
+    token.complete(&config, identifier).await?;
+    return Ok(HttpResponse::Created().json(manifest));
+  }
+
+  let (metadata, unique_id, tempfile) = form.into_metadata(&bucket, &identifier).await?;
 
   let storage_path =
-    actix_web::web::block(move || storage::persist_tempfile(&unique_id, tempfile.file, metadata))
-      .await??;
+    storage::nonblocking::persist_tempfile_in(&bucket, &unique_id, tempfile, metadata).await?;
 
   token.complete(&config, identifier).await?;
-  Ok(HttpResponse::Created().body(storage_path))
+  Ok(HttpResponse::Created().body(storage_path.to_string()))
 }
 
 async fn replace_file_in_active_bucket(
-  path: Path<String>, MultipartForm(form): MultipartForm<UploadFileBody>, token: BearerToken,
+  path: Path<ItemName>, MultipartForm(form): MultipartForm<UploadFileBody>, token: BearerToken,
   config: Data<Config>,
 ) -> Result<HttpResponse, ApiError> {
   let identifier = token
     .authenticate(&config, sdk::Operation::ReplaceActive)
     .await?;
 
-  let bucket = storage::internal::active_bucket()?;
-  let (metadata, _, tempfile) = form.into_metadata(&bucket)?;
-  let item = path.into_inner();
-
-  let storage_path = actix_web::web::block(move || {
-    let storage_path = storage::internal::storage_path(&bucket, &item);
+  let bucket = storage::nonblocking::active_bucket().await?;
+  let (mut metadata, _, tempfile) = form.into_metadata(&bucket, &identifier).await?;
+  let storage_path = StoragePath::new(bucket, path.into_inner());
+  metadata.replaces(storage::nonblocking::deserialize_metadata(&storage_path).await?);
 
-    storage::replace_tempfile(&storage_path, tempfile.file, metadata)
-  })
-  .await??;
+  let storage_path =
+    storage::nonblocking::replace_tempfile(&storage_path, tempfile, metadata).await?;
 
   token.complete(&config, identifier).await?;
-  Ok(HttpResponse::Created().body(storage_path))
+  Ok(HttpResponse::Created().body(storage_path.to_string()))
 }
 
 async fn replace_file(
-  path: Path<(String, String)>, MultipartForm(form): MultipartForm<UploadFileBody>,
+  path: Path<(BucketName, ItemName)>, MultipartForm(form): MultipartForm<UploadFileBody>,
   token: BearerToken, config: Data<Config>,
 ) -> Result<HttpResponse, ApiError> {
   let identifier = token.authenticate(&config, sdk::Operation::Replace).await?;
 
-  let (bucket, item) = path.into_inner();
-  let (metadata, _, tempfile) = form.into_metadata(&bucket)?;
-
-  let storage_path = actix_web::web::block(move || {
-    let storage_path = storage::internal::storage_path(&bucket, &item);
+  let storage_path = StoragePath::from(path.into_inner());
+  let (mut metadata, _, tempfile) = form
+    .into_metadata(&storage_path.bucket, &identifier)
+    .await?;
+  metadata.replaces(storage::nonblocking::deserialize_metadata(&storage_path).await?);
 
-    storage::replace_tempfile(&storage_path, tempfile.file, metadata)
-  })
-  .await??;
+  let storage_path =
+    storage::nonblocking::replace_tempfile(&storage_path, tempfile, metadata).await?;
 
   token.complete(&config, identifier).await?;
-  Ok(HttpResponse::Created().body(storage_path))
+  Ok(HttpResponse::Created().body(storage_path.to_string()))
+}
+
+async fn serve_file(
+  path: Path<(BucketName, ItemName)>, token: Option<BearerToken>, config: Data<Config>,
+  accept_encoding: Option<Header<AcceptEncoding>>, req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+  let storage_path = StoragePath::from(path.into_inner());
+  let authorization = buckets::authorize_read(&storage_path.bucket, token, &config).await?;
+
+  let metadata: Option<Metadata> =
+    storage::nonblocking::deserialize_metadata(&storage_path).await?;
+
+  if let Some((token, identifier)) = authorization {
+    token.complete(&config, identifier).await?;
+  }
+
+  serve_content(&storage_path, metadata, None, accept_encoding, &req).await
+}
+
+/// Answer with the content of the item. A compressed item is sent as it is
+/// stored with its `Content-Encoding` when the client accepts the encoding, and
+/// decompressed on the fly otherwise. An encrypted item is decrypted on the fly.
+async fn serve_content(
+  storage_path: &StoragePath, metadata: Option<Metadata>, disposition: Option<ContentDisposition>,
+  accept_encoding: Option<Header<AcceptEncoding>>, req: &HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+  // the file is opened off the executor, then NamedFile streams it from the
+  // blocking pool of actix
+  let (content, path) = storage::nonblocking::open(storage_path).await?;
+
+  let content_type = metadata.as_ref().and_then(Metadata::content_type);
+  let size = metadata
+    .as_ref()
+    .and_then(|m| Some(m.system.as_ref()?.size));
+  let encoding = metadata.and_then(|m| m.system?.encoding);
+  let accepted = encoding.filter(|encoding| accepts(accept_encoding.as_deref(), *encoding));
+
+  let mut response = match content {
+    storage::Content::Plain(file) if encoding.is_none() || accepted.is_some() => {
+      let mut file = actix_files::NamedFile::from_file(file, path)?;
+      if let Some(content_type) = content_type {
+        file = file.set_content_type(content_type);
+      }
+      if let Some(disposition) = disposition {
+        file = file.set_content_disposition(disposition);
+      }
+      if let Some(encoding) = accepted {
+        file = file.set_content_encoding(match encoding {
+          storage::Encoding::Zstd => ContentEncoding::Zstd,
+          storage::Encoding::Gzip => ContentEncoding::Gzip,
+        });
+      }
+
+      file.use_last_modified(true).into_response(req)
+    }
+    content => {
+      let mut response = HttpResponse::Ok();
+      if let Some(content_type) = content_type {
+        response.content_type(content_type);
+      }
+      if let Some(disposition) = disposition {
+        response.insert_header(disposition);
+      }
+      if let Some(encoding) = accepted {
+        response.insert_header((header::CONTENT_ENCODING, encoding.as_str()));
+      }
+
+      // the system metadata of compressed items has the decompressed size
+      let decode = encoding.filter(|_| accepted.is_none());
+      let size = match (decode, size) {
+        (Some(_), Some(size)) => size,
+        _ => content.size()?,
+      };
+
+      let stream = stream::blocking_stream(move |writer| {
+        let mut reader: Box<dyn std::io::Read + Send> = match decode {
+          Some(encoding) => encoding.decoder(content).map_err(std::io::Error::other)?,
+          None => Box::new(content),
+        };
+        std::io::copy(&mut reader, writer).map(|_| ())
+      });
+
+      response.body(SizedStream::new(size, stream))
+    }
+  };
+
+  // caches must not give the compressed content to the clients that don't
+  // accept it
+  if encoding.is_some() {
+    response.headers_mut().insert(
+      header::VARY,
+      header::HeaderValue::from_static("accept-encoding"),
+    );
+  }
+
+  Ok(response)
+}
+
+/// Whether the client prefers the `encoding` to the decompressed content
+fn accepts(accept_encoding: Option<&AcceptEncoding>, encoding: storage::Encoding) -> bool {
+  let encoding = match encoding {
+    storage::Encoding::Zstd => header::Encoding::zstd(),
+    storage::Encoding::Gzip => header::Encoding::gzip(),
+  };
+
+  accept_encoding
+    .and_then(|accept| accept.negotiate([&encoding, &header::Encoding::identity()].into_iter()))
+    == Some(encoding)
+}
+
+/// Answer with the system metadata of the item as headers, the content type,
+/// length and last modification date, alongside the `X-Shcs-Created-At`,
+/// `X-Shcs-Uploader` and `X-Shcs-Filename` headers when they are known
+async fn describe_file(
+  path: Path<(BucketName, ItemName)>, token: Option<BearerToken>, config: Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+  let storage_path = StoragePath::from(path.into_inner());
+  let authorization = buckets::authorize_read(&storage_path.bucket, token, &config).await?;
+
+  let metadata: Option<Metadata> =
+    storage::nonblocking::deserialize_metadata(&storage_path).await?;
+  let system = system_metadata(&storage_path, metadata.as_ref()).await?;
+
+  if let Some((token, identifier)) = authorization {
+    token.complete(&config, identifier).await?;
+  }
+
+  let http_date = |timestamp: i64| {
+    let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(timestamp.max(0) as u64);
+    HttpDate::from(time)
+  };
+
+  let mut response = HttpResponse::Ok();
+  response
+    .insert_header((header::CONTENT_TYPE, system.content_type))
+    .insert_header(LastModified(http_date(system.modified_at)))
+    .insert_header((
+      "X-Shcs-Created-At",
+      http_date(system.created_at).to_string(),
+    ));
+
+  if let Some(uploader) = system.uploader {
+    response.insert_header(("X-Shcs-Uploader", uploader));
+  }
+
+  if let Some(filename) = system.filename {
+    response.insert_header(("X-Shcs-Filename", filename));
+  }
+
+  // the body of a HEAD response is never sent, it only gives its length
+  Ok(response.body(SizedStream::new(
+    system.size,
+    futures_util::stream::empty::<Result<actix_web::web::Bytes, actix_web::Error>>(),
+  )))
+}
+
+/// The system metadata of the item, read from its file when its `metadata`
+/// predates them
+async fn system_metadata(
+  storage_path: &StoragePath, metadata: Option<&Metadata>,
+) -> Result<SystemMetadata, ApiError> {
+  if let Some(system) = metadata.and_then(|m| m.system.clone()) {
+    if storage::nonblocking::exists(storage_path).await? {
+      return Ok(system);
+    }
+  }
+
+  let (file, _) = storage::nonblocking::read(storage_path).await?;
+
+  Ok(SystemMetadata::from_file(
+    &storage_path.item,
+    &file.metadata().await?,
+  )?)
 }
 
 async fn serve_aliased_file(
-  path: Path<(String, String)>,
-) -> Result<actix_files::NamedFile, ApiError> {
-  let (bucket, item) = path.into_inner();
-  let storage_path = storage::internal::storage_path(&bucket, &item);
+  path: Path<(BucketName, ItemName)>, token: Option<BearerToken>, config: Data<Config>,
+  accept_encoding: Option<Header<AcceptEncoding>>, req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+  let storage_path = StoragePath::from(path.into_inner());
+  let authorization = buckets::authorize_read(&storage_path.bucket, token, &config).await?;
 
-  let (file, path) = storage::read(&storage_path)?;
-  let file = actix_files::NamedFile::from_file(file, path)?;
+  let metadata: Option<Metadata> =
+    storage::nonblocking::deserialize_metadata(&storage_path).await?;
 
-  let metadata: Option<Metadata> = storage::deserialize_metadata(&storage_path)?;
-  let alias = match metadata.map(|m| m.alias) {
+  let alias = match metadata.as_ref().map(|m| m.alias.clone()) {
     Some(alias) => alias,
-    None => item,
+    None => storage_path.item.to_string(),
   };
 
-  Ok(
-    file
-      .use_last_modified(true)
-      .set_content_disposition(ContentDisposition {
-        disposition: actix_web::http::header::DispositionType::Attachment,
-        parameters: vec![DispositionParam::Filename(alias)],
-      }),
+  if let Some((token, identifier)) = authorization {
+    token.complete(&config, identifier).await?;
+  }
+
+  let disposition = ContentDisposition {
+    disposition: actix_web::http::header::DispositionType::Attachment,
+    parameters: vec![DispositionParam::Filename(alias)],
+  };
+
+  serve_content(
+    &storage_path,
+    metadata,
+    Some(disposition),
+    accept_encoding,
+    &req,
   )
+  .await
 }
 
 async fn set_file_metadata(
-  path: Path<(String, String)>, custom: Option<Json<serde_json::Value>>, token: BearerToken,
-  config: Data<Config>,
+  path: Path<(BucketName, ItemName)>, custom: Option<Json<serde_json::Value>>,
+  if_match: Option<Header<IfMatch>>, token: BearerToken, config: Data<Config>,
 ) -> Result<HttpResponse, ApiError> {
   let identifier = token
     .authenticate(&config, sdk::Operation::MetadataSet)
     .await?;
 
-  let (bucket, item) = path.into_inner();
+  let storage_path = StoragePath::from(path.into_inner());
   let custom = custom.map(|c| c.into_inner());
+  let if_match = if_match.map(Header::into_inner);
+  let metadata = update_metadata(&storage_path, if_match, move |metadata| {
+    metadata.custom = custom;
+    metadata.revision += 1;
 
-  let storage_path = storage::internal::storage_path(&bucket, &item);
-  let metadata: Option<Metadata> = storage::deserialize_metadata(&storage_path)?;
-  let metadata = metadata.map(|m| Metadata { custom, ..m });
-  storage::internal::set_metadata(&storage_path, metadata)?;
+    Ok(())
+  })
+  .await?;
 
   token.complete(&config, identifier).await?;
-  Ok(HttpResponse::Ok().finish())
+  Ok(
+    HttpResponse::Ok()
+      .insert_header(ETag(metadata.etag()))
+      .finish(),
+  )
+}
+
+/// Apply a JSON merge patch to the alias and the custom metadata of the item
+async fn patch_file_metadata(
+  path: Path<(BucketName, ItemName)>, patch: Json<serde_json::Value>,
+  if_match: Option<Header<IfMatch>>, token: BearerToken, config: Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+  let identifier = token
+    .authenticate(&config, sdk::Operation::MetadataSet)
+    .await?;
+
+  let storage_path = StoragePath::from(path.into_inner());
+  let patch = patch.into_inner();
+  let if_match = if_match.map(Header::into_inner);
+  let metadata = update_metadata(&storage_path, if_match, move |metadata| {
+    metadata.merge_patch(&patch)
+  })
+  .await?;
+
+  token.complete(&config, identifier).await?;
+  Ok(
+    HttpResponse::Ok()
+      .insert_header(ETag(metadata.etag()))
+      .json(metadata.editable()),
+  )
+}
+
+/// Change the metadata of the item with `update` once the `If-Match` header is
+/// checked against its revision. An item without a metadata file gets one.
+async fn update_metadata<F>(
+  storage_path: &StoragePath, if_match: Option<IfMatch>, update: F,
+) -> Result<Metadata, ApiError>
+where
+  F: FnOnce(&mut Metadata) -> Result<(), ApiError> + Send + 'static,
+{
+  if !storage::nonblocking::exists(storage_path).await? {
+    return Err(ApiError::NotFound);
+  }
+
+  let alias = storage_path.item.to_string();
+
+  storage::nonblocking::update_metadata(storage_path, move |metadata: Option<Metadata>| {
+    let mut metadata = metadata.unwrap_or_else(|| Metadata::new(alias));
+
+    metadata.check_revision(if_match.as_ref())?;
+    update(&mut metadata)?;
+
+    Ok(metadata)
+  })
+  .await
 }
 
 async fn get_file_metadata(
-  path: Path<(String, String)>, token: BearerToken, config: Data<Config>,
+  path: Path<(BucketName, ItemName)>, token: BearerToken, config: Data<Config>,
 ) -> Result<HttpResponse, ApiError> {
   let identifier = token
     .authenticate(&config, sdk::Operation::MetadataGet)
     .await?;
 
-  let (bucket, item) = path.into_inner();
-  let storage_path = storage::internal::storage_path(&bucket, &item);
-  let metadata: Option<Metadata> = storage::deserialize_metadata(&storage_path)?;
+  let storage_path = StoragePath::from(path.into_inner());
+  let metadata = existing_metadata(&storage_path).await?;
 
   token.complete(&config, identifier).await?;
-  Ok(HttpResponse::Ok().json(metadata.and_then(|m| m.custom)))
+
+  let mut response = HttpResponse::Ok();
+  if let Some(metadata) = &metadata {
+    response.insert_header(ETag(metadata.etag()));
+  }
+
+  Ok(response.json(metadata.and_then(|m| m.custom)))
 }
 
 async fn get_file_alias(
-  path: Path<(String, String)>, token: BearerToken, config: Data<Config>,
+  path: Path<(BucketName, ItemName)>, token: BearerToken, config: Data<Config>,
 ) -> Result<HttpResponse, ApiError> {
   let identifier = token
     .authenticate(&config, sdk::Operation::MetadataGet)
     .await?;
 
-  let (bucket, item) = path.into_inner();
-  let storage_path = storage::internal::storage_path(&bucket, &item);
-  let metadata: Option<Metadata> = storage::deserialize_metadata(&storage_path)?;
+  let storage_path = StoragePath::from(path.into_inner());
+  let metadata = existing_metadata(&storage_path).await?;
 
   token.complete(&config, identifier).await?;
   Ok(HttpResponse::Ok().json(metadata.map(|m| m.alias)))
 }
 
+/// The metadata of the item, that may have none, or [ApiError::NotFound] if
+/// there is no item at the `storage_path`
+async fn existing_metadata(storage_path: &StoragePath) -> Result<Option<Metadata>, ApiError> {
+  let metadata: Option<Metadata> = storage::nonblocking::deserialize_metadata(storage_path).await?;
+
+  match metadata.is_some() || storage::nonblocking::exists(storage_path).await? {
+    true => Ok(metadata),
+    false => Err(ApiError::NotFound),
+  }
+}
+
 async fn get_file_size(
-  path: Path<(String, String)>, token: BearerToken, config: Data<Config>,
+  path: Path<(BucketName, ItemName)>, token: BearerToken, config: Data<Config>,
 ) -> Result<HttpResponse, ApiError> {
   let identifier = token
     .authenticate(&config, sdk::Operation::MetadataGet)
     .await?;
 
-  let (bucket, item) = path.into_inner();
-  let storage_path = storage::internal::storage_path(&bucket, &item);
-  let (file, _) = storage::read(&storage_path)?;
-  let size = file.metadata()?.len();
+  let storage_path = StoragePath::from(path.into_inner());
+  let metadata: Option<Metadata> =
+    storage::nonblocking::deserialize_metadata(&storage_path).await?;
+  let size = system_metadata(&storage_path, metadata.as_ref())
+    .await?
+    .size;
 
   token.complete(&config, identifier).await?;
   Ok(HttpResponse::Ok().body(size.to_string()))
 }
 
+async fn query_items(
+  query: Json<storage::Query>, token: BearerToken, config: Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+  let identifier = token.authenticate(&config, sdk::Operation::Query).await?;
+
+  let page = storage::nonblocking::query(&query).await?;
+
+  token.complete(&config, identifier).await?;
+  Ok(HttpResponse::Ok().json(page))
+}
+
+/// The alias, system and custom metadata of the item in one document
+async fn get_file_stat(
+  path: Path<(BucketName, ItemName)>, token: BearerToken, config: Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+  let identifier = token
+    .authenticate(&config, sdk::Operation::MetadataGet)
+    .await?;
+
+  let storage_path = StoragePath::from(path.into_inner());
+  let stat = file_stat(&storage_path).await?;
+
+  token.complete(&config, identifier).await?;
+
+  Ok(
+    HttpResponse::Ok()
+      .insert_header(ETag(EntityTag::new_strong(stat.revision.to_string())))
+      .json(stat),
+  )
+}
+
+/// Describe the item from its metadata, or from its file when it has none
+async fn file_stat(storage_path: &StoragePath) -> Result<sdk::Stat, ApiError> {
+  let metadata: Option<Metadata> = storage::nonblocking::deserialize_metadata(storage_path).await?;
+  let system = system_metadata(storage_path, metadata.as_ref()).await?;
+  let metadata = metadata.unwrap_or_else(|| Metadata::new(storage_path.item.to_string()));
+
+  Ok(sdk::Stat {
+    path: storage_path.clone(),
+    alias: metadata.alias,
+    content_type: system.content_type,
+    size: system.size,
+    checksum: system.checksum,
+    created_at: system.created_at,
+    modified_at: system.modified_at,
+    uploader: system.uploader,
+    filename: system.filename,
+    custom: metadata.custom,
+    revision: metadata.revision,
+  })
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct DestinationBody {
+  destination: StoragePath,
+}
+
+async fn copy_file(
+  path: Path<(BucketName, ItemName)>, body: Json<DestinationBody>, token: BearerToken,
+  config: Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+  let identifier = token.authenticate(&config, sdk::Operation::Copy).await?;
+
+  let storage_path = StoragePath::from(path.into_inner());
+  storage::nonblocking::copy(&storage_path, &body.destination).await?;
+
+  token.complete(&config, identifier).await?;
+  Ok(HttpResponse::Ok().finish())
+}
+
+async fn move_file(
+  path: Path<(BucketName, ItemName)>, body: Json<DestinationBody>, token: BearerToken,
+  config: Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+  let identifier = token.authenticate(&config, sdk::Operation::Move).await?;
+
+  let storage_path = StoragePath::from(path.into_inner());
+  storage::nonblocking::rename(&storage_path, &body.destination).await?;
+
+  token.complete(&config, identifier).await?;
+  Ok(HttpResponse::Ok().finish())
+}
+
 async fn delete_file(
-  path: Path<(String, String)>, token: BearerToken, config: Data<Config>,
+  path: Path<(BucketName, ItemName)>, token: BearerToken, config: Data<Config>,
 ) -> Result<HttpResponse, ApiError> {
   let identifier = token.authenticate(&config, sdk::Operation::Delete).await?;
 
-  let (bucket, item) = path.into_inner();
-  let storage_path = storage::internal::storage_path(&bucket, &item);
+  let storage_path = StoragePath::from(path.into_inner());
 
-  storage::remove(&storage_path)?;
+  storage::nonblocking::remove(&storage_path).await?;
 
   token.complete(&config, identifier).await?;
   Ok(HttpResponse::Ok().finish())
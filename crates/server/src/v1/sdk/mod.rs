@@ -1,7 +1,16 @@
-mod operation;
-pub use operation::Operation;
-
-pub mod api;
-
-mod params;
-pub(crate) use params::UrlBuilder;
+//! The types and the client of the v1 API, from the `shcs-types` and
+//! `shcs-client` crates that can be used without depending on the server
+pub use shcs_client::api;
+pub use shcs_client::blocking;
+pub use shcs_client::Credentials;
+pub use shcs_client::ShcsClient;
+pub use shcs_client::ShcsClientBuilder;
+pub use shcs_client::UploadBody;
+pub use shcs_types::ArchiveFormat;
+pub use shcs_types::ArchiveRequest;
+pub use shcs_types::BatchOperation;
+pub use shcs_types::BatchResult;
+pub use shcs_types::ExtractedItem;
+pub use shcs_types::Operation;
+pub use shcs_types::Problem;
+pub use shcs_types::Stat;
@@ -0,0 +1,55 @@
+//! Response bodies written by a blocking task as they are sent, for content
+//! produced by synchronous writers like the archive and decompression ones
+use std::io::Write;
+
+use actix_web::web::Bytes;
+use futures_util::Stream;
+use tokio::sync::mpsc;
+
+/// The size of the chunks of the body sent to the client
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// The number of chunks written ahead of the client before the writer waits
+const BUFFERED_CHUNKS: usize = 4;
+
+/// Run `write` on the blocking pool and stream what it writes. An error aborts
+/// the response, as the client already received its status.
+pub(super) fn blocking_stream<F>(write: F) -> impl Stream<Item = std::io::Result<Bytes>>
+where
+  F: FnOnce(&mut dyn Write) -> std::io::Result<()> + Send + 'static,
+{
+  let (sender, receiver) = mpsc::channel(BUFFERED_CHUNKS);
+
+  actix_web::rt::task::spawn_blocking(move || {
+    let mut writer = std::io::BufWriter::with_capacity(CHUNK_SIZE, ChunkWriter(sender.clone()));
+    let written = write(&mut writer).and_then(|_| writer.flush());
+
+    if let Err(e) = written {
+      println!("stream error: {e:?}");
+      let _ = sender.blocking_send(Err(e));
+    }
+  });
+
+  futures_util::stream::unfold(receiver, |mut receiver| async move {
+    receiver.recv().await.map(|chunk| (chunk, receiver))
+  })
+}
+
+/// Sends what is written to it as the chunks of a streamed response, waiting
+/// while the client is behind
+struct ChunkWriter(mpsc::Sender<std::io::Result<Bytes>>);
+
+impl Write for ChunkWriter {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    self
+      .0
+      .blocking_send(Ok(Bytes::copy_from_slice(buf)))
+      .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe))?;
+
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    Ok(())
+  }
+}
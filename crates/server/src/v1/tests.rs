@@ -0,0 +1,553 @@
+use actix_web::http::header::EntityTag;
+use actix_web::http::header::IfMatch;
+use actix_web::http::StatusCode;
+use actix_web::ResponseError;
+use serde_json::json;
+use storage::StoragePath;
+
+use super::ApiError;
+use super::Metadata;
+
+#[test]
+fn test_merge_patch() {
+  let cases = [
+    // null removes a member, and members missing from the patch are kept
+    (
+      json!({ "a": 1, "b": 2 }),
+      json!({ "a": null }),
+      json!({ "b": 2 }),
+    ),
+    // the objects are merged recursively
+    (
+      json!({ "a": { "b": 1, "c": 2 } }),
+      json!({ "a": { "c": 3, "d": null, "e": 4 } }),
+      json!({ "a": { "b": 1, "c": 3, "e": 4 } }),
+    ),
+    // a value that isn't an object replaces the target
+    (
+      json!({ "a": [1, 2] }),
+      json!({ "a": [3] }),
+      json!({ "a": [3] }),
+    ),
+    (json!({ "a": 1 }), json!(["a"]), json!(["a"])),
+    (json!("text"), json!({ "a": 1 }), json!({ "a": 1 })),
+    (
+      json!({ "a": "b" }),
+      json!({ "a": { "c": null } }),
+      json!({ "a": {} }),
+    ),
+  ];
+
+  for (mut target, patch, expected) in cases {
+    super::metadata::merge_patch(&mut target, &patch);
+    assert_eq!(target, expected, "{patch}");
+  }
+}
+
+#[test]
+fn test_metadata_merge_patch() {
+  let mut metadata = Metadata::new("one.md".to_owned());
+  metadata.custom = Some(json!({ "project": { "id": 42, "name": "shcs" } }));
+
+  metadata
+    .merge_patch(&json!({ "alias": "two.md", "custom": { "project": { "name": null } } }))
+    .expect("the patch applies");
+  assert_eq!(metadata.alias, "two.md");
+  assert_eq!(metadata.custom, Some(json!({ "project": { "id": 42 } })));
+  assert_eq!(metadata.revision, 1);
+
+  // the alias must stay a string, and only the editable members are patched
+  for patch in [json!({ "alias": null }), json!({ "system": {} })] {
+    assert!(matches!(
+      metadata.merge_patch(&patch),
+      Err(ApiError::BadRequest(_))
+    ));
+  }
+}
+
+#[test]
+fn test_check_revision() {
+  let mut metadata = Metadata::new("one.md".to_owned());
+  metadata.revision = 3;
+
+  let if_match = |tags: &[EntityTag]| IfMatch::Items(tags.to_vec());
+  let strong = |revision: &str| EntityTag::new_strong(revision.to_owned());
+
+  assert!(metadata.check_revision(None).is_ok());
+  assert!(metadata.check_revision(Some(&IfMatch::Any)).is_ok());
+  assert!(metadata.check_revision(Some(&if_match(&[]))).is_ok());
+  assert!(metadata
+    .check_revision(Some(&if_match(&[strong("2"), strong("3")])))
+    .is_ok());
+
+  for tags in [vec![strong("2")], vec![EntityTag::new_weak("3".to_owned())]] {
+    let error = metadata
+      .check_revision(Some(&if_match(&tags)))
+      .expect_err("the revision doesn't match");
+
+    assert!(matches!(error, ApiError::PreconditionFailed));
+    assert_eq!(error.status_code(), StatusCode::PRECONDITION_FAILED);
+  }
+}
+
+#[actix_web::test]
+async fn test_problem_responses() {
+  use storage::StorageError;
+
+  let not_found = std::io::Error::from(std::io::ErrorKind::NotFound);
+  let cases = [
+    (
+      ApiError::BadRequest("invalid patch"),
+      400,
+      Some("invalid patch"),
+    ),
+    (
+      ApiError::Storage(StorageError::Io(not_found)),
+      404,
+      Some("item not found"),
+    ),
+    (ApiError::NotFound, 404, None),
+    (
+      ApiError::Storage(StorageError::BucketNotEmpty),
+      409,
+      Some("bucket is not empty"),
+    ),
+    (
+      ApiError::PreconditionFailed,
+      412,
+      Some("the metadata changed since the given revision"),
+    ),
+    (
+      ApiError::PayloadTooLarge("bucket full"),
+      413,
+      Some("bucket full"),
+    ),
+  ];
+
+  for (error, status, detail) in cases {
+    let response = error.error_response();
+    assert_eq!(response.status().as_u16(), status);
+    assert_eq!(
+      response.headers().get("content-type").unwrap(),
+      "application/problem+json"
+    );
+
+    let body = actix_web::body::to_bytes(response.into_body())
+      .await
+      .unwrap();
+    let problem: super::sdk::Problem = serde_json::from_slice(&body).unwrap();
+    let reason = StatusCode::from_u16(status).unwrap().canonical_reason();
+
+    assert_eq!(problem.status, status);
+    assert_eq!(Some(problem.title.as_str()), reason);
+    assert_eq!(problem.detail.as_deref(), detail);
+  }
+}
+
+/// The storage root of the tests, the storage is global to the process
+const STORAGE: &str = "storage-test";
+
+/// Initialize the storage once, and create a new bucket for the test
+async fn test_bucket() -> storage::BucketName {
+  static INITIALIZATION: std::sync::Once = std::sync::Once::new();
+
+  INITIALIZATION.call_once(|| {
+    let _ = std::fs::remove_dir_all(STORAGE);
+    storage::initialize(STORAGE, None).expect("the storage is initialized");
+  });
+
+  let bucket: storage::BucketName = format!("test-{}", nanoid::nanoid!(8).to_lowercase())
+    .parse()
+    .expect("the bucket name is valid");
+  storage::nonblocking::buckets::create(&bucket, storage::BucketSettings::default())
+    .await
+    .expect("the bucket is created");
+
+  bucket
+}
+
+/// Store an item holding the `content` with the metadata the server writes
+async fn test_item(bucket: &storage::BucketName, name: &str, content: &str) -> StoragePath {
+  let path = StoragePath::new(bucket.clone(), name.parse().expect("the name is valid"));
+  let mut metadata = Metadata::new(name.to_owned());
+  metadata.custom = Some(json!({ "name": name }));
+
+  let root = storage::internal::root().expect("the storage is initialized");
+  storage::internal::write_exact(root, &path.bucket, &path.item, content)
+    .expect("the item is written");
+  storage::internal::set_metadata(&path, metadata).expect("the metadata is written");
+
+  path
+}
+
+#[actix_web::test]
+async fn test_batch_statuses() -> Result<(), ApiError> {
+  use super::sdk::BatchOperation;
+
+  let bucket = test_bucket().await;
+  let one = test_item(&bucket, "one.md", "content one").await;
+  let two = test_item(&bucket, "two.md", "content two").await;
+  let three = test_item(&bucket, "three.md", "content three").await;
+  let four = test_item(&bucket, "four.md", "content four").await;
+  let missing = StoragePath::new(bucket.clone(), "missing.md".parse()?);
+  let unknown_bucket = StoragePath::new("unknown-bucket".parse()?, "one.md".parse()?);
+
+  // the operations run at the same time, none of them depends on another
+  let results = super::batch::run_operations(vec![
+    BatchOperation::Stat { path: one.clone() },
+    BatchOperation::MetadataGet { path: one.clone() },
+    BatchOperation::MetadataSet {
+      path: two.clone(),
+      custom: Some(json!({ "name": "stale" })),
+      revision: Some(42),
+    },
+    BatchOperation::MetadataSet {
+      path: three.clone(),
+      custom: Some(json!({ "name": "updated" })),
+      revision: Some(0),
+    },
+    BatchOperation::Copy {
+      path: one.clone(),
+      destination: unknown_bucket,
+    },
+    BatchOperation::Delete {
+      path: missing.clone(),
+    },
+    BatchOperation::Stat {
+      path: missing.clone(),
+    },
+    BatchOperation::Delete { path: four.clone() },
+    BatchOperation::MetadataGet {
+      path: missing.clone(),
+    },
+  ])
+  .await?;
+
+  let statuses: Vec<u16> = results.iter().map(|result| result.status).collect();
+  assert_eq!(statuses, [200, 200, 412, 200, 404, 404, 404, 200, 404]);
+
+  assert_eq!(results[1].body, Some(json!({ "name": "one.md" })));
+  assert_eq!(results[3].body, Some(json!({ "revision": 1 })));
+  assert!(results[2].error.is_some() && results[2].body.is_none());
+  assert!(!storage::nonblocking::exists(&four).await?);
+
+  storage::nonblocking::buckets::delete(&bucket, true).await?;
+
+  Ok(())
+}
+
+#[actix_web::test]
+async fn test_batch_limit_and_ordering() -> Result<(), ApiError> {
+  use super::sdk::BatchOperation;
+
+  let bucket = test_bucket().await;
+  let mut operations = Vec::new();
+
+  // more operations than run at the same time, the missing items fail faster
+  // than the others are described
+  for i in 0..50 {
+    let path = match i % 3 {
+      0 => StoragePath::new(bucket.clone(), format!("missing-{i}.md").parse()?),
+      _ => test_item(&bucket, &format!("{i}.md"), "content").await,
+    };
+
+    operations.push(BatchOperation::Stat { path });
+  }
+
+  let results = super::batch::run_operations(operations.clone()).await?;
+  assert_eq!(results.len(), operations.len());
+
+  for (operation, result) in operations.iter().zip(&results) {
+    let BatchOperation::Stat { path } = operation else {
+      unreachable!()
+    };
+
+    match path.item.starts_with("missing") {
+      true => assert_eq!(result.status, 404, "{path}"),
+      false => {
+        assert_eq!(result.status, 200, "{path}");
+        assert_eq!(result.body.as_ref().unwrap()["path"], path.to_string());
+      }
+    }
+  }
+
+  let too_many = vec![operations[0].clone(); 1_001];
+  assert!(matches!(
+    super::batch::run_operations(too_many).await,
+    Err(ApiError::BadRequest(_))
+  ));
+
+  storage::nonblocking::buckets::delete(&bucket, true).await?;
+
+  Ok(())
+}
+
+#[actix_web::test]
+async fn test_archive_sizes_and_missing_items() -> Result<(), ApiError> {
+  use std::io::Read;
+  use std::io::Write;
+
+  let bucket = test_bucket().await;
+  let plain = test_item(&bucket, "plain.md", "plain content").await;
+  let removed = test_item(&bucket, "removed.md", "removed content").await;
+
+  // a compressed item, whose metadata records its decompressed size
+  let compressed = test_item(&bucket, "compressed.md", "").await;
+  let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+  encoder.write_all(b"the decompressed content")?;
+  let root = storage::internal::root()?;
+  std::fs::write(
+    root.join(bucket.as_str()).join("compressed.md"),
+    encoder.finish()?,
+  )?;
+
+  let mut metadata = Metadata::new("compressed.md".to_owned());
+  let file = std::fs::metadata(root.join(bucket.as_str()).join("compressed.md"))?;
+  let mut system = super::SystemMetadata::from_file("compressed.md", &file)?;
+  system.size = "the decompressed content".len() as u64;
+  system.encoding = Some(storage::Encoding::Gzip);
+  metadata.system = Some(system);
+  storage::internal::set_metadata(&compressed, metadata)?;
+
+  let missing = StoragePath::new(bucket.clone(), "missing.md".parse()?);
+  let entries = super::archive::entries(vec![plain, missing, removed.clone(), compressed]).await?;
+  assert_eq!(entries.len(), 3);
+
+  // removed once the archive was requested, as it is written
+  storage::nonblocking::remove(&removed).await?;
+
+  let mut archive = Vec::new();
+  super::archive::write_archive(super::sdk::ArchiveFormat::TarGz, entries, &mut archive)?;
+
+  let mut files = Vec::new();
+  let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(archive.as_slice()));
+  for file in tar.entries()? {
+    let mut file = file?;
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+
+    assert_eq!(file.header().size()?, content.len() as u64);
+    files.push((file.path()?.to_string_lossy().into_owned(), content));
+  }
+
+  assert_eq!(
+    files,
+    [
+      ("plain.md".to_owned(), "plain content".to_owned()),
+      (
+        "compressed.md".to_owned(),
+        "the decompressed content".to_owned()
+      ),
+    ]
+  );
+
+  storage::nonblocking::buckets::delete(&bucket, true).await?;
+
+  Ok(())
+}
+
+/// Write a ZIP archive holding the `entries`, named after their path
+fn zip_archive(entries: &[(&str, &[u8])]) -> std::io::Result<tempfile::NamedTempFile> {
+  use std::io::Write;
+
+  let file = tempfile::NamedTempFile::new()?;
+  let mut zip = zip::ZipWriter::new(file.reopen()?);
+
+  for (name, content) in entries {
+    let options =
+      zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    zip
+      .start_file(*name, options)
+      .map_err(std::io::Error::other)?;
+    zip.write_all(content)?;
+  }
+
+  zip.finish().map_err(std::io::Error::other)?;
+
+  Ok(file)
+}
+
+fn payload_too_large(result: Result<Vec<super::extract::ExtractedFile>, ApiError>) -> bool {
+  matches!(result, Err(ApiError::PayloadTooLarge(_)))
+}
+
+#[test]
+fn test_extract_limits() -> Result<(), ApiError> {
+  use super::extract::extract;
+  use super::extract::ExtractLimits;
+
+  let limits = ExtractLimits {
+    ratio_free_bytes: 0,
+    ..ExtractLimits::default()
+  };
+
+  // a megabyte of zeros compresses to a few kilobytes
+  let zeros = vec![0; 1024 * 1024];
+  let bomb = zip_archive(&[("zeros.bin", &zeros)])?;
+  assert!(payload_too_large(extract(bomb, limits)));
+
+  // the same archive is allowed past the ratio with enough ratio free bytes,
+  // but never past the maximum size
+  let bomb = zip_archive(&[("zeros.bin", &zeros)])?;
+  assert_eq!(extract(bomb, ExtractLimits::default())?.len(), 1);
+
+  let bomb = zip_archive(&[("zeros.bin", &zeros)])?;
+  let max_bytes = ExtractLimits {
+    max_bytes: 1024,
+    ..ExtractLimits::default()
+  };
+  assert!(payload_too_large(extract(bomb, max_bytes)));
+
+  // the entries are counted whether the archive announces them or not
+  let entries = [("a.md", b"a".as_slice()), ("b.md", b"b"), ("c.md", b"c")];
+  let max_entries = ExtractLimits {
+    max_entries: 2,
+    ..ExtractLimits::default()
+  };
+  assert!(payload_too_large(extract(
+    zip_archive(&entries)?,
+    max_entries
+  )));
+  assert_eq!(extract(zip_archive(&entries[..2])?, max_entries)?.len(), 2);
+
+  let tar = tempfile::NamedTempFile::new()?;
+  let mut builder = tar::Builder::new(tar.reopen()?);
+  for (name, content) in entries {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    builder.append_data(&mut header, name, content)?;
+  }
+  builder.finish()?;
+  assert!(payload_too_large(extract(tar, max_entries)));
+
+  Ok(())
+}
+
+#[actix_web::test]
+async fn test_extract_traversal_and_rollback() -> Result<(), ApiError> {
+  use super::extract::extract;
+  use super::extract::store_all;
+  use super::extract::ExtractLimits;
+
+  let bucket = test_bucket().await;
+  let root = storage::internal::root()?;
+
+  // the names of the entries are only aliases, every item is stored in the
+  // bucket whatever they point to
+  let archive = zip_archive(&[
+    ("../../escaped.md", b"escaped".as_slice()),
+    ("/absolute.md", b"absolute"),
+    ("folder/../../nested.md", b"nested"),
+  ])?;
+  let extracted = extract(archive, ExtractLimits::default())?;
+  let manifest = store_all(extracted, Some(&bucket), "uploader", None).await?;
+
+  assert_eq!(manifest.len(), 3);
+  for item in &manifest {
+    assert_eq!(item.path.bucket, bucket);
+    assert!(root
+      .join(bucket.as_str())
+      .join(item.path.item.as_str())
+      .is_file());
+
+    let metadata: Option<Metadata> = storage::nonblocking::deserialize_metadata(&item.path).await?;
+    assert_eq!(metadata.map(|m| m.alias), Some(item.entry.clone()));
+  }
+  assert!(!root.join("escaped.md").exists());
+  assert!(!root.join("..").join("escaped.md").exists());
+  assert!(!root.join("nested.md").exists());
+
+  // the items stored before the one that can't be are removed
+  let mut settings = storage::nonblocking::buckets::settings(&bucket).await?;
+  let stored = storage::nonblocking::buckets::describe(&bucket)
+    .await?
+    .bytes;
+  settings.max_size = Some(stored + 10);
+  storage::nonblocking::buckets::set_settings(&bucket, settings).await?;
+
+  let archive = zip_archive(&[("first.md", b"first".as_slice()), ("second.md", b"second")])?;
+  let extracted = extract(archive, ExtractLimits::default())?;
+  assert!(matches!(
+    store_all(extracted, Some(&bucket), "uploader", None).await,
+    Err(ApiError::Storage(storage::StorageError::BucketFull))
+  ));
+  assert_eq!(
+    storage::nonblocking::buckets::items(&bucket).await?.len(),
+    3
+  );
+
+  // the allowed types are checked against the bucket of every item
+  let mut settings = storage::nonblocking::buckets::settings(&bucket).await?;
+  settings.max_size = None;
+  settings.allowed_mime_types = Some(vec!["text/*".to_owned()]);
+  storage::nonblocking::buckets::set_settings(&bucket, settings).await?;
+
+  let archive = zip_archive(&[("first.md", b"first".as_slice()), ("image.png", &png(1, 1))])?;
+  let extracted = extract(archive, ExtractLimits::default())?;
+  assert!(matches!(
+    store_all(extracted, Some(&bucket), "uploader", None).await,
+    Err(ApiError::UnsupportedMediaType)
+  ));
+  assert_eq!(
+    storage::nonblocking::buckets::items(&bucket).await?.len(),
+    3
+  );
+
+  // without a bucket, the items are stored in the active one
+  let archive = zip_archive(&[("active.md", b"active".as_slice())])?;
+  let extracted = extract(archive, ExtractLimits::default())?;
+  for item in store_all(extracted, None, "uploader", None).await? {
+    assert_eq!(
+      item.path.bucket,
+      storage::nonblocking::active_bucket().await?
+    );
+    storage::nonblocking::remove(&item.path).await?;
+  }
+
+  storage::nonblocking::buckets::delete(&bucket, true).await?;
+
+  Ok(())
+}
+
+/// Encode an image of the given dimensions as a PNG
+fn png(width: u32, height: u32) -> Vec<u8> {
+  let mut encoded = std::io::Cursor::new(Vec::new());
+  image::DynamicImage::new_rgb8(width, height)
+    .write_to(&mut encoded, image::ImageFormat::Png)
+    .expect("the image is encoded");
+
+  encoded.into_inner()
+}
+
+#[test]
+fn test_thumbnail_sources() {
+  use super::thumbnail::decodable;
+  use super::thumbnail::decode;
+
+  assert!(decodable("image/png"));
+  assert!(decodable("IMAGE/JPEG; charset=binary"));
+  assert!(!decodable("image/svg+xml"));
+  assert!(!decodable("application/octet-stream"));
+
+  let image = decode(png(32, 16).as_slice(), 1024 * 1024).expect("the image is decoded");
+  assert_eq!((image.width(), image.height()), (32, 16));
+
+  // the content is read up to the maximum size
+  let content = png(32, 16);
+  assert!(matches!(
+    decode(content.as_slice(), content.len() as u64 - 1),
+    Err(ApiError::PayloadTooLarge(_))
+  ));
+
+  // a small file can describe a huge image, its dimensions are limited
+  assert!(matches!(
+    decode(png(20_000, 1).as_slice(), 1024 * 1024),
+    Err(ApiError::PayloadTooLarge(_))
+  ));
+
+  assert!(matches!(
+    decode(b"not an image".as_slice(), 1024 * 1024),
+    Err(ApiError::UnsupportedMediaType)
+  ));
+}
@@ -0,0 +1,302 @@
+//! The thumbnails of the image items, resized and re-encoded on the fly then
+//! cached as derivatives of the items
+use std::io::Read;
+
+use actix_web::web::Data;
+use actix_web::web::Path;
+use actix_web::web::Query;
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use image::ImageFormat;
+use storage::BucketName;
+use storage::ItemName;
+use storage::StoragePath;
+
+use super::buckets;
+use super::ApiError;
+use super::BearerToken;
+use super::Config;
+use super::Metadata;
+
+/// The content types of the items the thumbnails can be made of
+const DECODABLE_TYPES: [&str; 4] = ["image/png", "image/jpeg", "image/gif", "image/webp"];
+
+/// The largest width or height of the images that are decoded, in pixels
+const DECODE_MAX_DIMENSION: u32 = 16_384;
+
+/// The most memory the decoder can allocate for an image, 256MiB
+const DECODE_MAX_ALLOC: u64 = 256 * 1024 * 1024;
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct ThumbnailQuery {
+  /// The width of the thumbnail, its height when it is missing
+  w: Option<u32>,
+
+  /// The height of the thumbnail, its width when it is missing
+  h: Option<u32>,
+
+  #[serde(default)]
+  fit: Fit,
+
+  /// Defaults to the format of the item when it can be encoded, or to PNG
+  format: Option<ThumbnailFormat>,
+}
+
+/// How the image is resized to the dimensions of the thumbnail
+#[derive(Debug, Default, Clone, Copy, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Fit {
+  /// Keep the aspect ratio and fit in the dimensions, without enlarging
+  /// smaller images
+  #[default]
+  Contain,
+
+  /// Keep the aspect ratio and fill the dimensions, cropping what overflows
+  Cover,
+
+  /// Stretch the image to the dimensions
+  Fill,
+}
+
+impl Fit {
+  fn name(self) -> &'static str {
+    match self {
+      Fit::Contain => "contain",
+      Fit::Cover => "cover",
+      Fit::Fill => "fill",
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ThumbnailFormat {
+  Png,
+  Jpeg,
+  Webp,
+  Gif,
+}
+
+impl ThumbnailFormat {
+  /// The format of the thumbnails of an item with the `content_type`
+  fn from_content_type(content_type: Option<&str>) -> Self {
+    match content_type {
+      Some("image/jpeg") => ThumbnailFormat::Jpeg,
+      Some("image/webp") => ThumbnailFormat::Webp,
+      Some("image/gif") => ThumbnailFormat::Gif,
+      _ => ThumbnailFormat::Png,
+    }
+  }
+
+  fn image_format(self) -> ImageFormat {
+    match self {
+      ThumbnailFormat::Png => ImageFormat::Png,
+      ThumbnailFormat::Jpeg => ImageFormat::Jpeg,
+      ThumbnailFormat::Webp => ImageFormat::WebP,
+      ThumbnailFormat::Gif => ImageFormat::Gif,
+    }
+  }
+}
+
+pub(super) async fn get_thumbnail(
+  path: Path<(BucketName, ItemName)>, query: Query<ThumbnailQuery>, token: Option<BearerToken>,
+  config: Data<Config>, req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+  let storage_path = StoragePath::from(path.into_inner());
+  let authorization = buckets::authorize_read(&storage_path.bucket, token, &config).await?;
+
+  let (width, height) = dimensions(&query, config.thumbnail_max_size())?;
+  let metadata: Option<Metadata> =
+    storage::nonblocking::deserialize_metadata(&storage_path).await?;
+  let system = metadata.and_then(|m| m.system);
+
+  // the items that aren't images are refused before they are read
+  let content_type = match &system {
+    Some(system) => system.content_type.clone(),
+    None => actix_files::file_extension_to_mime(
+      std::path::Path::new(storage_path.item.as_str())
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default(),
+    )
+    .to_string(),
+  };
+  if !decodable(&content_type) {
+    return Err(ApiError::UnsupportedMediaType);
+  }
+
+  let format = query
+    .format
+    .unwrap_or_else(|| ThumbnailFormat::from_content_type(Some(&content_type)));
+
+  let image_format = format.image_format();
+  let name = ItemName::new(format!(
+    "{width}x{height}-{}.{}",
+    query.fit.name(),
+    image_format.extensions_str()[0]
+  ))?;
+
+  if storage::nonblocking::read_derivative(&storage_path, &name)
+    .await?
+    .is_none()
+  {
+    let (content, _) = storage::nonblocking::open(&storage_path).await?;
+    let fit = query.fit;
+    let encoding = system.as_ref().and_then(|s| s.encoding);
+    let max_bytes = config.thumbnail_max_source_bytes();
+
+    // the size of a compressed item is only known from its metadata, the
+    // decompressed bytes are counted as they are read too
+    let size = match &system {
+      Some(system) if encoding.is_some() => system.size,
+      _ => content.size()?,
+    };
+    if size > max_bytes {
+      return Err(image_too_large());
+    }
+
+    let thumbnail = actix_web::web::block(move || {
+      let reader: Box<dyn Read + Send> = match encoding {
+        Some(encoding) => encoding.decoder(content)?,
+        None => Box::new(content),
+      };
+      let image = decode(reader, max_bytes)?;
+
+      encode(&resize(image, width, height, fit), image_format)
+    })
+    .await??;
+
+    storage::nonblocking::write_derivative(
+      &storage_path,
+      &name,
+      thumbnail,
+      config.thumbnail_cache_size(),
+    )
+    .await?;
+  }
+
+  let (derivative, path) = storage::nonblocking::read_derivative(&storage_path, &name)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+  let content_type: actix_web::mime::Mime = image_format
+    .to_mime_type()
+    .parse()
+    .map_err(|_| ApiError::InternalServerError)?;
+
+  if let Some((token, identifier)) = authorization {
+    token.complete(&config, identifier).await?;
+  }
+
+  match derivative {
+    storage::Content::Plain(file) => Ok(
+      actix_files::NamedFile::from_file(file, path)?
+        .set_content_type(content_type)
+        .use_last_modified(true)
+        .into_response(&req),
+    ),
+    // the thumbnails are small enough to be decrypted in memory
+    mut derivative @ storage::Content::Encrypted(_) => {
+      let thumbnail = actix_web::web::block(move || {
+        let mut thumbnail = Vec::new();
+        derivative.read_to_end(&mut thumbnail).map(|_| thumbnail)
+      })
+      .await??;
+
+      Ok(
+        HttpResponse::Ok()
+          .content_type(content_type)
+          .body(thumbnail),
+      )
+    }
+  }
+}
+
+/// Whether the thumbnails can be made of an item with the `content_type`
+pub(super) fn decodable(content_type: &str) -> bool {
+  let essence = content_type.split(';').next().unwrap_or_default().trim();
+
+  DECODABLE_TYPES
+    .iter()
+    .any(|decodable| decodable.eq_ignore_ascii_case(essence))
+}
+
+/// Decode the image read from the `reader`, refusing the ones that weigh more
+/// than `max_bytes` or that would take too much memory once decoded
+pub(super) fn decode(reader: impl Read, max_bytes: u64) -> Result<DynamicImage, ApiError> {
+  // the reader of the image seeks, which the decoders of compressed items
+  // can't, so the content is read in memory
+  let mut content = Vec::new();
+  reader
+    .take(max_bytes.saturating_add(1))
+    .read_to_end(&mut content)?;
+
+  if content.len() as u64 > max_bytes {
+    return Err(image_too_large());
+  }
+
+  let mut limits = image::Limits::default();
+  limits.max_image_width = Some(DECODE_MAX_DIMENSION);
+  limits.max_image_height = Some(DECODE_MAX_DIMENSION);
+  limits.max_alloc = Some(DECODE_MAX_ALLOC);
+
+  let mut reader = image::ImageReader::new(std::io::Cursor::new(content)).with_guessed_format()?;
+  reader.limits(limits);
+
+  reader.decode().map_err(|e| match e {
+    image::ImageError::Limits(_) => image_too_large(),
+    _ => ApiError::UnsupportedMediaType,
+  })
+}
+
+fn image_too_large() -> ApiError {
+  ApiError::PayloadTooLarge("the image is too large to make a thumbnail of")
+}
+
+/// The width and height of the thumbnail, that cannot exceed the `max` ones
+fn dimensions(query: &ThumbnailQuery, max: (u32, u32)) -> Result<(u32, u32), ApiError> {
+  let (width, height) = match (query.w, query.h) {
+    (Some(w), Some(h)) => (w, h),
+    (Some(w), None) => (w, w),
+    (None, Some(h)) => (h, h),
+    (None, None) => return Err(ApiError::BadRequest("w or h is required")),
+  };
+
+  if width == 0 || height == 0 || width > max.0 || height > max.1 {
+    return Err(ApiError::BadRequest(
+      "the dimensions of the thumbnail are out of bounds",
+    ));
+  }
+
+  Ok((width, height))
+}
+
+fn resize(image: DynamicImage, width: u32, height: u32, fit: Fit) -> DynamicImage {
+  let filter = FilterType::CatmullRom;
+
+  match fit {
+    Fit::Contain if image.width() <= width && image.height() <= height => image,
+    Fit::Contain => image.resize(width, height, filter),
+    Fit::Cover => image.resize_to_fill(width, height, filter),
+    Fit::Fill => image.resize_exact(width, height, filter),
+  }
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>, ApiError> {
+  // JPEG has no alpha channel
+  let image = match format {
+    ImageFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8()),
+    _ => DynamicImage::ImageRgba8(image.to_rgba8()),
+  };
+
+  let mut encoded = std::io::Cursor::new(Vec::new());
+  image.write_to(&mut encoded, format).map_err(|e| {
+    println!("thumbnail encoding error: {e}");
+
+    ApiError::InternalServerError
+  })?;
+
+  Ok(encoded.into_inner())
+}
@@ -1,5 +1,9 @@
 use actix_multipart::form::tempfile::TempFile;
 use actix_multipart::form::MultipartForm;
+use storage::BucketName;
+use storage::ItemName;
+
+use super::bearer_token::AuthenticatedBearerIdentifier;
 
 #[derive(Debug, MultipartForm)]
 pub struct UploadFileBody {
@@ -8,41 +12,129 @@ pub struct UploadFileBody {
 }
 
 impl UploadFileBody {
-  pub fn into_metadata(
-    self, bucket: &str,
-  ) -> Result<(super::Metadata, String, TempFile), super::ApiError> {
-    let user_filename = self.file.file_name.clone();
-    let unique_id = Self::next_unique_id(bucket)?;
-
-    let mut filename = unique_id.clone();
-    if let Some(name) = user_filename.as_ref() {
-      let user_extension = std::path::Path::new(&name).extension();
-      if let Some(ext) = user_extension {
-        if let Some(ext) = ext.to_str() {
-          filename.push('.');
-          filename.push_str(ext);
-        }
-      }
+  /// Split the body into the metadata of the item, a unique name for it in the
+  /// `bucket` and the uploaded file. The system metadata of the item is filled
+  /// in, with the `uploader` as its uploader.
+  ///
+  /// Fails if the bucket doesn't exist, or if its settings don't allow the
+  /// MIME type of the uploaded file.
+  ///
+  /// The extension of the uploaded filename is kept in the name of the item
+  /// unless it contains characters an [ItemName] doesn't allow.
+  pub async fn into_metadata(
+    self, bucket: &BucketName, uploader: &AuthenticatedBearerIdentifier,
+  ) -> Result<(super::Metadata, ItemName, tempfile::NamedTempFile), super::ApiError> {
+    let settings = storage::nonblocking::buckets::settings(bucket).await?;
+    let content_type = self.content_type().await?;
+    let checksum = self.checksum().await?;
+
+    if !settings.allows_mime_type(&content_type) {
+      return Err(super::ApiError::UnsupportedMediaType);
     }
 
+    let (tempfile, encoding) =
+      storage::nonblocking::compress_tempfile(bucket, self.file.file, &content_type).await?;
+
+    let user_filename = self.file.file_name.clone();
+    let (unique_id, filename) = unique_item_name(bucket, user_filename.as_deref()).await?;
+
+    let now = super::metadata::unix_timestamp(std::time::SystemTime::now());
     let metadata = super::Metadata {
-      alias: user_filename.unwrap_or_else(|| unique_id),
+      alias: user_filename
+        .clone()
+        .unwrap_or_else(|| unique_id.to_string()),
       custom: self.metadata.map(|j| j.0),
+      system: Some(super::SystemMetadata {
+        content_type,
+        size: self.file.size as u64,
+        checksum: Some(checksum),
+        created_at: now,
+        modified_at: now,
+        uploader: Some(uploader.as_str().to_owned()),
+        filename: user_filename,
+        encoding,
+      }),
+      revision: 0,
     };
 
-    Ok((metadata, filename, self.file))
+    Ok((metadata, filename, tempfile))
   }
 
-  fn next_unique_id(bucket: &str) -> Result<String, super::ApiError> {
-    for _ in 0..100 {
-      let id = nanoid::nanoid!();
-      let storage_path = storage::internal::storage_path(bucket, &id);
+  /// The MIME type of the uploaded file given by the multipart form, or
+  /// sniffed from its first bytes when the form doesn't give a specific one
+  async fn content_type(&self) -> Result<String, super::ApiError> {
+    let given = self
+      .file
+      .content_type
+      .as_ref()
+      .map(|mime| mime.essence_str().to_owned());
 
-      if !storage::exists(&storage_path)? {
-        return Ok(id);
-      }
+    if let Some(given) = given.filter(|mime| mime != "application/octet-stream") {
+      return Ok(given);
     }
 
-    Err(super::ApiError::InternalServerError)
+    let path = self.file.file.path().to_owned();
+    let sniffed = actix_web::web::block(move || infer::get_from_path(path)).await??;
+
+    Ok(
+      sniffed
+        .map(|kind| kind.mime_type())
+        .unwrap_or("application/octet-stream")
+        .to_owned(),
+    )
+  }
+
+  /// The hex encoded SHA-256 of the uploaded file
+  async fn checksum(&self) -> Result<String, super::ApiError> {
+    use sha2::Digest;
+
+    let path = self.file.file.path().to_owned();
+    let digest = actix_web::web::block(move || {
+      let mut hasher = sha2::Sha256::new();
+      std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+
+      Ok::<_, std::io::Error>(hasher.finalize())
+    })
+    .await??;
+
+    Ok(format!("{digest:x}"))
   }
 }
+
+/// The query of the upload routes
+#[derive(Debug, serde::Deserialize)]
+pub struct UploadQuery {
+  /// Expand the uploaded ZIP or tar archive into one item per file
+  #[serde(default)]
+  pub extract: bool,
+}
+
+/// A unique id for a new item of the `bucket`, and the name of the item: the id
+/// followed by the extension of the `filename` unless it contains characters an
+/// [ItemName] doesn't allow.
+pub async fn unique_item_name(
+  bucket: &BucketName, filename: Option<&str>,
+) -> Result<(ItemName, ItemName), super::ApiError> {
+  let unique_id = next_unique_id(bucket).await?;
+
+  let name = filename
+    .and_then(|name| std::path::Path::new(name).extension())
+    .and_then(|ext| ext.to_str())
+    .and_then(|ext| ItemName::new(format!("{unique_id}.{ext}")).ok())
+    .unwrap_or_else(|| unique_id.clone());
+
+  Ok((unique_id, name))
+}
+
+async fn next_unique_id(bucket: &BucketName) -> Result<ItemName, super::ApiError> {
+  for _ in 0..100 {
+    let id = ItemName::new(nanoid::nanoid!())?;
+    let storage_path = storage::StoragePath::new(bucket.clone(), id.clone());
+
+    if !storage::nonblocking::exists(&storage_path).await? {
+      return Ok(id);
+    }
+  }
+
+  Err(super::ApiError::InternalServerError)
+}
@@ -0,0 +1,53 @@
+//! The endpoints to manage the previous versions of the items, kept by the
+//! versioned buckets
+use actix_web::web::Data;
+use actix_web::web::Path;
+use actix_web::HttpResponse;
+
+use storage::BucketName;
+use storage::ItemName;
+use storage::StoragePath;
+
+use super::sdk;
+use super::ApiError;
+use super::BearerToken;
+use super::Config;
+
+pub(super) async fn list_versions(
+  path: Path<(BucketName, ItemName)>, token: BearerToken, config: Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+  let identifier = token
+    .authenticate(&config, sdk::Operation::MetadataGet)
+    .await?;
+
+  let storage_path = StoragePath::from(path.into_inner());
+  let versions = storage::nonblocking::versions::list(&storage_path).await?;
+
+  token.complete(&config, identifier).await?;
+  Ok(HttpResponse::Ok().json(versions))
+}
+
+pub(super) async fn restore_version(
+  path: Path<(BucketName, ItemName, String)>, token: BearerToken, config: Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+  let identifier = token.authenticate(&config, sdk::Operation::Replace).await?;
+
+  let (bucket, item, version) = path.into_inner();
+  let storage_path = StoragePath::new(bucket, item);
+  storage::nonblocking::versions::restore(&storage_path, &version).await?;
+
+  token.complete(&config, identifier).await?;
+  Ok(HttpResponse::Ok().finish())
+}
+
+pub(super) async fn purge_versions(
+  path: Path<(BucketName, ItemName)>, token: BearerToken, config: Data<Config>,
+) -> Result<HttpResponse, ApiError> {
+  let identifier = token.authenticate(&config, sdk::Operation::Delete).await?;
+
+  let storage_path = StoragePath::from(path.into_inner());
+  storage::nonblocking::versions::purge(&storage_path).await?;
+
+  token.complete(&config, identifier).await?;
+  Ok(HttpResponse::Ok().finish())
+}
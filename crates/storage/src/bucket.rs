@@ -11,18 +11,82 @@ impl Bucket {
     id
   }
 
-  pub(crate) fn path(root: &std::path::PathBuf, name: &str) -> std::path::PathBuf {
+  pub(crate) fn path(root: &std::path::Path, name: &str) -> std::path::PathBuf {
     root.join(name)
   }
 
-  pub(crate) fn exists(root: &std::path::PathBuf, name: &str) -> bool {
+  pub(crate) fn exists(root: &std::path::Path, name: &str) -> bool {
     Self::path(root, name).exists()
   }
 
-  /// Returns the number of files in the bucket
-  pub(crate) fn size(root: &std::path::PathBuf, name: &str) -> Result<usize> {
-    let count = std::fs::read_dir(Self::path(root, name))?.count();
+  /// Returns the names of the items in the bucket, without their metadata
+  /// files nor the hidden files
+  pub(crate) fn items(root: &std::path::Path, name: &str) -> Result<Vec<String>> {
+    let mut items = Vec::new();
 
-    Ok(count)
+    for entry in std::fs::read_dir(Self::path(root, name))? {
+      let entry = entry?;
+      let filename = entry.file_name().to_string_lossy().into_owned();
+
+      if entry.file_type()?.is_file()
+        && !filename.starts_with('.')
+        && !Metadata::is_metadata_filename(&filename)
+      {
+        items.push(filename);
+      }
+    }
+
+    Ok(items)
+  }
+
+  /// Returns the number of items in the bucket, without their metadata files
+  /// nor the hidden files
+  pub(crate) fn item_count(root: &std::path::Path, name: &str) -> Result<usize> {
+    Ok(Self::items(root, name)?.len())
+  }
+
+  /// The hidden folder of the bucket where the previous versions of its items
+  /// are kept: `.versions/{name}/{version}`
+  pub(crate) fn versions_path(root: &std::path::Path, name: &str) -> std::path::PathBuf {
+    Self::path(root, name).join(".versions")
+  }
+
+  /// Returns the sum of the sizes of the previous versions of the items of the
+  /// bucket, and of their metadata files
+  pub(crate) fn versions_bytes(root: &std::path::Path, name: &str) -> Result<u64> {
+    let items = match std::fs::read_dir(Self::versions_path(root, name)) {
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+      items => items?,
+    };
+    let mut total = 0;
+
+    for item in items {
+      for version in std::fs::read_dir(item?.path())? {
+        let metadata = version?.metadata()?;
+
+        if metadata.is_file() {
+          total += metadata.len();
+        }
+      }
+    }
+
+    Ok(total)
+  }
+
+  /// Returns the sum of the sizes of the files in the bucket, without the
+  /// hidden files
+  pub(crate) fn total_bytes(root: &std::path::Path, name: &str) -> Result<u64> {
+    let mut total = 0;
+
+    for entry in std::fs::read_dir(Self::path(root, name))? {
+      let entry = entry?;
+      let metadata = entry.metadata()?;
+
+      if metadata.is_file() && !entry.file_name().to_string_lossy().starts_with('.') {
+        total += metadata.len();
+      }
+    }
+
+    Ok(total)
   }
 }
@@ -0,0 +1,104 @@
+use crate::*;
+
+/// The settings of a bucket, stored in a hidden file inside of the bucket. A
+/// bucket without a settings file uses the default settings.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BucketSettings {
+  /// Whether the items can be downloaded without authentication
+  pub visibility: Visibility,
+
+  /// The maximum number of bytes the files of the bucket can weigh in total
+  pub max_size: Option<u64>,
+
+  /// The MIME types the items of the bucket can have, `image/*` allows every
+  /// image type. Every type is allowed if unset.
+  pub allowed_mime_types: Option<Vec<String>>,
+
+  /// The number of seconds after their last modification the items are
+  /// removed
+  pub ttl: Option<u64>,
+
+  /// Whether the replaced items are kept as previous versions
+  pub versioning: bool,
+
+  /// The maximum number of previous versions kept per item, the oldest ones
+  /// are removed past it. Every version is kept if unset.
+  pub max_versions: Option<usize>,
+
+  /// How the content of the new items is compressed at rest, it is stored as
+  /// is if unset
+  pub compression: Option<Compression>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Visibility {
+  #[default]
+  Public,
+  Private,
+}
+
+impl BucketSettings {
+  const FILENAME: &'static str = ".bucket.yaml";
+
+  fn path(root: &std::path::Path, bucket: &str) -> std::path::PathBuf {
+    Bucket::path(root, bucket).join(Self::FILENAME)
+  }
+
+  pub(crate) fn from_file(root: &std::path::Path, bucket: &str) -> Result<Self> {
+    let path = Self::path(root, bucket);
+
+    let settings = match path.exists() {
+      true => serde_yaml::from_str(&std::fs::read_to_string(path)?)?,
+      false => Self::default(),
+    };
+
+    Ok(settings)
+  }
+
+  pub(crate) fn to_file(&self, root: &std::path::Path, bucket: &str) -> Result<()> {
+    std::fs::write(Self::path(root, bucket), serde_yaml::to_string(self)?)?;
+
+    Ok(())
+  }
+
+  /// Returns whether an item of the given `mime_type` can be stored in the
+  /// bucket
+  pub fn allows_mime_type(&self, mime_type: &str) -> bool {
+    let Some(allowed) = &self.allowed_mime_types else {
+      return true;
+    };
+
+    mime_type_matches(allowed, mime_type)
+  }
+
+  /// The encoding of a new item of `size` bytes and of the `mime_type`, if the
+  /// bucket compresses it
+  pub fn encoding_for(&self, size: u64, mime_type: &str) -> Option<Encoding> {
+    self.compression.as_ref()?.encoding_for(size, mime_type)
+  }
+
+  /// Returns whether an item last modified at `modified` outlived the TTL of
+  /// the bucket
+  pub(crate) fn is_expired(&self, modified: std::time::SystemTime) -> bool {
+    let Some(ttl) = self.ttl else {
+      return false;
+    };
+
+    modified
+      .elapsed()
+      .is_ok_and(|age| age > std::time::Duration::from_secs(ttl))
+  }
+}
+
+/// Returns whether the `mime_type` matches one of the `patterns`, where
+/// `image/*` matches every image type
+pub(crate) fn mime_type_matches(patterns: &[String], mime_type: &str) -> bool {
+  patterns
+    .iter()
+    .any(|pattern| match pattern.strip_suffix("/*") {
+      Some(kind) => mime_type.split('/').next() == Some(kind),
+      None => pattern == mime_type,
+    })
+}
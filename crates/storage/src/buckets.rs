@@ -0,0 +1,202 @@
+//! Explicit management of the buckets, alongside the ones that are created
+//! automatically by the [crate::BucketPolicy].
+use crate::*;
+
+/// The description of a bucket returned by [describe]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BucketInfo {
+  pub name: BucketName,
+  pub settings: BucketSettings,
+
+  /// The number of items in the bucket, metadata files excluded
+  pub items: usize,
+
+  /// The total size of the files in the bucket, metadata files included
+  pub bytes: u64,
+
+  /// Whether the bucket is the one receiving the new uploads
+  pub active: bool,
+}
+
+/// Create a new empty bucket with the given `settings`
+///
+/// ```rs
+/// storage::buckets::create(&"invoices".parse()?, storage::BucketSettings::default())?;
+/// ```
+pub fn create(name: &BucketName, settings: BucketSettings) -> Result<()> {
+  let root = &config()?.root;
+
+  if Bucket::exists(root, name) {
+    return Err(StorageError::BucketAlreadyExists);
+  }
+
+  std::fs::create_dir_all(Bucket::path(root, name))?;
+  settings.to_file(root, name)?;
+
+  Ok(())
+}
+
+/// Remove the bucket called `name`. Unless `force` is set, a bucket that still
+/// holds items is not removed.
+///
+/// If the bucket is the active one then a new bucket is activated first.
+pub fn delete(name: &BucketName, force: bool) -> Result<()> {
+  let config = config()?;
+
+  if !Bucket::exists(&config.root, name) {
+    return Err(StorageError::BucketNotFound);
+  }
+
+  if !force && Bucket::item_count(&config.root, name)? > 0 {
+    return Err(StorageError::BucketNotEmpty);
+  }
+
+  if config.active_bucket_name()? == *name {
+    config.rotate()?;
+  }
+
+  std::fs::remove_dir_all(Bucket::path(&config.root, name))?;
+  config.index.remove_bucket(name)?;
+
+  Ok(())
+}
+
+/// Rename the bucket `from` into `to`, the storage paths of its items change
+/// accordingly.
+pub fn rename(from: &BucketName, to: &BucketName) -> Result<()> {
+  let config = config()?;
+
+  if !Bucket::exists(&config.root, from) {
+    return Err(StorageError::BucketNotFound);
+  }
+
+  if Bucket::exists(&config.root, to) {
+    return Err(StorageError::BucketAlreadyExists);
+  }
+
+  config.rename_bucket(from, to)
+}
+
+/// Describe the bucket called `name`
+pub fn describe(name: &BucketName) -> Result<BucketInfo> {
+  let config = config()?;
+
+  if !Bucket::exists(&config.root, name) {
+    return Err(StorageError::BucketNotFound);
+  }
+
+  Ok(BucketInfo {
+    name: name.clone(),
+    settings: BucketSettings::from_file(&config.root, name)?,
+    items: Bucket::item_count(&config.root, name)?,
+    bytes: Bucket::total_bytes(&config.root, name)?,
+    active: config.active_bucket_name()? == *name,
+  })
+}
+
+/// Returns the names of every bucket, the folders of the root whose name isn't
+/// a valid [BucketName] are ignored
+pub fn list() -> Result<Vec<BucketName>> {
+  let root = &config()?.root;
+  let mut buckets = Vec::new();
+
+  for entry in std::fs::read_dir(root)? {
+    let entry = entry?;
+    let name = BucketName::new(entry.file_name().to_string_lossy());
+
+    if let (true, Ok(name)) = (entry.file_type()?.is_dir(), name) {
+      buckets.push(name);
+    }
+  }
+
+  Ok(buckets)
+}
+
+/// Returns the names of the items of the bucket called `name`, sorted, without
+/// their metadata files
+pub fn items(name: &BucketName) -> Result<Vec<ItemName>> {
+  let root = &config()?.root;
+
+  if !Bucket::exists(root, name) {
+    return Err(StorageError::BucketNotFound);
+  }
+
+  let mut items: Vec<ItemName> = Bucket::items(root, name)?
+    .into_iter()
+    .filter_map(|item| ItemName::new(item).ok())
+    .collect();
+  items.sort();
+
+  Ok(items)
+}
+
+/// Returns the settings of the bucket called `name`
+pub fn settings(name: &BucketName) -> Result<BucketSettings> {
+  let root = &config()?.root;
+
+  if !Bucket::exists(root, name) {
+    return Err(StorageError::BucketNotFound);
+  }
+
+  BucketSettings::from_file(root, name)
+}
+
+/// Replace the settings of the bucket called `name`, they only apply to the
+/// future operations.
+pub fn set_settings(name: &BucketName, settings: BucketSettings) -> Result<()> {
+  let root = &config()?.root;
+
+  if !Bucket::exists(root, name) {
+    return Err(StorageError::BucketNotFound);
+  }
+
+  settings.to_file(root, name)
+}
+
+/// Remove the items that outlived the TTL of their bucket, returns the number
+/// of removed items.
+///
+/// A bucket or an item that cannot be read or removed is reported and skipped,
+/// so that it doesn't prevent the removal of the others.
+pub fn remove_expired() -> Result<usize> {
+  let root = &config()?.root;
+  let mut removed = 0;
+
+  for bucket in list()? {
+    let items = BucketSettings::from_file(root, &bucket)
+      .and_then(|settings| Ok((settings, Bucket::items(root, &bucket)?)));
+
+    let (settings, items) = match items {
+      Ok((settings, _)) if settings.ttl.is_none() => continue,
+      Ok(items) => items,
+      Err(e) => {
+        println!("ERROR: expired items of {bucket} could not be listed: {e}");
+        continue;
+      }
+    };
+
+    for item in items {
+      let Ok(item) = ItemName::new(item) else {
+        continue;
+      };
+
+      let storage_path = StoragePath::new(bucket.clone(), item);
+      let expired = std::fs::metadata(Item::path(root, &bucket, &storage_path.item))
+        .and_then(|file| file.modified())
+        .map(|modified| settings.is_expired(modified));
+
+      let removal = match expired {
+        Ok(false) => continue,
+        Ok(true) => remove(&storage_path),
+        Err(e) => Err(e.into()),
+      };
+
+      match removal {
+        Ok(()) => removed += 1,
+        Err(e) => println!("ERROR: expired item {storage_path} could not be removed: {e}"),
+      }
+    }
+  }
+
+  Ok(removed)
+}
@@ -2,14 +2,33 @@ use crate::*;
 
 pub(crate) struct Config {
   pub(crate) root: std::path::PathBuf,
-  active_bucket_name: std::sync::RwLock<String>,
+  active_bucket_name: std::sync::RwLock<BucketName>,
 
-  /// the maximum size of a bucket
-  bucket_size: usize,
+  /// decides when the active bucket is replaced by a new one
+  policy: std::sync::RwLock<Box<dyn BucketPolicy>>,
+
+  /// the format the metadata files are written in
+  metadata_format: std::sync::RwLock<MetadataFormat>,
+
+  /// the index of the items and their metadata, used by the queries
+  pub(crate) index: Index,
+
+  /// held per item while its metadata file is read then rewritten by
+  /// [crate::update_metadata]
+  pub(crate) metadata_lock: KeyedLock,
+
+  /// held per bucket from the max size check of a write until the item is in
+  /// place, so that concurrent writes cannot exceed the max size together
+  pub(crate) write_lock: KeyedLock,
 }
 
 static CONFIG: once_cell::sync::OnceCell<Config> = once_cell::sync::OnceCell::new();
 
+/// The keys of the encryption at rest, kept apart from the [Config] so that
+/// they can be set before [initialize] reads the metadata files to rebuild the
+/// index
+static KEYRING: std::sync::RwLock<Option<std::sync::Arc<Keyring>>> = std::sync::RwLock::new(None);
+
 /// Initialize the storage system to use the given `root` directory for its
 /// internal storage. Then optionally set the maximum number of items a single
 /// bucket can hold. If set to `None` then it will use the default optimized
@@ -24,61 +43,157 @@ static CONFIG: once_cell::sync::OnceCell<Config> = once_cell::sync::OnceCell::ne
 /// ```
 pub fn initialize(
   root: impl Into<std::path::PathBuf>, custom_bucket_size: Option<usize>,
+) -> Result<()> {
+  let max = custom_bucket_size.unwrap_or(constants::BUCKET_SIZE_MAX);
+
+  initialize_with_policy(root, ItemCount::new(max))
+}
+
+/// Initialize the storage system to use the given `root` directory for its
+/// internal storage, and the given [BucketPolicy] to decide when new buckets
+/// are created.
+///
+/// ```rs
+/// storage::initialize_with_policy(".", storage::Periodic::Daily)?;
+/// ```
+pub fn initialize_with_policy(
+  root: impl Into<std::path::PathBuf>, policy: impl BucketPolicy + 'static,
 ) -> Result<()> {
   let root = root.into();
-  let dotfile = DotFile::from_file(&root)?;
-  let active_bucket_name = dotfile.active_bucket_name.into_owned();
+  let dotfile = DotFile::from_file(&root, &policy)?;
+  let active_bucket_name = dotfile.active_bucket_name;
 
-  if let Err(_) = std::fs::create_dir_all(Bucket::path(&root, &active_bucket_name)) {}
+  let _ = std::fs::create_dir_all(Bucket::path(&root, &active_bucket_name));
+  let index = Index::open(&root)?;
 
   CONFIG
     .set(Config {
-      root: root,
+      root,
       active_bucket_name: active_bucket_name.into(),
-      bucket_size: custom_bucket_size.unwrap_or(constants::BUCKET_SIZE_MAX),
+      policy: std::sync::RwLock::new(Box::new(policy)),
+      metadata_format: std::sync::RwLock::new(MetadataFormat::default()),
+      index,
+      metadata_lock: KeyedLock::default(),
+      write_lock: KeyedLock::default(),
     })
     .map_err(|_| StorageError::ConfigAlreadySet)
 }
 
+/// Replace the [BucketPolicy] of the initialized storage system. The new
+/// policy applies from the next stored item.
+pub fn set_bucket_policy(policy: impl BucketPolicy + 'static) -> Result<()> {
+  *config()?.policy.write()? = Box::new(policy);
+
+  Ok(())
+}
+
+/// Replace the [MetadataFormat] the metadata files are written in. The files
+/// that were written in another format can still be read, see
+/// [crate::migrate_metadata] to convert them.
+pub fn set_metadata_format(format: MetadataFormat) -> Result<()> {
+  *config()?.metadata_format.write()? = format;
+
+  Ok(())
+}
+
+/// Encrypt the items, their metadata and derivatives written from now on with
+/// the given [Keyring], or write them as they are if `None`. The files that
+/// were written encrypted are read with the keyring whatever its setting is,
+/// and those that were not are read as they are.
+///
+/// It can be called before [initialize], and must be when the metadata files
+/// are encrypted and the index may need to be rebuilt from them.
+///
+/// ```rs
+/// storage::set_keyring(Some(storage::Keyring::new("00..ff".parse()?)))?;
+/// ```
+pub fn set_keyring(keyring: Option<Keyring>) -> Result<()> {
+  *KEYRING.write()? = keyring.map(std::sync::Arc::new);
+
+  Ok(())
+}
+
+pub(crate) fn keyring() -> Result<Option<std::sync::Arc<Keyring>>> {
+  Ok(KEYRING.read()?.clone())
+}
+
 pub(crate) fn config() -> Result<&'static Config> {
   CONFIG.get().ok_or(StorageError::ConfigNotSet)
 }
 
 impl Config {
-  pub(crate) fn with_bucket<'a>(&self) -> Result<String> {
-    let active_bucket_size = {
+  pub(crate) fn with_bucket(&self) -> Result<BucketName> {
+    {
       let name = self.active_bucket_name.read()?;
 
-      Bucket::size(&self.root, &name)?
-    };
+      if !self.policy.read()?.is_full(&self.root, &name)? {
+        return Ok(name.clone());
+      }
+    }
 
-    if active_bucket_size >= self.bucket_size {
-      let mut active_bucket = self.active_bucket_name.write()?;
-      let mut new_bucket_name = None;
+    let mut active_bucket = self.active_bucket_name.write()?;
 
-      // this loop ensures the newly created bucket doesn't point to an already
-      // existing one.
-      while let None = new_bucket_name {
-        let bucket_name = Bucket::new_random_name();
+    // another thread may have replaced the bucket while no lock was held
+    if self.policy.read()?.is_full(&self.root, &active_bucket)? {
+      self.activate_next_bucket(&mut active_bucket)?;
+    }
 
-        if !Bucket::exists(&self.root, &bucket_name) {
-          new_bucket_name = Some(bucket_name);
-        }
-      }
+    Ok(active_bucket.clone())
+  }
+
+  pub(crate) fn metadata_format(&self) -> Result<MetadataFormat> {
+    Ok(*self.metadata_format.read()?)
+  }
+
+  pub(crate) fn active_bucket_name(&self) -> Result<BucketName> {
+    Ok(self.active_bucket_name.read()?.clone())
+  }
 
-      let new_bucket_name = new_bucket_name.unwrap_or_default();
+  /// Rename the bucket `from` into `to`, and follow it if it was the active
+  /// bucket.
+  pub(crate) fn rename_bucket(&self, from: &BucketName, to: &BucketName) -> Result<()> {
+    let mut active_bucket = self.active_bucket_name.write()?;
 
-      std::fs::create_dir_all(&Bucket::path(&self.root, &new_bucket_name))?;
+    std::fs::rename(Bucket::path(&self.root, from), Bucket::path(&self.root, to))?;
+    self.index.rename_bucket(from, to)?;
 
+    if *active_bucket == *from {
       DotFile {
-        active_bucket_name: std::borrow::Cow::from(&new_bucket_name),
+        active_bucket_name: to.clone(),
       }
       .to_file(&self.root)?;
 
-      // dotfile.active_bucket_name(&self.root, bucket_name).await?;
-      *active_bucket = new_bucket_name;
+      *active_bucket = to.clone();
     }
 
-    Ok(self.active_bucket_name.read()?.clone())
+    Ok(())
+  }
+
+  /// Create the next bucket the policy decides on and make it the active one,
+  /// regardless of whether the currently active bucket is full.
+  pub(crate) fn rotate(&self) -> Result<BucketName> {
+    let mut active_bucket = self.active_bucket_name.write()?;
+
+    self.activate_next_bucket(&mut active_bucket)?;
+
+    Ok(active_bucket.clone())
+  }
+
+  fn activate_next_bucket(&self, active_bucket: &mut BucketName) -> Result<()> {
+    let new_bucket_name = self
+      .policy
+      .read()?
+      .next_name(&self.root, Some(active_bucket))?;
+
+    std::fs::create_dir_all(Bucket::path(&self.root, &new_bucket_name))?;
+
+    DotFile {
+      active_bucket_name: new_bucket_name.clone(),
+    }
+    .to_file(&self.root)?;
+
+    *active_bucket = new_bucket_name;
+
+    Ok(())
   }
 }
@@ -1,3 +1,14 @@
 /// The maximum amount of items a single bucket can store before a new bucket
 /// is created to hold new items.
 pub const BUCKET_SIZE_MAX: usize = 10_000;
+
+/// The number of items in a page of query results when the query doesn't set
+/// a limit.
+pub const QUERY_LIMIT_DEFAULT: usize = 100;
+
+/// The maximum number of items in a page of query results.
+pub const QUERY_LIMIT_MAX: usize = 1_000;
+
+/// The size in bytes under which the items aren't compressed, when the
+/// compression settings of their bucket don't set one.
+pub const COMPRESSION_MIN_SIZE_DEFAULT: u64 = 1024;
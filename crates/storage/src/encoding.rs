@@ -0,0 +1,103 @@
+use crate::*;
+
+/// How the content of an item is compressed at rest, see [Compression]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+  Zstd,
+  Gzip,
+}
+
+impl Encoding {
+  /// The name of the encoding in the `Content-Encoding` header
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Encoding::Zstd => "zstd",
+      Encoding::Gzip => "gzip",
+    }
+  }
+
+  /// Wrap the `reader` of the content of an item stored with this encoding,
+  /// so that it reads the decompressed content
+  pub fn decoder<'a, R>(self, reader: R) -> Result<Box<dyn std::io::Read + Send + 'a>>
+  where
+    R: std::io::Read + Send + 'a,
+  {
+    let decoder: Box<dyn std::io::Read + Send> = match self {
+      Encoding::Zstd => Box::new(zstd::stream::read::Decoder::new(reader)?),
+      Encoding::Gzip => Box::new(flate2::read::GzDecoder::new(reader)),
+    };
+
+    Ok(decoder)
+  }
+
+  /// Write the compressed content of the `reader` to the `writer`
+  pub(crate) fn encode(
+    self, reader: &mut impl std::io::Read, writer: impl std::io::Write,
+  ) -> Result<()> {
+    match self {
+      // 0 is the default level of zstd
+      Encoding::Zstd => zstd::stream::copy_encode(reader, writer, 0)?,
+      Encoding::Gzip => {
+        let mut encoder = flate2::write::GzEncoder::new(writer, flate2::Compression::default());
+        std::io::copy(reader, &mut encoder)?;
+        encoder.finish()?;
+      }
+    };
+
+    Ok(())
+  }
+}
+
+/// The compression at rest of the items of a bucket, that only applies to the
+/// items that are large enough and of a compressible MIME type
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Compression {
+  pub encoding: Encoding,
+
+  /// The items smaller than this number of bytes are stored as they are
+  #[serde(default = "Compression::default_min_size")]
+  pub min_size: u64,
+
+  /// The MIME types of the items that are compressed, `text/*` matches every
+  /// text type. Defaults to text and structured data, as media types are
+  /// already compressed.
+  #[serde(default = "Compression::default_mime_types")]
+  pub mime_types: Vec<String>,
+}
+
+impl Compression {
+  pub fn new(encoding: Encoding) -> Self {
+    Self {
+      encoding,
+      min_size: Self::default_min_size(),
+      mime_types: Self::default_mime_types(),
+    }
+  }
+
+  fn default_min_size() -> u64 {
+    constants::COMPRESSION_MIN_SIZE_DEFAULT
+  }
+
+  fn default_mime_types() -> Vec<String> {
+    [
+      "text/*",
+      "application/json",
+      "application/xml",
+      "application/javascript",
+      "application/x-ndjson",
+      "image/svg+xml",
+    ]
+    .map(String::from)
+    .to_vec()
+  }
+
+  /// The encoding of an item of `size` bytes and of the `mime_type`, if it is
+  /// compressed
+  pub fn encoding_for(&self, size: u64, mime_type: &str) -> Option<Encoding> {
+    let applies = size >= self.min_size && mime_type_matches(&self.mime_types, mime_type);
+
+    applies.then_some(self.encoding)
+  }
+}
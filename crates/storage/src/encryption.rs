@@ -0,0 +1,481 @@
+use std::io::Read;
+use std::io::Seek;
+use std::io::Write;
+
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::stream::DecryptorBE32;
+use chacha20poly1305::aead::stream::EncryptorBE32;
+use chacha20poly1305::aead::Aead;
+use chacha20poly1305::aead::AeadCore;
+use chacha20poly1305::aead::KeyInit;
+use chacha20poly1305::aead::OsRng;
+use chacha20poly1305::Key;
+use chacha20poly1305::XChaCha20Poly1305;
+use chacha20poly1305::XNonce;
+
+use crate::*;
+
+/// Starts the files written encrypted, the files without it are read as they
+/// are
+const MAGIC: &[u8; 8] = b"SHCSENC1";
+
+const KEY_NONCE_LEN: usize = 24;
+
+/// A data key encrypted by a master key, followed by its tag
+const WRAPPED_KEY_LEN: usize = 32 + TAG_LEN;
+
+/// The nonce of the STREAM construction is the one of XChaCha20 minus the 5
+/// bytes of its counter
+const STREAM_NONCE_LEN: usize = 19;
+
+const HEADER_LEN: usize = MAGIC.len() + KEY_NONCE_LEN + WRAPPED_KEY_LEN + STREAM_NONCE_LEN;
+
+/// The content is encrypted by chunks of this size, each followed by its tag
+const CHUNK_SIZE: usize = 64 * 1024;
+const TAG_LEN: usize = 16;
+
+/// A 32 bytes key that encrypts the data keys of the items, parsed from its
+/// hex encoding
+#[derive(Clone)]
+pub struct MasterKey(Key);
+
+impl MasterKey {
+  pub fn new(bytes: [u8; 32]) -> Self {
+    Self(bytes.into())
+  }
+
+  fn cipher(&self) -> XChaCha20Poly1305 {
+    XChaCha20Poly1305::new(&self.0)
+  }
+}
+
+impl std::str::FromStr for MasterKey {
+  type Err = StorageError;
+
+  fn from_str(s: &str) -> Result<Self> {
+    let s = s.trim();
+
+    if s.len() != 64 || !s.is_ascii() {
+      return Err(StorageError::InvalidMasterKey);
+    }
+
+    let mut bytes = [0; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+      *byte =
+        u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).map_err(|_| StorageError::InvalidMasterKey)?;
+    }
+
+    Ok(Self::new(bytes))
+  }
+}
+
+// the key never ends up in the logs
+impl std::fmt::Debug for MasterKey {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str("MasterKey(..)")
+  }
+}
+
+/// The master keys of the encryption at rest, see [crate::set_keyring]. Every
+/// item is encrypted with its own random data key, that is wrapped by the
+/// current master key.
+///
+/// ```rs
+/// let keyring = storage::Keyring::new(new_key).with_previous(old_key);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Keyring {
+  current: MasterKey,
+
+  /// The keys of before the last rotations, that only unwrap the data keys
+  /// until [crate::rotate_keys] wraps them with the current key
+  previous: Vec<MasterKey>,
+}
+
+impl Keyring {
+  pub fn new(current: MasterKey) -> Self {
+    Self {
+      current,
+      previous: Vec::new(),
+    }
+  }
+
+  pub fn with_previous(mut self, key: MasterKey) -> Self {
+    self.previous.push(key);
+    self
+  }
+
+  /// Wrap the `data_key` with the current master key
+  fn wrap(&self, header: &mut Header, data_key: &Key) -> Result<()> {
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let wrapped = self
+      .current
+      .cipher()
+      .encrypt(&nonce, data_key.as_slice())
+      .map_err(|_| StorageError::UnknownDataKey)?;
+
+    header.key_nonce = nonce.into();
+    header.wrapped_key.copy_from_slice(&wrapped);
+
+    Ok(())
+  }
+
+  /// Unwrap the data key of the `header` with whichever master key wrapped
+  /// it, and return whether it is the current one
+  fn unwrap(&self, header: &Header) -> Result<(Key, bool)> {
+    let nonce = XNonce::from_slice(&header.key_nonce);
+
+    std::iter::once(&self.current)
+      .chain(&self.previous)
+      .enumerate()
+      .find_map(|(i, key)| {
+        let data_key = key.cipher().decrypt(nonce, header.wrapped_key.as_slice());
+
+        data_key
+          .ok()
+          .map(|data_key| (*Key::from_slice(&data_key), i == 0))
+      })
+      .ok_or(StorageError::UnknownDataKey)
+  }
+}
+
+/// The beginning of an encrypted file
+struct Header {
+  key_nonce: [u8; KEY_NONCE_LEN],
+  wrapped_key: [u8; WRAPPED_KEY_LEN],
+  stream_nonce: [u8; STREAM_NONCE_LEN],
+}
+
+impl Header {
+  /// Read the header at the beginning of the `reader`, if it is the one of an
+  /// encrypted file. Otherwise the bytes that were read are lost.
+  fn read(reader: &mut impl Read) -> std::io::Result<Option<Self>> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN);
+    reader
+      .by_ref()
+      .take(HEADER_LEN as u64)
+      .read_to_end(&mut bytes)?;
+
+    Ok(Self::from_bytes(&bytes))
+  }
+
+  fn from_bytes(bytes: &[u8]) -> Option<Self> {
+    let rest = bytes.strip_prefix(MAGIC)?;
+
+    if rest.len() < HEADER_LEN - MAGIC.len() {
+      return None;
+    }
+
+    let (key_nonce, rest) = rest.split_at(KEY_NONCE_LEN);
+    let (wrapped_key, rest) = rest.split_at(WRAPPED_KEY_LEN);
+
+    Some(Self {
+      key_nonce: key_nonce.try_into().ok()?,
+      wrapped_key: wrapped_key.try_into().ok()?,
+      stream_nonce: rest[..STREAM_NONCE_LEN].try_into().ok()?,
+    })
+  }
+
+  fn to_bytes(&self) -> Vec<u8> {
+    [
+      MAGIC.as_slice(),
+      &self.key_nonce,
+      &self.wrapped_key,
+      &self.stream_nonce,
+    ]
+    .concat()
+  }
+}
+
+/// Encrypt what the `reader` reads into the `writer`, with a new data key
+/// wrapped by the current key of the `keyring`
+pub(crate) fn encrypt(keyring: &Keyring, reader: impl Read, mut writer: impl Write) -> Result<()> {
+  let data_key = XChaCha20Poly1305::generate_key(&mut OsRng);
+  let mut header = Header {
+    key_nonce: [0; KEY_NONCE_LEN],
+    wrapped_key: [0; WRAPPED_KEY_LEN],
+    stream_nonce: [0; STREAM_NONCE_LEN],
+  };
+  OsRng.fill_bytes(&mut header.stream_nonce);
+  keyring.wrap(&mut header, &data_key)?;
+
+  writer.write_all(&header.to_bytes())?;
+
+  let mut encryptor = EncryptorBE32::from_aead(
+    XChaCha20Poly1305::new(&data_key),
+    header.stream_nonce.as_slice().into(),
+  );
+  let mut chunks = Chunks::new(reader, CHUNK_SIZE);
+
+  while !chunks.fill()? {
+    let chunk = encryptor
+      .encrypt_next(chunks.chunk())
+      .map_err(|_| std::io::Error::other("chunk encryption failed"))?;
+    writer.write_all(&chunk)?;
+    chunks.consume();
+  }
+
+  let chunk = encryptor
+    .encrypt_last(chunks.chunk())
+    .map_err(|_| std::io::Error::other("chunk encryption failed"))?;
+  writer.write_all(&chunk)?;
+
+  Ok(())
+}
+
+/// The `bytes` encrypted if a keyring is set, as they are otherwise
+pub(crate) fn seal(bytes: &[u8]) -> Result<std::borrow::Cow<'_, [u8]>> {
+  let Some(keyring) = keyring()? else {
+    return Ok(bytes.into());
+  };
+
+  let mut sealed = Vec::with_capacity(HEADER_LEN + bytes.len() + TAG_LEN);
+  encrypt(&keyring, bytes, &mut sealed)?;
+
+  Ok(sealed.into())
+}
+
+/// The `bytes` decrypted if they were encrypted by [seal], as they are
+/// otherwise
+pub(crate) fn unseal(bytes: Vec<u8>) -> Result<Vec<u8>> {
+  let Some(header) = Header::from_bytes(&bytes) else {
+    return Ok(bytes);
+  };
+
+  let mut unsealed = Vec::with_capacity(bytes.len());
+  Decryptor::new(&bytes[HEADER_LEN..], &header)?.read_to_end(&mut unsealed)?;
+
+  Ok(unsealed)
+}
+
+/// Reads the decrypted content of an encrypted file, and fails with an
+/// `InvalidData` io error if the file was altered
+pub struct Decryptor<R> {
+  chunks: Chunks<R>,
+
+  /// Taken once the last chunk is decrypted
+  decryptor: Option<DecryptorBE32<XChaCha20Poly1305>>,
+  plaintext: Vec<u8>,
+  position: usize,
+}
+
+impl<R: Read> Decryptor<R> {
+  /// Decrypt the content that follows the `header` in the `reader`
+  fn new(reader: R, header: &Header) -> Result<Self> {
+    let keyring = keyring()?.ok_or(StorageError::KeyringNotSet)?;
+    let (data_key, _) = keyring.unwrap(header)?;
+
+    Ok(Self {
+      chunks: Chunks::new(reader, CHUNK_SIZE + TAG_LEN),
+      decryptor: Some(DecryptorBE32::from_aead(
+        XChaCha20Poly1305::new(&data_key),
+        header.stream_nonce.as_slice().into(),
+      )),
+      plaintext: Vec::new(),
+      position: 0,
+    })
+  }
+}
+
+impl<R: Read> Read for Decryptor<R> {
+  fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+    while self.position == self.plaintext.len() {
+      let Some(mut decryptor) = self.decryptor.take() else {
+        return Ok(0);
+      };
+
+      let plaintext = match self.chunks.fill()? {
+        true => decryptor.decrypt_last(self.chunks.chunk()),
+        false => {
+          let plaintext = decryptor.decrypt_next(self.chunks.chunk());
+          self.decryptor = Some(decryptor);
+          plaintext
+        }
+      };
+
+      self.plaintext = plaintext
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "altered content"))?;
+      self.position = 0;
+      self.chunks.consume();
+    }
+
+    let n = buf.len().min(self.plaintext.len() - self.position);
+    buf[..n].copy_from_slice(&self.plaintext[self.position..self.position + n]);
+    self.position += n;
+
+    Ok(n)
+  }
+}
+
+/// Splits what a reader reads into chunks of a given size, and tells which
+/// one is the last
+struct Chunks<R> {
+  reader: R,
+  size: usize,
+
+  /// Holds the next chunk, and the first byte of the one after if there is one
+  buffer: Vec<u8>,
+}
+
+impl<R: Read> Chunks<R> {
+  fn new(reader: R, size: usize) -> Self {
+    Self {
+      reader,
+      size,
+      buffer: Vec::with_capacity(size + 1),
+    }
+  }
+
+  /// Read the next chunk, and return whether it is the last one
+  fn fill(&mut self) -> std::io::Result<bool> {
+    let missing = self.size + 1 - self.buffer.len();
+    self
+      .reader
+      .by_ref()
+      .take(missing as u64)
+      .read_to_end(&mut self.buffer)?;
+
+    Ok(self.buffer.len() <= self.size)
+  }
+
+  fn chunk(&self) -> &[u8] {
+    &self.buffer[..self.buffer.len().min(self.size)]
+  }
+
+  fn consume(&mut self) {
+    let len = self.buffer.len().min(self.size);
+    self.buffer.drain(..len);
+  }
+}
+
+/// The content of an item, decrypted as it is read if it is stored encrypted
+pub enum Content {
+  /// Stored as is, the file can be served as it is
+  Plain(std::fs::File),
+  Encrypted(Decryptor<std::fs::File>),
+}
+
+impl Content {
+  pub(crate) fn new(mut file: std::fs::File) -> Result<Self> {
+    match Header::read(&mut file)? {
+      Some(header) => Ok(Self::Encrypted(Decryptor::new(file, &header)?)),
+      None => {
+        file.rewind()?;
+
+        Ok(Self::Plain(file))
+      }
+    }
+  }
+
+  /// The file the content is read from, as it is stored
+  pub fn file(&self) -> &std::fs::File {
+    match self {
+      Content::Plain(file) => file,
+      Content::Encrypted(decryptor) => &decryptor.chunks.reader,
+    }
+  }
+
+  /// The number of bytes of the content once decrypted
+  pub fn size(&self) -> Result<u64> {
+    match self {
+      Content::Plain(file) => Ok(file.metadata()?.len()),
+      Content::Encrypted(decryptor) => {
+        Ok(decrypted_size(decryptor.chunks.reader.metadata()?.len()))
+      }
+    }
+  }
+}
+
+/// The number of bytes of the content of the file at `path` once decrypted,
+/// that is found without a keyring since nothing is decrypted
+pub(crate) fn plaintext_size(path: &std::path::Path) -> Result<u64> {
+  let mut file = std::fs::File::open(path)?;
+  let len = file.metadata()?.len();
+
+  match Header::read(&mut file)? {
+    Some(_) => Ok(decrypted_size(len)),
+    None => Ok(len),
+  }
+}
+
+/// The size of the content of an encrypted file of `len` bytes
+fn decrypted_size(len: u64) -> u64 {
+  let ciphertext = len.saturating_sub(HEADER_LEN as u64);
+  let chunks = ciphertext.div_ceil((CHUNK_SIZE + TAG_LEN) as u64).max(1);
+
+  ciphertext.saturating_sub(chunks * TAG_LEN as u64)
+}
+
+impl Read for Content {
+  fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+    match self {
+      Content::Plain(file) => file.read(buf),
+      Content::Encrypted(decryptor) => decryptor.read(buf),
+    }
+  }
+}
+
+/// Wrap the data keys of every encrypted file under the `root` with the
+/// current key of the keyring, and return the number of rewrapped keys. Only
+/// the headers of the files are rewritten, their content is left untouched.
+/// The versions and the derivatives of the items are rewrapped too, and the
+/// metadata of the index is sealed again with the current key.
+///
+/// The files are rewritten in place, so no server should be using the `root`
+/// while its keys are rotated. The previous keys can be removed from the
+/// keyring once it is done.
+///
+/// ```rs
+/// storage::set_keyring(Some(storage::Keyring::new(new_key).with_previous(old_key)))?;
+/// storage::rotate_keys("buckets")?;
+/// ```
+pub fn rotate_keys(root: impl AsRef<std::path::Path>) -> Result<usize> {
+  let keyring = keyring()?.ok_or(StorageError::KeyringNotSet)?;
+  let root = root.as_ref();
+  let rotated = rotate_keys_in(&keyring, root)?;
+
+  // the metadata sealed in the index is sealed again from the rewrapped files
+  Index::reseal(root)?;
+
+  Ok(rotated)
+}
+
+fn rotate_keys_in(keyring: &Keyring, folder: &std::path::Path) -> Result<usize> {
+  let mut rotated = 0;
+
+  for entry in std::fs::read_dir(folder)? {
+    let entry = entry?;
+    let file_type = entry.file_type()?;
+
+    if file_type.is_dir() {
+      rotated += rotate_keys_in(keyring, &entry.path())?;
+      continue;
+    }
+
+    if !file_type.is_file() {
+      continue;
+    }
+
+    let mut file = std::fs::File::options()
+      .read(true)
+      .write(true)
+      .open(entry.path())?;
+    let Some(mut header) = Header::read(&mut file)? else {
+      continue;
+    };
+
+    let (data_key, is_current) = keyring.unwrap(&header)?;
+    if is_current {
+      continue;
+    }
+
+    keyring.wrap(&mut header, &data_key)?;
+    file.rewind()?;
+    file.write_all(&header.to_bytes())?;
+    file.sync_data()?;
+
+    rotated += 1;
+  }
+
+  Ok(rotated)
+}
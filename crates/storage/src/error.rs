@@ -6,11 +6,60 @@ pub enum StorageError {
   ConfigAlreadySet,
   Io(std::io::Error),
   Serde(serde_yaml::Error),
+
+  /// A metadata file couldn't be written or read in its [crate::MetadataFormat]
+  MetadataEncoding(Box<dyn std::error::Error + Send + Sync>),
+  UnknownMetadataFormat,
   PoisonError,
+
+  /// A blocking operation of the [crate::nonblocking] API panicked or was
+  /// cancelled
+  BlockingTaskFailed,
+
+  /// The metadata index couldn't be read or updated
+  Index(rusqlite::Error),
+
+  /// The [crate::Query] cannot be run, the reason is given
+  InvalidQuery(&'static str),
   Custom(&'static str),
 
-  ReadMissingBucket,
-  ReadMissingItem,
+  /// The storage path has no bucket name before its `/`
+  MissingBucketName,
+
+  /// The storage path has no item name after its `/`
+  MissingItemName,
+
+  /// The storage path has more than a bucket and an item segment
+  TrailingPathSegments,
+
+  /// The name doesn't fit the allowed charset of [crate::BucketName]
+  InvalidBucketName,
+
+  /// The name doesn't fit the allowed charset of [crate::ItemName]
+  InvalidItemName,
+
+  BucketNotFound,
+  BucketAlreadyExists,
+  BucketNotEmpty,
+
+  /// The write would exceed the `max_size` of the bucket
+  BucketFull,
+
+  /// An encrypted file was read while no [crate::Keyring] is set
+  KeyringNotSet,
+
+  /// None of the keys of the [crate::Keyring] wrapped the data key of the
+  /// encrypted file
+  UnknownDataKey,
+
+  /// The master key isn't 32 bytes encoded as 64 hex characters
+  InvalidMasterKey,
+}
+
+impl StorageError {
+  pub(crate) fn metadata(error: impl std::error::Error + Send + Sync + 'static) -> Self {
+    Self::MetadataEncoding(Box::new(error))
+  }
 }
 
 impl From<std::io::Error> for StorageError {
@@ -25,6 +74,32 @@ impl From<serde_yaml::Error> for StorageError {
   }
 }
 
+impl From<shcs_types::ParseError> for StorageError {
+  fn from(value: shcs_types::ParseError) -> Self {
+    match value {
+      shcs_types::ParseError::MissingBucketName => Self::MissingBucketName,
+      shcs_types::ParseError::MissingItemName => Self::MissingItemName,
+      shcs_types::ParseError::TrailingPathSegments => Self::TrailingPathSegments,
+      shcs_types::ParseError::InvalidBucketName => Self::InvalidBucketName,
+      shcs_types::ParseError::InvalidItemName => Self::InvalidItemName,
+      shcs_types::ParseError::InvalidQueryField => Self::InvalidQuery("invalid metadata path"),
+    }
+  }
+}
+
+impl From<rusqlite::Error> for StorageError {
+  fn from(value: rusqlite::Error) -> Self {
+    Self::Index(value)
+  }
+}
+
+#[cfg(feature = "async")]
+impl From<tokio::task::JoinError> for StorageError {
+  fn from(_: tokio::task::JoinError) -> Self {
+    Self::BlockingTaskFailed
+  }
+}
+
 impl<T> From<std::sync::PoisonError<T>> for StorageError {
   fn from(_: std::sync::PoisonError<T>) -> Self {
     Self::PoisonError
@@ -38,9 +113,38 @@ impl std::fmt::Display for StorageError {
       StorageError::ConfigAlreadySet => write!(f, "config already set"),
       StorageError::Io(io) => write!(f, "io error: {io}"),
       StorageError::Serde(e) => write!(f, "serde error: {e}"),
+      StorageError::MetadataEncoding(e) => write!(f, "metadata encoding error: {e}"),
+      StorageError::UnknownMetadataFormat => {
+        write!(
+          f,
+          "unknown metadata format, expected yaml, json, messagepack or cbor"
+        )
+      }
       StorageError::PoisonError => write!(f, "rwlock poison error"),
-      StorageError::ReadMissingBucket => write!(f, "read failure, missing bucket name"),
-      StorageError::ReadMissingItem => write!(f, "read failure, missing item name"),
+      StorageError::BlockingTaskFailed => write!(f, "blocking task failed"),
+      StorageError::Index(e) => write!(f, "metadata index error: {e}"),
+      StorageError::InvalidQuery(reason) => write!(f, "invalid query: {reason}"),
+      StorageError::MissingBucketName => write!(f, "invalid storage path, missing bucket name"),
+      StorageError::MissingItemName => write!(f, "invalid storage path, missing item name"),
+      StorageError::TrailingPathSegments => {
+        write!(
+          f,
+          "invalid storage path, unexpected segments after the item name"
+        )
+      }
+      StorageError::InvalidBucketName => write!(f, "invalid bucket name"),
+      StorageError::InvalidItemName => write!(f, "invalid item name"),
+      StorageError::BucketNotFound => write!(f, "bucket not found"),
+      StorageError::BucketAlreadyExists => write!(f, "bucket already exists"),
+      StorageError::BucketNotEmpty => write!(f, "bucket is not empty"),
+      StorageError::BucketFull => write!(f, "bucket max size exceeded"),
+      StorageError::KeyringNotSet => write!(f, "the file is encrypted but no keyring is set"),
+      StorageError::UnknownDataKey => {
+        write!(f, "no master key of the keyring can decrypt the file")
+      }
+      StorageError::InvalidMasterKey => {
+        write!(f, "invalid master key, expected 64 hex characters")
+      }
       StorageError::Custom(s) => write!(f, "{s}"),
     }
   }
@@ -0,0 +1,382 @@
+use crate::*;
+
+/// An SQLite database in the root that mirrors the items and their metadata,
+/// so that they can be looked up with a [Query] instead of reading every
+/// metadata file. It is kept in sync by the storage functions, and rebuilt from
+/// the buckets when it is missing.
+///
+/// The metadata is sealed with the [Keyring] when one is set, like the metadata
+/// files are, and unsealed by the `unseal` SQL function the queries call. The
+/// size of an item is the `system.size` of its metadata when it records one,
+/// like the decompressed size of a compressed item, or the size of its
+/// decrypted content.
+pub(crate) struct Index {
+  connection: std::sync::Mutex<rusqlite::Connection>,
+}
+
+impl Index {
+  const FILENAME: &'static str = ".index.sqlite";
+
+  fn path(root: &std::path::Path) -> std::path::PathBuf {
+    root.join(Self::FILENAME)
+  }
+
+  /// Open the index of the `root`, or create it from the current content of
+  /// the buckets if it doesn't exist yet. It is rebuilt too when a keyring is
+  /// set and it holds metadata that isn't sealed.
+  pub(crate) fn open(root: &std::path::Path) -> Result<Self> {
+    let is_new = !Self::path(root).exists();
+    let index = Self::connect(root)?;
+
+    if is_new || (keyring()?.is_some() && index.has_unsealed_metadata()?) {
+      index.rebuild(root)?;
+    }
+
+    Ok(index)
+  }
+
+  /// Rebuild the index of the `root` if it exists, to seal its metadata with
+  /// the current key of the keyring
+  pub(crate) fn reseal(root: &std::path::Path) -> Result<()> {
+    if Self::path(root).exists() {
+      Self::connect(root)?.rebuild(root)?;
+    }
+
+    Ok(())
+  }
+
+  fn connect(root: &std::path::Path) -> Result<Self> {
+    let connection = rusqlite::Connection::open(Self::path(root))?;
+
+    // the replaced metadata is overwritten rather than left in the free pages
+    connection.execute_batch(
+      "PRAGMA secure_delete = ON;
+      CREATE TABLE IF NOT EXISTS items (
+        bucket TEXT NOT NULL,
+        item TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        uploaded_at INTEGER NOT NULL,
+        metadata TEXT,
+        PRIMARY KEY (bucket, item)
+      );
+      CREATE INDEX IF NOT EXISTS items_size ON items (size);
+      CREATE INDEX IF NOT EXISTS items_uploaded_at ON items (uploaded_at);",
+    )?;
+
+    connection.create_scalar_function(
+      "unseal",
+      1,
+      rusqlite::functions::FunctionFlags::SQLITE_UTF8,
+      |context| {
+        unsealed_metadata(context.get_raw(0))
+          .map_err(|e| rusqlite::Error::UserFunctionError(Box::new(e)))
+      },
+    )?;
+
+    Ok(Self {
+      connection: std::sync::Mutex::new(connection),
+    })
+  }
+
+  /// Whether some metadata was indexed in clear, before a keyring was set
+  fn has_unsealed_metadata(&self) -> Result<bool> {
+    let unsealed = self.connection.lock()?.query_row(
+      "SELECT EXISTS (SELECT 1 FROM items WHERE typeof(metadata) = 'text')",
+      (),
+      |row| row.get(0),
+    )?;
+
+    Ok(unsealed)
+  }
+
+  /// Replace the content of the index with the items currently stored in the
+  /// buckets, returns the number of indexed items
+  pub(crate) fn rebuild(&self, root: &std::path::Path) -> Result<usize> {
+    let mut connection = self.connection.lock()?;
+    let transaction = connection.transaction()?;
+    let mut indexed = 0;
+
+    transaction.execute("DELETE FROM items", ())?;
+
+    for entry in std::fs::read_dir(root)? {
+      let entry = entry?;
+      let Ok(bucket) = BucketName::new(entry.file_name().to_string_lossy()) else {
+        continue;
+      };
+
+      if !entry.file_type()?.is_dir() {
+        continue;
+      }
+
+      for item in Bucket::items(root, &bucket)? {
+        let Ok(item) = ItemName::new(item) else {
+          continue;
+        };
+
+        let path = Item::path(root, &bucket, &item);
+        let modified = std::fs::metadata(&path)?.modified()?;
+        let metadata: Option<serde_json::Value> = Metadata::read(root, &bucket, &item)?;
+        let size = match recorded_size(metadata.as_ref()) {
+          Some(size) => size,
+          None => plaintext_size(&path)?,
+        };
+
+        transaction.execute(
+          "INSERT INTO items (bucket, item, size, uploaded_at, metadata)
+          VALUES (?1, ?2, ?3, ?4, ?5)",
+          (
+            bucket.as_str(),
+            item.as_str(),
+            size,
+            unix_timestamp(modified),
+            sealed_metadata(metadata.as_ref())?,
+          ),
+        )?;
+
+        indexed += 1;
+      }
+    }
+
+    transaction.commit()?;
+
+    Ok(indexed)
+  }
+
+  /// Record the new content of the item, of `size` bytes once decrypted, its
+  /// metadata is left untouched like its metadata file is
+  pub(crate) fn insert_item(&self, storage_path: &StoragePath, size: u64) -> Result<()> {
+    self.connection.lock()?.execute(
+      "INSERT INTO items (bucket, item, size, uploaded_at) VALUES (?1, ?2, ?3, ?4)
+      ON CONFLICT (bucket, item) DO UPDATE SET
+        size = excluded.size,
+        uploaded_at = excluded.uploaded_at",
+      (
+        storage_path.bucket.as_str(),
+        storage_path.item.as_str(),
+        size,
+        unix_timestamp(std::time::SystemTime::now()),
+      ),
+    )?;
+
+    Ok(())
+  }
+
+  /// Record the new metadata of the item, that is indexed from its file at
+  /// `path` if it wasn't yet
+  pub(crate) fn set_metadata(
+    &self, storage_path: &StoragePath, path: &std::path::Path, metadata: Option<&serde_json::Value>,
+  ) -> Result<()> {
+    let modified = std::fs::metadata(path)?.modified()?;
+    let recorded = recorded_size(metadata);
+    let size = match recorded {
+      Some(size) => size,
+      None => plaintext_size(path)?,
+    };
+
+    self.connection.lock()?.execute(
+      "INSERT INTO items (bucket, item, size, uploaded_at, metadata) VALUES (?1, ?2, ?3, ?4, ?5)
+      ON CONFLICT (bucket, item) DO UPDATE SET
+        metadata = excluded.metadata,
+        size = COALESCE(?6, size)",
+      (
+        storage_path.bucket.as_str(),
+        storage_path.item.as_str(),
+        size,
+        unix_timestamp(modified),
+        sealed_metadata(metadata)?,
+        recorded,
+      ),
+    )?;
+
+    Ok(())
+  }
+
+  pub(crate) fn remove(&self, storage_path: &StoragePath) -> Result<()> {
+    self.connection.lock()?.execute(
+      "DELETE FROM items WHERE bucket = ?1 AND item = ?2",
+      (storage_path.bucket.as_str(), storage_path.item.as_str()),
+    )?;
+
+    Ok(())
+  }
+
+  /// Move the row of the item at `from` to `to`, replacing the row of the
+  /// item that was there
+  pub(crate) fn rename(&self, from: &StoragePath, to: &StoragePath) -> Result<()> {
+    let mut connection = self.connection.lock()?;
+    let transaction = connection.transaction()?;
+
+    transaction.execute(
+      "DELETE FROM items WHERE bucket = ?1 AND item = ?2",
+      (to.bucket.as_str(), to.item.as_str()),
+    )?;
+    transaction.execute(
+      "UPDATE items SET bucket = ?3, item = ?4 WHERE bucket = ?1 AND item = ?2",
+      (
+        from.bucket.as_str(),
+        from.item.as_str(),
+        to.bucket.as_str(),
+        to.item.as_str(),
+      ),
+    )?;
+
+    transaction.commit()?;
+
+    Ok(())
+  }
+
+  pub(crate) fn remove_bucket(&self, bucket: &BucketName) -> Result<()> {
+    self
+      .connection
+      .lock()?
+      .execute("DELETE FROM items WHERE bucket = ?1", (bucket.as_str(),))?;
+
+    Ok(())
+  }
+
+  pub(crate) fn rename_bucket(&self, from: &BucketName, to: &BucketName) -> Result<()> {
+    self.connection.lock()?.execute(
+      "UPDATE items SET bucket = ?2 WHERE bucket = ?1",
+      (from.as_str(), to.as_str()),
+    )?;
+
+    Ok(())
+  }
+
+  /// Run the `query` and return the page of items it selects
+  pub(crate) fn query(&self, query: &Query) -> Result<QueryPage> {
+    let mut sql =
+      String::from("SELECT bucket, item, size, uploaded_at, metadata FROM items WHERE 1");
+    let mut params: Vec<rusqlite::types::Value> = Vec::new();
+
+    if let Some(bucket) = &query.bucket {
+      params.push(bucket.to_string().into());
+      sql.push_str(&format!(" AND bucket = ?{}", params.len()));
+    }
+
+    if let Some(after) = &query.after {
+      params.push(after.bucket.to_string().into());
+      params.push(after.item.to_string().into());
+      sql.push_str(&format!(
+        " AND (bucket, item) > (?{}, ?{})",
+        params.len() - 1,
+        params.len()
+      ));
+    }
+
+    for filter in &query.filters {
+      let column = match &filter.field {
+        QueryField::Bucket => "bucket".to_owned(),
+        QueryField::Item => "item".to_owned(),
+        QueryField::Size => "size".to_owned(),
+        QueryField::UploadedAt => "uploaded_at".to_owned(),
+        QueryField::Metadata(keys) => {
+          params.push(crate::query::json_path(keys).into());
+          format!("json_extract(unseal(metadata), ?{})", params.len())
+        }
+      };
+
+      params.push(crate::query::sql_value(filter)?);
+      sql.push_str(&format!(
+        " AND {column} {} ?{}",
+        crate::query::sql_operator(filter.op),
+        params.len()
+      ));
+    }
+
+    let limit = crate::query::limit(query);
+    params.push((limit as i64 + 1).into());
+    sql.push_str(&format!(" ORDER BY bucket, item LIMIT ?{}", params.len()));
+
+    let connection = self.connection.lock()?;
+    let mut statement = connection.prepare(&sql)?;
+    let mut rows = statement.query(rusqlite::params_from_iter(params))?;
+    let mut items = Vec::new();
+
+    while let Some(row) = rows.next()? {
+      let bucket: String = row.get(0)?;
+      let item: String = row.get(1)?;
+      let metadata = unsealed_metadata(row.get_ref(4)?)?;
+
+      items.push(QueryHit {
+        path: StoragePath::new(BucketName::new(bucket)?, ItemName::new(item)?),
+        size: row.get(2)?,
+        uploaded_at: row.get(3)?,
+        metadata: metadata
+          .map(|m| serde_json::from_str(&m))
+          .transpose()
+          .map_err(StorageError::metadata)?,
+      });
+    }
+
+    // one more row than the limit is selected to know if there is a next page
+    let next = match items.len() > limit {
+      true => {
+        items.truncate(limit);
+        items.last().map(|hit| hit.path.clone())
+      }
+      false => None,
+    };
+
+    Ok(QueryPage { items, next })
+  }
+}
+
+/// The `system.size` recorded in the `metadata` of an item, if any
+fn recorded_size(metadata: Option<&serde_json::Value>) -> Option<u64> {
+  metadata?.pointer("/system/size")?.as_u64()
+}
+
+/// The `metadata` as it is stored in the index: sealed if a keyring is set, or
+/// as JSON text otherwise
+fn sealed_metadata(metadata: Option<&serde_json::Value>) -> Result<rusqlite::types::Value> {
+  let Some(metadata) = metadata else {
+    return Ok(rusqlite::types::Value::Null);
+  };
+
+  let json = metadata.to_string();
+
+  match keyring()?.is_some() {
+    true => Ok(rusqlite::types::Value::Blob(
+      seal(json.as_bytes())?.into_owned(),
+    )),
+    false => Ok(rusqlite::types::Value::Text(json)),
+  }
+}
+
+/// The JSON text of the metadata stored in the index, sealed or not
+fn unsealed_metadata(value: rusqlite::types::ValueRef<'_>) -> Result<Option<String>> {
+  use rusqlite::types::ValueRef;
+
+  let bytes = match value {
+    ValueRef::Null => return Ok(None),
+    ValueRef::Text(text) => text.to_vec(),
+    ValueRef::Blob(sealed) => unseal(sealed.to_vec())?,
+    ValueRef::Integer(_) | ValueRef::Real(_) => {
+      return Err(StorageError::metadata(std::io::Error::other(
+        "the indexed metadata isn't JSON",
+      )))
+    }
+  };
+
+  String::from_utf8(bytes)
+    .map(Some)
+    .map_err(StorageError::metadata)
+}
+
+/// The number of seconds between the unix epoch and the `time`
+pub(crate) fn unix_timestamp(time: std::time::SystemTime) -> i64 {
+  match time.duration_since(std::time::UNIX_EPOCH) {
+    Ok(duration) => duration.as_secs() as i64,
+    Err(e) => -(e.duration().as_secs() as i64),
+  }
+}
+
+/// Rebuild the metadata index from the content of the buckets, for when the
+/// files of the root were modified without going through the storage
+/// functions. Returns the number of indexed items.
+pub fn rebuild_index() -> Result<usize> {
+  let config = config()?;
+
+  config.index.rebuild(&config.root)
+}
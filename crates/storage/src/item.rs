@@ -3,12 +3,12 @@ use crate::*;
 pub struct Item;
 
 impl Item {
-  pub fn path(root: &std::path::PathBuf, bucket: &str, name: &str) -> std::path::PathBuf {
+  pub fn path(root: &std::path::Path, bucket: &str, name: &str) -> std::path::PathBuf {
     Bucket::path(root, bucket).join(name)
   }
 
   pub fn file(
-    root: &std::path::PathBuf, bucket: &str, name: &str,
+    root: &std::path::Path, bucket: &str, name: &str,
   ) -> Result<(std::fs::File, std::path::PathBuf)> {
     let path = Self::path(root, bucket, name);
     let file = std::fs::File::open(&path)?;
@@ -16,24 +16,107 @@ impl Item {
     Ok((file, path))
   }
 
-  pub fn write(root: &std::path::PathBuf, bucket: &str, name: &str, content: &str) -> Result<()> {
-    std::fs::write(Self::path(root, bucket, name), content)?;
+  pub fn write(root: &std::path::Path, bucket: &str, name: &str, content: &str) -> Result<()> {
+    std::fs::write(Self::path(root, bucket, name), seal(content.as_bytes())?)?;
 
     Ok(())
   }
 
-  pub fn exists(root: &std::path::PathBuf, bucket: &str, name: &str) -> bool {
+  pub fn exists(root: &std::path::Path, bucket: &str, name: &str) -> bool {
     Self::path(root, bucket, name).exists()
   }
 
-  pub fn remove(root: &std::path::PathBuf, bucket: &str, name: &str) -> Result<()> {
+  pub fn remove(root: &std::path::Path, bucket: &str, name: &str) -> Result<()> {
     std::fs::remove_file(Self::path(root, bucket, name))?;
 
     Ok(())
   }
 
+  /// Copy the content of the item, as a reflink when the filesystem supports
+  /// them so that the copy shares the blocks of the source until either is
+  /// written
+  pub fn copy(
+    root: &std::path::Path, from_bucket: &str, from_name: &str, to_bucket: &str, to_name: &str,
+  ) -> Result<()> {
+    let to = Self::path(root, to_bucket, to_name);
+
+    // reflinks fail on existing destinations
+    if to.exists() {
+      std::fs::remove_file(&to)?;
+    }
+
+    reflink_copy::reflink_or_copy(Self::path(root, from_bucket, from_name), to)?;
+
+    Ok(())
+  }
+
+  pub fn rename(
+    root: &std::path::Path, from_bucket: &str, from_name: &str, to_bucket: &str, to_name: &str,
+  ) -> Result<()> {
+    std::fs::rename(
+      Self::path(root, from_bucket, from_name),
+      Self::path(root, to_bucket, to_name),
+    )?;
+
+    Ok(())
+  }
+
+  /// The hidden folder of the bucket where the derivatives of the item are
+  /// cached: `.derivatives/{name}`
+  pub fn derivatives_path(root: &std::path::Path, bucket: &str, name: &str) -> std::path::PathBuf {
+    Bucket::path(root, bucket).join(".derivatives").join(name)
+  }
+
+  /// Remove the cached derivatives of the item, they no longer match its
+  /// content
+  pub fn remove_derivatives(root: &std::path::Path, bucket: &str, name: &str) -> Result<()> {
+    match std::fs::remove_dir_all(Self::derivatives_path(root, bucket, name)) {
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+      removal => Ok(removal?),
+    }
+  }
+
+  /// Compress the content of the `tempfile` into a new tempfile next to it
+  pub fn compress(
+    tempfile: &tempfile::NamedTempFile, encoding: Encoding,
+  ) -> Result<tempfile::NamedTempFile> {
+    let compressed = Self::sibling_tempfile(tempfile)?;
+
+    encoding.encode(
+      &mut std::io::BufReader::new(tempfile.reopen()?),
+      std::io::BufWriter::new(compressed.as_file()),
+    )?;
+
+    Ok(compressed)
+  }
+
+  /// Encrypt the content of the `tempfile` into a new tempfile next to it
+  pub fn encrypt(
+    tempfile: &tempfile::NamedTempFile, keyring: &Keyring,
+  ) -> Result<tempfile::NamedTempFile> {
+    let encrypted = Self::sibling_tempfile(tempfile)?;
+
+    let mut writer = std::io::BufWriter::new(encrypted.as_file());
+    encrypt(keyring, tempfile.reopen()?, &mut writer)?;
+    std::io::Write::flush(&mut writer)?;
+    drop(writer);
+
+    Ok(encrypted)
+  }
+
+  /// A new tempfile in the folder of the `tempfile`, so that it is persisted
+  /// without crossing a filesystem boundary
+  fn sibling_tempfile(tempfile: &tempfile::NamedTempFile) -> Result<tempfile::NamedTempFile> {
+    let folder = tempfile
+      .path()
+      .parent()
+      .unwrap_or_else(|| std::path::Path::new("."));
+
+    Ok(tempfile::NamedTempFile::new_in(folder)?)
+  }
+
   pub fn persist_tempfile(
-    root: &std::path::PathBuf, bucket: &str, name: &str, tempfile: tempfile::NamedTempFile,
+    root: &std::path::Path, bucket: &str, name: &str, tempfile: tempfile::NamedTempFile,
   ) -> Result<()> {
     tempfile
       .persist(Self::path(root, bucket, name))
@@ -42,7 +125,102 @@ impl Item {
     Ok(())
   }
 
-  pub fn to_string(bucket: &str, name: &str) -> String {
-    format!("{bucket}/{name}")
+  /// Move the current content of the item and its metadata to the hidden
+  /// versions folder of the bucket: `.versions/{name}/{timestamp}`, and return
+  /// the name of the version
+  pub fn archive_version(root: &std::path::Path, bucket: &str, name: &str) -> Result<String> {
+    let folder = Self::versions_path(root, bucket, name);
+    std::fs::create_dir_all(&folder)?;
+
+    let version = chrono::Utc::now().format("%Y%m%dT%H%M%S%.9fZ").to_string();
+    std::fs::rename(Self::path(root, bucket, name), folder.join(&version))?;
+
+    if let Some((path, format)) = Metadata::find(root, bucket, name) {
+      std::fs::rename(
+        path,
+        folder.join(Metadata::metadata_filename(&version, format)),
+      )?;
+    }
+
+    Ok(version)
+  }
+
+  /// Move the archived `version` of the item and its metadata back in place,
+  /// the opposite of [Item::archive_version]
+  pub fn restore_version(
+    root: &std::path::Path, bucket: &str, name: &str, version: &str,
+  ) -> Result<()> {
+    let folder = Self::versions_path(root, bucket, name);
+
+    std::fs::rename(folder.join(version), Self::path(root, bucket, name))?;
+
+    for format in MetadataFormat::ALL {
+      let path = folder.join(Metadata::metadata_filename(version, format));
+
+      if path.exists() {
+        std::fs::rename(path, Metadata::path(root, bucket, name, format))?;
+      }
+    }
+
+    Ok(())
+  }
+
+  /// The folder of the previous versions of the item, in the versions folder
+  /// of its bucket
+  pub fn versions_path(root: &std::path::Path, bucket: &str, name: &str) -> std::path::PathBuf {
+    Bucket::versions_path(root, bucket).join(name)
+  }
+
+  /// Returns the archived versions of the item, the oldest first
+  pub fn versions(root: &std::path::Path, bucket: &str, name: &str) -> Result<Vec<String>> {
+    let entries = match std::fs::read_dir(Self::versions_path(root, bucket, name)) {
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+      entries => entries?,
+    };
+    let mut versions = Vec::new();
+
+    for entry in entries {
+      let filename = entry?.file_name().to_string_lossy().into_owned();
+
+      if !filename.starts_with('.') && !Metadata::is_metadata_filename(&filename) {
+        versions.push(filename);
+      }
+    }
+
+    // the versions are named after the time they were archived at
+    versions.sort();
+
+    Ok(versions)
+  }
+
+  /// Remove the oldest archived versions of the item past the `keep` newest
+  /// ones, with their metadata
+  pub fn prune_versions(
+    root: &std::path::Path, bucket: &str, name: &str, keep: usize,
+  ) -> Result<()> {
+    let folder = Self::versions_path(root, bucket, name);
+    let versions = Self::versions(root, bucket, name)?;
+
+    for version in &versions[..versions.len().saturating_sub(keep)] {
+      std::fs::remove_file(folder.join(version))?;
+
+      for format in MetadataFormat::ALL {
+        let path = folder.join(Metadata::metadata_filename(version, format));
+
+        if path.exists() {
+          std::fs::remove_file(path)?;
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Remove every archived version of the item
+  pub fn remove_versions(root: &std::path::Path, bucket: &str, name: &str) -> Result<()> {
+    match std::fs::remove_dir_all(Self::versions_path(root, bucket, name)) {
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+      removal => Ok(removal?),
+    }
   }
 }
@@ -1,12 +1,37 @@
+pub use shcs_types::BucketName;
+pub use shcs_types::ItemName;
+pub use shcs_types::ParseError;
+pub use shcs_types::StoragePath;
+
 mod bucket;
 pub(crate) use bucket::*;
 
 mod item;
 pub(crate) use item::*;
 
+mod bucket_settings;
+pub use bucket_settings::*;
+
+mod encoding;
+pub use encoding::*;
+
+mod encryption;
+pub use encryption::*;
+
+pub mod buckets;
+
 mod metadata;
 pub(crate) use metadata::*;
 
+mod metadata_format;
+pub use metadata_format::*;
+
+mod index;
+pub(crate) use index::*;
+
+mod query;
+pub use query::*;
+
 mod storage;
 
 mod error;
@@ -15,22 +40,45 @@ pub use error::*;
 mod config;
 pub(crate) use config::*;
 
+mod lock;
+pub(crate) use lock::*;
+
+mod policy;
+pub use policy::*;
+
 mod dotfile;
 pub(crate) use dotfile::*;
 
-pub(crate) mod constants;
+pub mod constants;
+
+#[cfg(feature = "async")]
+pub mod nonblocking;
 
 #[cfg(test)]
 mod tests;
 
 pub use crate::config::initialize;
+pub use crate::config::initialize_with_policy;
+pub use crate::config::set_bucket_policy;
+pub use crate::config::set_keyring;
+pub use crate::config::set_metadata_format;
+pub use crate::index::rebuild_index;
+pub use crate::storage::compress_tempfile;
+pub use crate::storage::copy;
 pub use crate::storage::deserialize_metadata;
 pub use crate::storage::exists;
+pub use crate::storage::open;
 pub use crate::storage::persist_tempfile;
+pub use crate::storage::persist_tempfile_in;
 pub use crate::storage::read;
+pub use crate::storage::read_derivative;
 pub use crate::storage::read_metadata;
 pub use crate::storage::remove;
+pub use crate::storage::rename;
 pub use crate::storage::replace_tempfile;
+pub use crate::storage::update_metadata;
 pub use crate::storage::write;
+pub use crate::storage::write_derivative;
 
 pub use crate::storage::internal;
+pub use crate::storage::versions;
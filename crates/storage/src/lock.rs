@@ -0,0 +1,44 @@
+use crate::*;
+
+/// A lock per key, like a bucket or an item name, so that the operations that
+/// must run one at a time on a key don't wait for the ones on the other keys
+#[derive(Default)]
+pub(crate) struct KeyedLock {
+  held: std::sync::Mutex<std::collections::HashSet<String>>,
+  released: std::sync::Condvar,
+}
+
+/// Releases the key of a [KeyedLock] when dropped
+pub(crate) struct KeyedLockGuard<'a> {
+  lock: &'a KeyedLock,
+  key: String,
+}
+
+impl KeyedLock {
+  /// Wait until no one else holds the `key` and hold it
+  pub(crate) fn lock(&self, key: impl Into<String>) -> Result<KeyedLockGuard<'_>> {
+    let key = key.into();
+    let mut held = self.held.lock()?;
+
+    while held.contains(&key) {
+      held = self.released.wait(held)?;
+    }
+
+    held.insert(key.clone());
+
+    Ok(KeyedLockGuard { lock: self, key })
+  }
+}
+
+impl Drop for KeyedLockGuard<'_> {
+  fn drop(&mut self) {
+    let mut held = self
+      .lock
+      .held
+      .lock()
+      .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    held.remove(&self.key);
+    self.lock.released.notify_all();
+  }
+}
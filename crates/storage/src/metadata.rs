@@ -3,48 +3,124 @@ use crate::*;
 pub struct Metadata;
 
 impl Metadata {
-  fn metadata_filename(name: &str) -> String {
-    format!("{name}.metadata.yaml")
+  pub(crate) fn metadata_filename(name: &str, format: MetadataFormat) -> String {
+    format!("{name}{}", format.extension())
   }
 
-  pub fn path(root: &std::path::PathBuf, bucket: &str, name: &str) -> std::path::PathBuf {
-    let item_path = Item::path(root, bucket, name);
-    let item_filename = item_path
-      .file_name()
-      .unwrap_or_default()
-      .to_str()
-      .unwrap_or_default();
+  /// Returns whether the `filename` is the one of a metadata file, in any
+  /// format
+  pub(crate) fn is_metadata_filename(filename: &str) -> bool {
+    MetadataFormat::from_filename(filename).is_some()
+  }
 
-    let metadata_filename = Self::metadata_filename(item_filename);
+  /// The path of the metadata file of the item when written in `format`
+  pub fn path(
+    root: &std::path::Path, bucket: &str, name: &str, format: MetadataFormat,
+  ) -> std::path::PathBuf {
+    Bucket::path(root, bucket).join(Self::metadata_filename(name, format))
+  }
 
-    item_path.with_file_name(metadata_filename)
+  /// Returns the path and the format of the metadata file of the item, if it
+  /// has one
+  pub fn find(
+    root: &std::path::Path, bucket: &str, name: &str,
+  ) -> Option<(std::path::PathBuf, MetadataFormat)> {
+    MetadataFormat::ALL
+      .into_iter()
+      .map(|format| (Self::path(root, bucket, name, format), format))
+      .find(|(path, _)| path.exists())
   }
 
+  /// Open the metadata file of the item. If it has none, the returned path is
+  /// the one it would have in the given `format`.
   pub fn file(
-    root: &std::path::PathBuf, bucket: &str, name: &str,
+    root: &std::path::Path, bucket: &str, name: &str, format: MetadataFormat,
   ) -> Result<(Option<std::fs::File>, std::path::PathBuf)> {
-    let path = Self::path(root, bucket, name);
+    let file = match Self::find(root, bucket, name) {
+      Some((path, _)) => (Some(std::fs::File::open(&path)?), path),
+      None => (None, Self::path(root, bucket, name, format)),
+    };
+
+    Ok(file)
+  }
 
-    let file = match Self::exists(root, bucket, name) {
-      true => Some(std::fs::File::open(&path)?),
-      false => None,
+  /// Deserialize the metadata file of the item, whatever its format is and
+  /// whether it is encrypted
+  pub fn read<M>(root: &std::path::Path, bucket: &str, name: &str) -> Result<Option<M>>
+  where
+    M: serde::de::DeserializeOwned,
+  {
+    let Some((path, format)) = Self::find(root, bucket, name) else {
+      return Ok(None);
     };
 
-    Ok((file, path))
+    Ok(Some(format.deserialize(&unseal(std::fs::read(path)?)?)?))
+  }
+
+  /// Write the `metadata` of the item in the given `format`, the metadata
+  /// files the item had in the other formats are removed
+  pub fn write<M>(
+    root: &std::path::Path, bucket: &str, name: &str, format: MetadataFormat, metadata: &M,
+  ) -> Result<()>
+  where
+    M: serde::Serialize,
+  {
+    std::fs::write(
+      Self::path(root, bucket, name, format),
+      seal(&format.serialize(metadata)?)?,
+    )?;
+
+    for other in MetadataFormat::ALL.into_iter().filter(|f| *f != format) {
+      let path = Self::path(root, bucket, name, other);
+
+      if path.exists() {
+        std::fs::remove_file(path)?;
+      }
+    }
+
+    Ok(())
   }
 
-  pub fn write(root: &std::path::PathBuf, bucket: &str, name: &str, content: &str) -> Result<()> {
-    std::fs::write(Self::path(root, bucket, name), content)?;
+  /// Copy the metadata file of the item in its current format, the metadata
+  /// files the destination had in other formats are removed
+  pub fn copy(
+    root: &std::path::Path, from_bucket: &str, from_name: &str, to_bucket: &str, to_name: &str,
+  ) -> Result<()> {
+    Self::remove(root, to_bucket, to_name)?;
+
+    if let Some((source, format)) = Self::find(root, from_bucket, from_name) {
+      reflink_copy::reflink_or_copy(source, Self::path(root, to_bucket, to_name, format))?;
+    }
 
     Ok(())
   }
 
-  pub fn exists(root: &std::path::PathBuf, bucket: &str, name: &str) -> bool {
-    Self::path(root, bucket, name).exists()
+  /// Move the metadata file of the item in its current format, the metadata
+  /// files the destination had in other formats are removed
+  pub fn rename(
+    root: &std::path::Path, from_bucket: &str, from_name: &str, to_bucket: &str, to_name: &str,
+  ) -> Result<()> {
+    Self::remove(root, to_bucket, to_name)?;
+
+    if let Some((source, format)) = Self::find(root, from_bucket, from_name) {
+      std::fs::rename(source, Self::path(root, to_bucket, to_name, format))?;
+    }
+
+    Ok(())
+  }
+
+  pub fn exists(root: &std::path::Path, bucket: &str, name: &str) -> bool {
+    Self::find(root, bucket, name).is_some()
   }
 
-  pub fn remove(root: &std::path::PathBuf, bucket: &str, name: &str) -> Result<()> {
-    std::fs::remove_file(Self::path(root, bucket, name))?;
+  pub fn remove(root: &std::path::Path, bucket: &str, name: &str) -> Result<()> {
+    for format in MetadataFormat::ALL {
+      let path = Self::path(root, bucket, name, format);
+
+      if path.exists() {
+        std::fs::remove_file(path)?;
+      }
+    }
 
     Ok(())
   }
@@ -0,0 +1,160 @@
+use crate::*;
+
+/// The serialization format of the metadata files. The format of a metadata
+/// file is given by its extension, so the files that were written before the
+/// format changed can still be read.
+///
+/// ```rs
+/// storage::set_metadata_format(storage::MetadataFormat::Json)?;
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetadataFormat {
+  #[default]
+  Yaml,
+  Json,
+  MessagePack,
+  Cbor,
+}
+
+impl MetadataFormat {
+  pub const ALL: [MetadataFormat; 4] = [
+    MetadataFormat::Yaml,
+    MetadataFormat::Json,
+    MetadataFormat::MessagePack,
+    MetadataFormat::Cbor,
+  ];
+
+  /// The extension of the metadata files written in this format, appended to
+  /// the name of their item
+  pub fn extension(self) -> &'static str {
+    match self {
+      MetadataFormat::Yaml => ".metadata.yaml",
+      MetadataFormat::Json => ".metadata.json",
+      MetadataFormat::MessagePack => ".metadata.msgpack",
+      MetadataFormat::Cbor => ".metadata.cbor",
+    }
+  }
+
+  /// Returns the format of the metadata file called `filename`, if it is one
+  pub fn from_filename(filename: &str) -> Option<Self> {
+    Self::ALL
+      .into_iter()
+      .find(|format| filename.ends_with(format.extension()))
+  }
+
+  pub(crate) fn serialize<M>(self, value: &M) -> Result<Vec<u8>>
+  where
+    M: serde::Serialize,
+  {
+    let bytes = match self {
+      MetadataFormat::Yaml => serde_yaml::to_string(value)?.into_bytes(),
+      MetadataFormat::Json => serde_json::to_vec(value).map_err(StorageError::metadata)?,
+      MetadataFormat::MessagePack => {
+        rmp_serde::to_vec_named(value).map_err(StorageError::metadata)?
+      }
+      MetadataFormat::Cbor => {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(value, &mut bytes).map_err(StorageError::metadata)?;
+
+        bytes
+      }
+    };
+
+    Ok(bytes)
+  }
+
+  pub(crate) fn deserialize<M>(self, bytes: &[u8]) -> Result<M>
+  where
+    M: serde::de::DeserializeOwned,
+  {
+    let value = match self {
+      MetadataFormat::Yaml => serde_yaml::from_slice(bytes)?,
+      MetadataFormat::Json => serde_json::from_slice(bytes).map_err(StorageError::metadata)?,
+      MetadataFormat::MessagePack => {
+        rmp_serde::from_slice(bytes).map_err(StorageError::metadata)?
+      }
+      MetadataFormat::Cbor => ciborium::from_reader(bytes).map_err(StorageError::metadata)?,
+    };
+
+    Ok(value)
+  }
+}
+
+impl std::str::FromStr for MetadataFormat {
+  type Err = StorageError;
+
+  fn from_str(s: &str) -> Result<Self> {
+    match s {
+      "yaml" => Ok(MetadataFormat::Yaml),
+      "json" => Ok(MetadataFormat::Json),
+      "messagepack" | "msgpack" => Ok(MetadataFormat::MessagePack),
+      "cbor" => Ok(MetadataFormat::Cbor),
+      _ => Err(StorageError::UnknownMetadataFormat),
+    }
+  }
+}
+
+impl std::fmt::Display for MetadataFormat {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      MetadataFormat::Yaml => write!(f, "yaml"),
+      MetadataFormat::Json => write!(f, "json"),
+      MetadataFormat::MessagePack => write!(f, "messagepack"),
+      MetadataFormat::Cbor => write!(f, "cbor"),
+    }
+  }
+}
+
+/// Convert every metadata file under the `root` into the given `format`, and
+/// return the number of converted files. The versions of the items are
+/// converted too. The encrypted files are decrypted with the keyring, and are
+/// written encrypted if one is set, see [crate::set_keyring].
+///
+/// The files are rewritten in place, so no server should be using the `root`
+/// while it is migrated.
+///
+/// ```rs
+/// storage::migrate_metadata("buckets", storage::MetadataFormat::Json)?;
+/// ```
+pub fn migrate_metadata(
+  root: impl AsRef<std::path::Path>, format: MetadataFormat,
+) -> Result<usize> {
+  let mut converted = 0;
+
+  for entry in std::fs::read_dir(root)? {
+    let entry = entry?;
+    let file_type = entry.file_type()?;
+    let filename = entry.file_name().to_string_lossy().into_owned();
+
+    if file_type.is_dir() {
+      converted += migrate_metadata(entry.path(), format)?;
+      continue;
+    }
+
+    let Some(current) = MetadataFormat::from_filename(&filename) else {
+      continue;
+    };
+
+    if !file_type.is_file() || current == format {
+      continue;
+    }
+
+    // ciborium's value can represent everything the other formats can, so
+    // nothing is lost on the way
+    let value: ciborium::Value = current.deserialize(&unseal(std::fs::read(entry.path())?)?)?;
+    let item = &filename[..filename.len() - current.extension().len()];
+
+    std::fs::write(
+      entry
+        .path()
+        .with_file_name(Metadata::metadata_filename(item, format)),
+      seal(&format.serialize(&value)?)?,
+    )?;
+    std::fs::remove_file(entry.path())?;
+
+    converted += 1;
+  }
+
+  Ok(converted)
+}
@@ -0,0 +1,377 @@
+//! The async counterpart of the storage functions, enabled by the `async`
+//! feature. Every filesystem call runs on the blocking pool of tokio so that a
+//! slow disk never stalls the async executor, and the content of the items is
+//! read and written through [AsyncRead] and [AsyncWrite].
+//!
+//! ```rs
+//! let (file, _) = storage::nonblocking::read(&"qsdo34-23d/filename.md".parse()?).await?;
+//! ```
+//!
+//! [AsyncRead]: tokio::io::AsyncRead
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt;
+
+use crate::*;
+
+/// Run the blocking `f` on the blocking pool of tokio
+async fn blocking<T, F>(f: F) -> Result<T>
+where
+  F: FnOnce() -> Result<T> + Send + 'static,
+  T: Send + 'static,
+{
+  tokio::task::spawn_blocking(f).await?
+}
+
+/// See [crate::read]
+pub async fn read(storage_path: &StoragePath) -> Result<(tokio::fs::File, PathBuf)> {
+  let storage_path = storage_path.clone();
+  let (file, path) = blocking(move || crate::read(&storage_path)).await?;
+
+  Ok((tokio::fs::File::from_std(file), path))
+}
+
+/// See [crate::open]
+///
+/// The [Content] reads the item synchronously, so it is meant to be read off
+/// the executor, or served as is when it is [Content::Plain].
+pub async fn open(storage_path: &StoragePath) -> Result<(Content, PathBuf)> {
+  let storage_path = storage_path.clone();
+
+  blocking(move || crate::open(&storage_path)).await
+}
+
+/// See [crate::read_metadata]
+pub async fn read_metadata(
+  storage_path: &StoragePath,
+) -> Result<(Option<tokio::fs::File>, PathBuf)> {
+  let storage_path = storage_path.clone();
+  let (file, path) = blocking(move || crate::read_metadata(&storage_path)).await?;
+
+  Ok((file.map(tokio::fs::File::from_std), path))
+}
+
+/// See [crate::deserialize_metadata]
+pub async fn deserialize_metadata<M>(storage_path: &StoragePath) -> Result<Option<M>>
+where
+  M: serde::de::DeserializeOwned + Send + 'static,
+{
+  let storage_path = storage_path.clone();
+
+  blocking(move || crate::deserialize_metadata(&storage_path)).await
+}
+
+/// See [crate::exists]
+pub async fn exists(storage_path: &StoragePath) -> Result<bool> {
+  let storage_path = storage_path.clone();
+
+  blocking(move || crate::exists(&storage_path)).await
+}
+
+/// See [crate::remove]
+pub async fn remove(storage_path: &StoragePath) -> Result<()> {
+  let storage_path = storage_path.clone();
+
+  blocking(move || crate::remove(&storage_path)).await
+}
+
+/// See [crate::copy]
+pub async fn copy(from: &StoragePath, to: &StoragePath) -> Result<()> {
+  let (from, to) = (from.clone(), to.clone());
+
+  blocking(move || crate::copy(&from, &to)).await
+}
+
+/// See [crate::compress_tempfile]
+pub async fn compress_tempfile(
+  bucket: &BucketName, tempfile: tempfile::NamedTempFile, mime_type: &str,
+) -> Result<(tempfile::NamedTempFile, Option<Encoding>)> {
+  let (bucket, mime_type) = (bucket.clone(), mime_type.to_owned());
+
+  blocking(move || crate::compress_tempfile(&bucket, tempfile, &mime_type)).await
+}
+
+/// See [crate::read_derivative]
+///
+/// The [Content] reads the derivative synchronously, so it is meant to be
+/// read off the executor.
+pub async fn read_derivative(
+  storage_path: &StoragePath, name: &ItemName,
+) -> Result<Option<(Content, PathBuf)>> {
+  let (storage_path, name) = (storage_path.clone(), name.clone());
+
+  blocking(move || crate::read_derivative(&storage_path, &name)).await
+}
+
+/// See [crate::write_derivative]
+pub async fn write_derivative(
+  storage_path: &StoragePath, name: &ItemName, content: Vec<u8>, keep: usize,
+) -> Result<()> {
+  let (storage_path, name) = (storage_path.clone(), name.clone());
+
+  blocking(move || crate::write_derivative(&storage_path, &name, &content, keep)).await
+}
+
+/// See [crate::rename]
+pub async fn rename(from: &StoragePath, to: &StoragePath) -> Result<()> {
+  let (from, to) = (from.clone(), to.clone());
+
+  blocking(move || crate::rename(&from, &to)).await
+}
+
+/// See [crate::write]
+pub async fn write<M>(name: &ItemName, content: String, metadata: M) -> Result<StoragePath>
+where
+  M: serde::Serialize + Send + 'static,
+{
+  let name = name.clone();
+
+  blocking(move || crate::write(&name, &content, metadata)).await
+}
+
+/// See [crate::persist_tempfile]
+pub async fn persist_tempfile<M>(
+  name: &ItemName, tempfile: tempfile::NamedTempFile, metadata: M,
+) -> Result<StoragePath>
+where
+  M: serde::Serialize + Send + 'static,
+{
+  let name = name.clone();
+
+  blocking(move || crate::persist_tempfile(&name, tempfile, metadata)).await
+}
+
+/// See [crate::persist_tempfile_in]
+pub async fn persist_tempfile_in<M>(
+  bucket: &BucketName, name: &ItemName, tempfile: tempfile::NamedTempFile, metadata: M,
+) -> Result<StoragePath>
+where
+  M: serde::Serialize + Send + 'static,
+{
+  let (bucket, name) = (bucket.clone(), name.clone());
+
+  blocking(move || crate::persist_tempfile_in(&bucket, &name, tempfile, metadata)).await
+}
+
+/// See [crate::replace_tempfile]
+pub async fn replace_tempfile<M>(
+  storage_path: &StoragePath, tempfile: tempfile::NamedTempFile, metadata: M,
+) -> Result<StoragePath>
+where
+  M: serde::Serialize + Send + 'static,
+{
+  let storage_path = storage_path.clone();
+
+  blocking(move || crate::replace_tempfile(&storage_path, tempfile, metadata)).await
+}
+
+/// See [crate::internal::set_metadata]
+pub async fn set_metadata<M>(storage_path: &StoragePath, metadata: M) -> Result<()>
+where
+  M: serde::Serialize + Send + 'static,
+{
+  let storage_path = storage_path.clone();
+
+  blocking(move || internal::set_metadata(&storage_path, metadata)).await
+}
+
+/// See [crate::update_metadata]
+pub async fn update_metadata<M, E, F>(
+  storage_path: &StoragePath, update: F,
+) -> std::result::Result<M, E>
+where
+  M: serde::Serialize + serde::de::DeserializeOwned + Send + 'static,
+  E: From<StorageError> + Send + 'static,
+  F: FnOnce(Option<M>) -> std::result::Result<M, E> + Send + 'static,
+{
+  let storage_path = storage_path.clone();
+
+  tokio::task::spawn_blocking(move || crate::update_metadata(&storage_path, update))
+    .await
+    .map_err(StorageError::from)?
+}
+
+/// See [crate::query]
+pub async fn query(query: &Query) -> Result<QueryPage> {
+  let query = query.clone();
+
+  blocking(move || crate::query(&query)).await
+}
+
+/// See [crate::internal::active_bucket]
+pub async fn active_bucket() -> Result<BucketName> {
+  blocking(internal::active_bucket).await
+}
+
+/// Streams the content of a new item into a tempfile of the storage root. The
+/// item only appears in its bucket once the writer is persisted, a writer that
+/// is dropped before leaves nothing behind.
+///
+/// ```rs
+/// let mut writer = storage::nonblocking::ItemWriter::new().await?;
+/// tokio::io::copy(&mut body, &mut writer).await?;
+/// writer.persist(&"report.pdf".parse()?, ()).await?;
+/// ```
+pub struct ItemWriter {
+  file: tokio::fs::File,
+  path: tempfile::TempPath,
+}
+
+impl ItemWriter {
+  pub async fn new() -> Result<Self> {
+    let tempfile = blocking(|| Ok(tempfile::NamedTempFile::new_in(&config()?.root)?)).await?;
+    let (file, path) = tempfile.into_parts();
+
+    Ok(Self {
+      file: tokio::fs::File::from_std(file),
+      path,
+    })
+  }
+
+  async fn into_tempfile(mut self) -> Result<tempfile::NamedTempFile> {
+    self.file.flush().await?;
+    let file = self.file.into_std().await;
+
+    Ok(tempfile::NamedTempFile::from_parts(file, self.path))
+  }
+
+  /// Store the written content in the active bucket, see [persist_tempfile]
+  pub async fn persist<M>(self, name: &ItemName, metadata: M) -> Result<StoragePath>
+  where
+    M: serde::Serialize + Send + 'static,
+  {
+    persist_tempfile(name, self.into_tempfile().await?, metadata).await
+  }
+
+  /// Store the written content in the given `bucket`, see
+  /// [persist_tempfile_in]
+  pub async fn persist_in<M>(
+    self, bucket: &BucketName, name: &ItemName, metadata: M,
+  ) -> Result<StoragePath>
+  where
+    M: serde::Serialize + Send + 'static,
+  {
+    persist_tempfile_in(bucket, name, self.into_tempfile().await?, metadata).await
+  }
+
+  /// Replace the item at `storage_path` with the written content, see
+  /// [replace_tempfile]
+  pub async fn replace<M>(self, storage_path: &StoragePath, metadata: M) -> Result<StoragePath>
+  where
+    M: serde::Serialize + Send + 'static,
+  {
+    replace_tempfile(storage_path, self.into_tempfile().await?, metadata).await
+  }
+}
+
+impl AsyncWrite for ItemWriter {
+  fn poll_write(
+    mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8],
+  ) -> Poll<std::io::Result<usize>> {
+    Pin::new(&mut self.file).poll_write(cx, buf)
+  }
+
+  fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+    Pin::new(&mut self.file).poll_flush(cx)
+  }
+
+  fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+    Pin::new(&mut self.file).poll_shutdown(cx)
+  }
+}
+
+/// The async counterpart of [crate::buckets]
+pub mod buckets {
+  use super::blocking;
+  use crate::buckets::BucketInfo;
+  use crate::*;
+
+  /// See [crate::buckets::create]
+  pub async fn create(name: &BucketName, settings: BucketSettings) -> Result<()> {
+    let name = name.clone();
+
+    blocking(move || crate::buckets::create(&name, settings)).await
+  }
+
+  /// See [crate::buckets::delete]
+  pub async fn delete(name: &BucketName, force: bool) -> Result<()> {
+    let name = name.clone();
+
+    blocking(move || crate::buckets::delete(&name, force)).await
+  }
+
+  /// See [crate::buckets::rename]
+  pub async fn rename(from: &BucketName, to: &BucketName) -> Result<()> {
+    let (from, to) = (from.clone(), to.clone());
+
+    blocking(move || crate::buckets::rename(&from, &to)).await
+  }
+
+  /// See [crate::buckets::describe]
+  pub async fn describe(name: &BucketName) -> Result<BucketInfo> {
+    let name = name.clone();
+
+    blocking(move || crate::buckets::describe(&name)).await
+  }
+
+  /// See [crate::buckets::list]
+  pub async fn list() -> Result<Vec<BucketName>> {
+    blocking(crate::buckets::list).await
+  }
+
+  /// See [crate::buckets::items]
+  pub async fn items(name: &BucketName) -> Result<Vec<ItemName>> {
+    let name = name.clone();
+
+    blocking(move || crate::buckets::items(&name)).await
+  }
+
+  /// See [crate::buckets::settings]
+  pub async fn settings(name: &BucketName) -> Result<BucketSettings> {
+    let name = name.clone();
+
+    blocking(move || crate::buckets::settings(&name)).await
+  }
+
+  /// See [crate::buckets::set_settings]
+  pub async fn set_settings(name: &BucketName, settings: BucketSettings) -> Result<()> {
+    let name = name.clone();
+
+    blocking(move || crate::buckets::set_settings(&name, settings)).await
+  }
+
+  /// See [crate::buckets::remove_expired]
+  pub async fn remove_expired() -> Result<usize> {
+    blocking(crate::buckets::remove_expired).await
+  }
+}
+
+pub mod versions {
+  use super::blocking;
+  use crate::*;
+
+  /// See [crate::versions::list]
+  pub async fn list(storage_path: &StoragePath) -> Result<Vec<String>> {
+    let storage_path = storage_path.clone();
+
+    blocking(move || crate::versions::list(&storage_path)).await
+  }
+
+  /// See [crate::versions::restore]
+  pub async fn restore(storage_path: &StoragePath, version: &str) -> Result<()> {
+    let (storage_path, version) = (storage_path.clone(), version.to_owned());
+
+    blocking(move || crate::versions::restore(&storage_path, &version)).await
+  }
+
+  /// See [crate::versions::purge]
+  pub async fn purge(storage_path: &StoragePath) -> Result<()> {
+    let storage_path = storage_path.clone();
+
+    blocking(move || crate::versions::purge(&storage_path)).await
+  }
+}
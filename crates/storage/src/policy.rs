@@ -0,0 +1,210 @@
+use std::path::Path;
+
+use crate::*;
+
+/// Decides when the active bucket stops receiving new items, and what the
+/// bucket that replaces it is named.
+///
+/// ```rs
+/// storage::initialize_with_policy(".", storage::ItemCount::new(10_000))?;
+/// storage::set_bucket_policy(storage::Periodic::Monthly)?;
+/// ```
+pub trait BucketPolicy: Send + Sync {
+  /// Returns whether the `active` bucket should be replaced before a new item
+  /// is stored
+  fn is_full(&self, root: &Path, active: &BucketName) -> Result<bool>;
+
+  /// Returns the name of the bucket that replaces the `active` one, or of the
+  /// first bucket if there is no active bucket yet. If a bucket with this name
+  /// already exists then it is reused as is.
+  fn next_name(&self, root: &Path, active: Option<&BucketName>) -> Result<BucketName>;
+}
+
+impl<P> BucketPolicy for Box<P>
+where
+  P: BucketPolicy + ?Sized,
+{
+  fn is_full(&self, root: &Path, active: &BucketName) -> Result<bool> {
+    self.as_ref().is_full(root, active)
+  }
+
+  fn next_name(&self, root: &Path, active: Option<&BucketName>) -> Result<BucketName> {
+    self.as_ref().next_name(root, active)
+  }
+}
+
+/// Generates the names of the new buckets for the policies that don't impose
+/// a naming scheme. Any `Fn() -> String` can be used as a naming scheme:
+///
+/// ```rs
+/// storage::ItemCount::new(500).with_naming(|| format!("archive-{}", nanoid::nanoid!(6)))
+/// ```
+///
+/// If the name is already used by a bucket then a `-{n}` suffix is appended
+/// to it. The name must be a valid [BucketName], the rotation fails otherwise.
+pub trait BucketNaming: Send + Sync {
+  fn name(&self) -> String;
+}
+
+impl<F> BucketNaming for F
+where
+  F: Fn() -> String + Send + Sync,
+{
+  fn name(&self) -> String {
+    self()
+  }
+}
+
+impl BucketNaming for Box<dyn BucketNaming> {
+  fn name(&self) -> String {
+    self.as_ref().name()
+  }
+}
+
+/// Names the buckets with a random unique id, this is the default naming
+pub struct RandomNaming;
+
+impl BucketNaming for RandomNaming {
+  fn name(&self) -> String {
+    Bucket::new_random_name()
+  }
+}
+
+/// Names the buckets after the UTC time of their creation, optionally
+/// prefixed: `{prefix}20231231-235959`
+pub struct TimestampNaming {
+  pub prefix: String,
+}
+
+impl BucketNaming for TimestampNaming {
+  fn name(&self) -> String {
+    let now = chrono::Utc::now().format("%Y%m%d-%H%M%S");
+
+    format!("{}{now}", self.prefix)
+  }
+}
+
+/// Returns the first name derived from the `naming` that isn't used by a bucket
+fn unused_name(root: &Path, naming: &dyn BucketNaming) -> Result<BucketName> {
+  let name = BucketName::new(naming.name())?;
+
+  if !Bucket::exists(root, &name) {
+    return Ok(name);
+  }
+
+  (1..)
+    .map(|n| BucketName::new(format!("{name}-{n}")))
+    .find(|name| {
+      name
+        .as_ref()
+        .map_or(true, |name| !Bucket::exists(root, name))
+    })
+    .unwrap_or(Err(ParseError::InvalidBucketName))
+    .map_err(StorageError::from)
+}
+
+/// Replaces the active bucket once it holds `max` items. Unlike the number of
+/// entries in the bucket folder, the metadata files are not counted.
+pub struct ItemCount {
+  max: usize,
+  naming: Box<dyn BucketNaming>,
+}
+
+impl ItemCount {
+  pub fn new(max: usize) -> Self {
+    Self {
+      max,
+      naming: Box::new(RandomNaming),
+    }
+  }
+
+  pub fn with_naming(self, naming: impl BucketNaming + 'static) -> Self {
+    Self {
+      naming: Box::new(naming),
+      ..self
+    }
+  }
+}
+
+impl Default for ItemCount {
+  fn default() -> Self {
+    Self::new(constants::BUCKET_SIZE_MAX)
+  }
+}
+
+impl BucketPolicy for ItemCount {
+  fn is_full(&self, root: &Path, active: &BucketName) -> Result<bool> {
+    Ok(Bucket::item_count(root, active)? >= self.max)
+  }
+
+  fn next_name(&self, root: &Path, _: Option<&BucketName>) -> Result<BucketName> {
+    unused_name(root, self.naming.as_ref())
+  }
+}
+
+/// Replaces the active bucket once its files, metadata included, weigh `max`
+/// bytes or more.
+pub struct TotalBytes {
+  max: u64,
+  naming: Box<dyn BucketNaming>,
+}
+
+impl TotalBytes {
+  pub fn new(max: u64) -> Self {
+    Self {
+      max,
+      naming: Box::new(RandomNaming),
+    }
+  }
+
+  pub fn with_naming(self, naming: impl BucketNaming + 'static) -> Self {
+    Self {
+      naming: Box::new(naming),
+      ..self
+    }
+  }
+}
+
+impl BucketPolicy for TotalBytes {
+  fn is_full(&self, root: &Path, active: &BucketName) -> Result<bool> {
+    Ok(Bucket::total_bytes(root, active)? >= self.max)
+  }
+
+  fn next_name(&self, root: &Path, _: Option<&BucketName>) -> Result<BucketName> {
+    unused_name(root, self.naming.as_ref())
+  }
+}
+
+/// Stores the items of every period in its own bucket, named after the UTC
+/// date of the period: `2023-12-31` for a day and `2023-12` for a month.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Periodic {
+  Daily,
+  Monthly,
+}
+
+impl Periodic {
+  /// Returns the name of the bucket holding the items of the given `date`
+  pub fn bucket_name(&self, date: chrono::NaiveDate) -> String {
+    let format = match self {
+      Periodic::Daily => "%Y-%m-%d",
+      Periodic::Monthly => "%Y-%m",
+    };
+
+    date.format(format).to_string()
+  }
+
+  fn current_name(&self) -> String {
+    self.bucket_name(chrono::Utc::now().date_naive())
+  }
+}
+
+impl BucketPolicy for Periodic {
+  fn is_full(&self, _: &Path, active: &BucketName) -> Result<bool> {
+    Ok(active.as_str() != self.current_name())
+  }
+
+  fn next_name(&self, _: &Path, _: Option<&BucketName>) -> Result<BucketName> {
+    Ok(BucketName::new(self.current_name())?)
+  }
+}
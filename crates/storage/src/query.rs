@@ -0,0 +1,83 @@
+use crate::*;
+
+pub use shcs_types::Filter;
+pub use shcs_types::FilterOp;
+pub use shcs_types::Query;
+pub use shcs_types::QueryField;
+pub use shcs_types::QueryHit;
+pub use shcs_types::QueryPage;
+
+/// The number of items in a page of the `query`, defaults to
+/// [constants::QUERY_LIMIT_DEFAULT] and cannot exceed
+/// [constants::QUERY_LIMIT_MAX]
+pub(crate) fn limit(query: &Query) -> usize {
+  query
+    .limit
+    .unwrap_or(constants::QUERY_LIMIT_DEFAULT)
+    .min(constants::QUERY_LIMIT_MAX)
+}
+
+/// The value the `filter` compares the field to, as an SQLite parameter
+pub(crate) fn sql_value(filter: &Filter) -> Result<rusqlite::types::Value> {
+  use rusqlite::types::Value;
+  use serde_json::Value as Json;
+
+  let value = match (&filter.field, &filter.value) {
+    (_, Json::Null) if matches!(filter.op, FilterOp::Eq | FilterOp::Ne) => Value::Null,
+    (_, Json::Null) => {
+      return Err(StorageError::InvalidQuery(
+        "null can only be compared with eq or ne",
+      ))
+    }
+    (QueryField::UploadedAt, Json::String(date)) => chrono::DateTime::parse_from_rfc3339(date)
+      .map(|date| Value::Integer(date.timestamp()))
+      .map_err(|_| {
+        StorageError::InvalidQuery("uploaded_at expects a unix timestamp or an RFC 3339 date")
+      })?,
+    (_, Json::Bool(b)) => Value::Integer(*b as i64),
+    (_, Json::Number(n)) => match n.as_i64() {
+      Some(n) => Value::Integer(n),
+      None => Value::Real(n.as_f64().unwrap_or(f64::NAN)),
+    },
+    (_, Json::String(s)) => Value::Text(s.clone()),
+    (_, Json::Array(_) | Json::Object(_)) => {
+      return Err(StorageError::InvalidQuery(
+        "arrays and objects cannot be compared",
+      ))
+    }
+  };
+
+  Ok(value)
+}
+
+pub(crate) fn sql_operator(op: FilterOp) -> &'static str {
+  match op {
+    // unlike `=`, `IS` also matches the missing values when comparing to null
+    FilterOp::Eq => "IS",
+    FilterOp::Ne => "IS NOT",
+    FilterOp::Lt => "<",
+    FilterOp::Lte => "<=",
+    FilterOp::Gt => ">",
+    FilterOp::Gte => ">=",
+  }
+}
+
+/// The SQLite JSON path of a [QueryField::Metadata] field
+pub(crate) fn json_path(keys: &[String]) -> String {
+  keys
+    .iter()
+    .fold(String::from("$"), |path, key| format!("{path}.\"{key}\""))
+}
+
+/// Returns the page of the items selected by the `query`, from the metadata
+/// index of the storage rather than from the metadata files.
+///
+/// ```rs
+/// let page = storage::query(&storage::Query {
+///   filters: vec![storage::Filter::new("custom.project_id".parse()?, storage::FilterOp::Eq, 42)],
+///   ..Default::default()
+/// })?;
+/// ```
+pub fn query(query: &Query) -> Result<QueryPage> {
+  config()?.index.query(query)
+}
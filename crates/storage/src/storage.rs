@@ -2,134 +2,536 @@ use crate::*;
 
 /// Read the file at given `storage_path` and return the File & its path.
 ///
-/// A storage path consists of a string, split by a `/` where the left side
-/// is the bucket_name and the right side is the filename: `qsdo34-23d/filename.md`
-///
 /// ```rs
-/// storage::read("qsdo34-23d/filename.md")
+/// storage::read(&"qsdo34-23d/filename.md".parse()?)
 /// ```
 ///
 /// If the supplied path does not point to an existing file, then an error is
 /// returned.
-pub fn read(storage_path: &str) -> Result<(std::fs::File, std::path::PathBuf)> {
-  let (bucket, item) = internal::bucket_and_item(storage_path)?;
+///
+/// If the item outlived the TTL of its bucket then it is removed and a
+/// `NotFound` io error is returned.
+///
+/// The file is returned as it is stored, see [open] for the decrypted content
+/// of the items written with a [Keyring].
+pub fn read(storage_path: &StoragePath) -> Result<(std::fs::File, std::path::PathBuf)> {
+  let StoragePath { bucket, item } = storage_path;
+  let root = &config()?.root;
+
+  let (file, path) = Item::file(root, bucket, item)?;
+  let settings = BucketSettings::from_file(root, bucket)?;
+
+  if settings.is_expired(file.metadata()?.modified()?) {
+    drop(file);
+    remove(storage_path)?;
 
-  Item::file(&config()?.root, bucket, item)
+    return Err(std::io::Error::from(std::io::ErrorKind::NotFound).into());
+  }
+
+  Ok((file, path))
 }
 
-/// Read the metadata for the file sitting at the given `storage_path`.
+/// Open the item at the given `storage_path` like [read] does, and return its
+/// [Content] that is decrypted as it is read if the item is encrypted.
 ///
-/// A storage path consists of a string, split by a `/` where the left side
-/// is the bucket_name and the right side is the filename: `qsdo34-23d/filename.md`
+/// ```rs
+/// let (mut content, _) = storage::open(&"qsdo34-23d/filename.md".parse()?)?;
+/// std::io::copy(&mut content, &mut std::io::stdout())?;
+/// ```
+pub fn open(storage_path: &StoragePath) -> Result<(Content, std::path::PathBuf)> {
+  let (file, path) = read(storage_path)?;
+
+  Ok((Content::new(file)?, path))
+}
+
+/// Read the metadata for the file sitting at the given `storage_path`.
 ///
 /// ```rs
-/// storage::read("qsdo34-23d/filename.md")
+/// storage::read_metadata(&"qsdo34-23d/filename.md".parse()?)
 /// ```
 ///
 /// Due to the optional nature of the metadata file, and unlike the [read()]
 /// function, if the supplied path does not point to an existing file, then None
 /// is returned.
-pub fn read_metadata(storage_path: &str) -> Result<(Option<std::fs::File>, std::path::PathBuf)> {
-  let (bucket, item) = internal::bucket_and_item(storage_path)?;
+///
+/// The file is returned as it is stored, encrypted if it was written with a
+/// [Keyring], see [deserialize_metadata] to read it whatever it is.
+pub fn read_metadata(
+  storage_path: &StoragePath,
+) -> Result<(Option<std::fs::File>, std::path::PathBuf)> {
+  let config = config()?;
 
-  Metadata::file(&config()?.root, bucket, item)
+  Metadata::file(
+    &config.root,
+    &storage_path.bucket,
+    &storage_path.item,
+    config.metadata_format()?,
+  )
 }
 
 /// Read the metadata for the file sitting at the given `storage_path` and
 /// deserialize the content into the returned value `M` as long as `M` implements
 /// [serde::Deserialize]
-pub fn deserialize_metadata<M>(storage_path: &str) -> Result<Option<M>>
+///
+/// The format of the metadata file is detected from its extension, so files
+/// written before a change of [MetadataFormat] are still read.
+pub fn deserialize_metadata<M>(storage_path: &StoragePath) -> Result<Option<M>>
 where
   M: serde::de::DeserializeOwned,
 {
-  let (file, path) = read_metadata(storage_path)?;
+  Metadata::read(&config()?.root, &storage_path.bucket, &storage_path.item)
+}
 
-  let metadata = match file.is_some() {
-    true => {
-      let content = std::fs::read_to_string(path)?;
+/// Replace the metadata of the item at `storage_path` with the one `update`
+/// returns, given the current metadata of the item if it has some. The updates
+/// of an item run one at a time, so `update` always receives its latest
+/// metadata.
+///
+/// ```rs
+/// storage::update_metadata(&path, |count: Option<u64>| Ok::<_, StorageError>(count.unwrap_or(0) + 1))?;
+/// ```
+///
+/// If the item doesn't exist, a `NotFound` io error is returned and `update`
+/// isn't called.
+pub fn update_metadata<M, E, F>(storage_path: &StoragePath, update: F) -> std::result::Result<M, E>
+where
+  M: serde::Serialize + serde::de::DeserializeOwned,
+  E: From<StorageError>,
+  F: FnOnce(Option<M>) -> std::result::Result<M, E>,
+{
+  let config = config()?;
+  let _lock = config.metadata_lock.lock(storage_path.to_string())?;
 
-      Some(serde_yaml::from_str(&content)?)
-    }
-    false => None,
-  };
+  if !exists(storage_path)? {
+    return Err(StorageError::from(std::io::Error::from(std::io::ErrorKind::NotFound)).into());
+  }
+
+  let metadata = update(deserialize_metadata(storage_path)?)?;
+  internal::set_metadata(storage_path, &metadata)?;
 
   Ok(metadata)
 }
 
 /// Returns whether the given path points to an existing item.
 ///
-/// A storage path consists of a string, split by a `/` where the left side
-/// is the bucket_name and the right side is the filename: `qsdo34-23d/filename.md`
-///
 /// ```rs
-/// storage::exists("qsdo34-23d/filename.md")
+/// storage::exists(&"qsdo34-23d/filename.md".parse()?)
 /// ```
-pub fn exists(storage_path: &str) -> Result<bool> {
-  let (bucket, item) = internal::bucket_and_item(storage_path)?;
-
-  Ok(Item::exists(&config()?.root, bucket, item))
+pub fn exists(storage_path: &StoragePath) -> Result<bool> {
+  Ok(Item::exists(
+    &config()?.root,
+    &storage_path.bucket,
+    &storage_path.item,
+  ))
 }
 
 /// Remove the file sitting at `storage_path` while also removing the optional
-/// metadata file that is linked to the file.
-pub fn remove(storage_path: &str) -> Result<()> {
-  let (bucket, item) = internal::bucket_and_item(storage_path)?;
-  let root = &config()?.root;
+/// metadata file that is linked to the file, and its previous versions.
+pub fn remove(storage_path: &StoragePath) -> Result<()> {
+  let StoragePath { bucket, item } = storage_path;
+  let config = config()?;
+  let root = &config.root;
 
   let item_removal = Item::remove(root, bucket, item);
   let mut metadata_removal = Ok(());
 
-  if Metadata::exists(&root, bucket, item) {
+  if Metadata::exists(root, bucket, item) {
     metadata_removal = Metadata::remove(root, bucket, item);
   }
 
+  config.index.remove(storage_path)?;
+  Item::remove_derivatives(root, bucket, item)?;
+  Item::remove_versions(root, bucket, item)?;
+
   item_removal.and(metadata_removal)
 }
 
-pub fn write<M>(name: &str, content: &str, metadata: M) -> Result<String>
+/// Read the derivative called `name` of the item at `storage_path`, a file
+/// computed from its content like a thumbnail, if it is cached.
+///
+/// The derivatives of an item are removed whenever the item is written, moved
+/// or removed.
+pub fn read_derivative(
+  storage_path: &StoragePath, name: &ItemName,
+) -> Result<Option<(Content, std::path::PathBuf)>> {
+  let StoragePath { bucket, item } = storage_path;
+  let path = Item::derivatives_path(&config()?.root, bucket, item).join(name.as_str());
+
+  match std::fs::File::open(&path) {
+    Ok(file) => Ok(Some((Content::new(file)?, path))),
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+    Err(e) => Err(e.into()),
+  }
+}
+
+/// Cache the `content` of the derivative called `name` of the item at
+/// `storage_path`, see [read_derivative]. At most `keep` derivatives of the
+/// item are cached, the ones written the longest ago are evicted to make room.
+pub fn write_derivative(
+  storage_path: &StoragePath, name: &ItemName, content: &[u8], keep: usize,
+) -> Result<()> {
+  let StoragePath { bucket, item } = storage_path;
+  let root = &config()?.root;
+
+  if !Item::exists(root, bucket, item) {
+    return Err(std::io::Error::from(std::io::ErrorKind::NotFound).into());
+  }
+
+  let folder = Item::derivatives_path(root, bucket, item);
+  std::fs::create_dir_all(&folder)?;
+
+  // written aside then moved so that a derivative is never read half written
+  let mut tempfile = tempfile::NamedTempFile::new_in(&folder)?;
+  std::io::Write::write_all(&mut tempfile, &seal(content)?)?;
+  tempfile
+    .persist(folder.join(name.as_str()))
+    .map_err(|e| e.error)?;
+
+  evict_derivatives(&folder, keep)
+}
+
+/// Remove the oldest derivatives of the `folder` past the `keep` newest ones
+fn evict_derivatives(folder: &std::path::Path, keep: usize) -> Result<()> {
+  let mut derivatives = Vec::new();
+
+  for entry in std::fs::read_dir(folder)? {
+    let entry = entry?;
+
+    // the derivatives being written by other requests are left alone
+    if entry.file_name().to_string_lossy().starts_with('.') {
+      continue;
+    }
+
+    match entry.metadata().and_then(|metadata| metadata.modified()) {
+      Ok(modified) => derivatives.push((modified, entry.path())),
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+      Err(e) => return Err(e.into()),
+    }
+  }
+
+  if derivatives.len() <= keep {
+    return Ok(());
+  }
+
+  derivatives.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+
+  for (_, path) in derivatives.into_iter().skip(keep) {
+    match std::fs::remove_file(path) {
+      Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+      _ => {}
+    }
+  }
+
+  Ok(())
+}
+
+/// Copy the item at `from` and its metadata to `to`, replacing the item that
+/// was there. The bucket of `to` must exist, and its settings apply to the
+/// copy like they do to any write.
+///
+/// ```rs
+/// storage::copy(&"invoices/2023.pdf".parse()?, &"archive/2023.pdf".parse()?)?;
+/// ```
+pub fn copy(from: &StoragePath, to: &StoragePath) -> Result<()> {
+  let config = config()?;
+  let root = &config.root;
+
+  if !Item::exists(root, &from.bucket, &from.item) {
+    return Err(std::io::Error::from(std::io::ErrorKind::NotFound).into());
+  }
+
+  if from == to {
+    return Ok(());
+  }
+
+  let size = std::fs::metadata(Item::path(root, &from.bucket, &from.item))?.len();
+  write_item(root, &to.bucket, &to.item, size, || {
+    Item::copy(root, &from.bucket, &from.item, &to.bucket, &to.item)
+  })?;
+
+  Metadata::copy(root, &from.bucket, &from.item, &to.bucket, &to.item)?;
+
+  config
+    .index
+    .insert_item(to, plaintext_size(&Item::path(root, &to.bucket, &to.item))?)?;
+  index_metadata(to)
+}
+
+/// Move the item at `from` and its metadata to `to`, replacing the item that
+/// was there. The bucket of `to` must exist, and its settings apply to the
+/// moved item like they do to any write. The previous versions of `from` are
+/// removed.
+///
+/// ```rs
+/// storage::rename(&"invoices/2023.pdf".parse()?, &"archive/2023.pdf".parse()?)?;
+/// ```
+pub fn rename(from: &StoragePath, to: &StoragePath) -> Result<()> {
+  let config = config()?;
+  let root = &config.root;
+
+  if !Item::exists(root, &from.bucket, &from.item) {
+    return Err(std::io::Error::from(std::io::ErrorKind::NotFound).into());
+  }
+
+  if from == to {
+    return Ok(());
+  }
+
+  // the item already counts towards the size of its own bucket
+  let size = match from.bucket == to.bucket {
+    true => 0,
+    false => std::fs::metadata(Item::path(root, &from.bucket, &from.item))?.len(),
+  };
+  write_item(root, &to.bucket, &to.item, size, || {
+    Item::rename(root, &from.bucket, &from.item, &to.bucket, &to.item)
+  })?;
+
+  Metadata::rename(root, &from.bucket, &from.item, &to.bucket, &to.item)?;
+  Item::remove_derivatives(root, &from.bucket, &from.item)?;
+  Item::remove_versions(root, &from.bucket, &from.item)?;
+
+  config.index.rename(from, to)
+}
+
+/// Index the metadata file of the item at `storage_path` as it is on disk
+fn index_metadata(storage_path: &StoragePath) -> Result<()> {
+  let StoragePath { bucket, item } = storage_path;
+  let config = config()?;
+
+  let metadata: Option<serde_json::Value> = Metadata::read(&config.root, bucket, item)?;
+  let path = Item::path(&config.root, bucket, item);
+
+  config
+    .index
+    .set_metadata(storage_path, &path, metadata.as_ref())
+}
+
+pub fn write<M>(name: &ItemName, content: &str, metadata: M) -> Result<StoragePath>
 where
   M: serde::Serialize,
 {
   let config = config()?;
   let active_bucket = config.with_bucket()?;
 
-  let storage_path = internal::write_exact(&config.root, &active_bucket, name, content)?;
+  let size = content.len() as u64;
+  write_item(&config.root, &active_bucket, name, size, || {
+    internal::write_exact(&config.root, &active_bucket, name, content).map(|_| ())
+  })?;
+
+  let storage_path = StoragePath::new(active_bucket, name.clone());
   internal::set_metadata(&storage_path, metadata)?;
 
   Ok(storage_path)
 }
 
+/// Compress the `tempfile` of a new item of the `bucket` if the [Compression]
+/// settings of the bucket apply to its `mime_type`, and return the tempfile to
+/// persist with its encoding. The encoding must be kept alongside the item,
+/// usually in its metadata, to read it back with [Encoding::decoder].
+///
+/// The content is stored as is when compressing doesn't make it smaller.
+pub fn compress_tempfile(
+  bucket: &BucketName, tempfile: tempfile::NamedTempFile, mime_type: &str,
+) -> Result<(tempfile::NamedTempFile, Option<Encoding>)> {
+  let root = &config()?.root;
+
+  if !Bucket::exists(root, bucket) {
+    return Err(StorageError::BucketNotFound);
+  }
+
+  let size = tempfile.as_file().metadata()?.len();
+  let settings = BucketSettings::from_file(root, bucket)?;
+
+  let Some(encoding) = settings.encoding_for(size, mime_type) else {
+    return Ok((tempfile, None));
+  };
+
+  let compressed = Item::compress(&tempfile, encoding)?;
+
+  match compressed.as_file().metadata()?.len() < size {
+    true => Ok((compressed, Some(encoding))),
+    false => Ok((tempfile, None)),
+  }
+}
+
+/// Move the `tempfile` into the active bucket under the given `name`, and
+/// return the resulting storage path.
 pub fn persist_tempfile<M>(
-  name: &str, tempfile: tempfile::NamedTempFile, metadata: M,
-) -> Result<String>
+  name: &ItemName, tempfile: tempfile::NamedTempFile, metadata: M,
+) -> Result<StoragePath>
+where
+  M: serde::Serialize,
+{
+  let active_bucket = config()?.with_bucket()?;
+
+  persist_tempfile_in(&active_bucket, name, tempfile, metadata)
+}
+
+/// Move the `tempfile` into the given `bucket` under the given `name`, and
+/// return the resulting storage path. The bucket must exist. The content is
+/// encrypted on the way if a [Keyring] is set.
+pub fn persist_tempfile_in<M>(
+  bucket: &BucketName, name: &ItemName, tempfile: tempfile::NamedTempFile, metadata: M,
+) -> Result<StoragePath>
 where
   M: serde::Serialize,
 {
   let config = config()?;
-  let active_bucket = config.with_bucket()?;
+  let plaintext_size = tempfile.as_file().metadata()?.len();
+  let tempfile = match keyring()? {
+    Some(keyring) => Item::encrypt(&tempfile, &keyring)?,
+    None => tempfile,
+  };
+  let size = tempfile.as_file().metadata()?.len();
 
-  Item::persist_tempfile(&config.root, &active_bucket, &name, tempfile)?;
+  write_item(&config.root, bucket, name, size, || {
+    Item::persist_tempfile(&config.root, bucket, name, tempfile)
+  })?;
 
-  let storage_path = internal::storage_path(&active_bucket, name);
+  let storage_path = StoragePath::new(bucket.clone(), name.clone());
+  config.index.insert_item(&storage_path, plaintext_size)?;
   internal::set_metadata(&storage_path, metadata)?;
 
   Ok(storage_path)
 }
 
+/// Replace the item at `storage_path` with the `tempfile`, the bucket must
+/// exist.
 pub fn replace_tempfile<M>(
-  storage_path: &str, tempfile: tempfile::NamedTempFile, metadata: M,
-) -> Result<String>
+  storage_path: &StoragePath, tempfile: tempfile::NamedTempFile, metadata: M,
+) -> Result<StoragePath>
 where
   M: serde::Serialize,
 {
-  let (active_bucket, name) = internal::bucket_and_item(storage_path)?;
+  persist_tempfile_in(&storage_path.bucket, &storage_path.item, tempfile, metadata)
+}
 
-  Item::persist_tempfile(&config()?.root, &active_bucket, &name, tempfile)?;
+/// Run the `write` of `size` bytes to the `item` of the `bucket` with the
+/// settings of the bucket applied: its max size is enforced, the current
+/// version of the item is archived if the bucket is versioned, and restored if
+/// the write fails, then the versions past the max of the bucket and the
+/// derivatives of the item are removed.
+///
+/// The writes of a bucket run one at a time, so that they cannot exceed its
+/// max size together.
+fn write_item(
+  root: &std::path::Path, bucket: &str, item: &str, size: u64, write: impl FnOnce() -> Result<()>,
+) -> Result<()> {
+  if !Bucket::exists(root, bucket) {
+    return Err(StorageError::BucketNotFound);
+  }
 
-  let storage_path = internal::storage_path(&active_bucket, name);
-  internal::set_metadata(&storage_path, metadata)?;
+  let _lock = config()?.write_lock.lock(bucket)?;
+  let settings = BucketSettings::from_file(root, bucket)?;
+  let archived = settings.versioning && Item::exists(root, bucket, item);
 
-  Ok(storage_path)
+  if let Some(max_size) = settings.max_size {
+    // an archived version keeps counting towards the size of the bucket
+    let replaced = match archived {
+      true => 0,
+      false => std::fs::metadata(Item::path(root, bucket, item)).map_or(0, |m| m.len()),
+    };
+    let stored = Bucket::total_bytes(root, bucket)? + Bucket::versions_bytes(root, bucket)?;
+
+    if stored.saturating_sub(replaced) + size > max_size {
+      return Err(StorageError::BucketFull);
+    }
+  }
+
+  let version = match archived {
+    true => Some(Item::archive_version(root, bucket, item)?),
+    false => None,
+  };
+
+  if let Err(e) = write() {
+    if let Some(version) = version {
+      Item::restore_version(root, bucket, item, &version)?;
+    }
+
+    return Err(e);
+  }
+
+  if let (true, Some(max_versions)) = (archived, settings.max_versions) {
+    Item::prune_versions(root, bucket, item, max_versions)?;
+  }
+
+  Item::remove_derivatives(root, bucket, item)
+}
+
+/// The previous versions of the items, kept by the buckets with `versioning`
+/// set in their [crate::BucketSettings]. The versions are named after the time
+/// they were archived at.
+pub mod versions {
+  use super::config;
+  use super::index_metadata;
+  use super::plaintext_size;
+  use super::write_item;
+  use super::Item;
+  use super::Metadata;
+  use super::Result;
+  use super::StoragePath;
+
+  /// Returns the previous versions of the item at `storage_path`, the oldest
+  /// first.
+  ///
+  /// ```rs
+  /// let versions = storage::versions::list(&"invoices/2023.pdf".parse()?)?;
+  /// ```
+  ///
+  /// If the item doesn't exist, a `NotFound` io error is returned.
+  pub fn list(storage_path: &StoragePath) -> Result<Vec<String>> {
+    let StoragePath { bucket, item } = storage_path;
+    let root = &config()?.root;
+
+    if !Item::exists(root, bucket, item) {
+      return Err(std::io::Error::from(std::io::ErrorKind::NotFound).into());
+    }
+
+    Item::versions(root, bucket, item)
+  }
+
+  /// Replace the item at `storage_path` and its metadata with its previous
+  /// `version`, that is no longer a version once restored. The replaced item is
+  /// archived as a new version if the bucket is versioned.
+  ///
+  /// If the item or the version doesn't exist, a `NotFound` io error is
+  /// returned.
+  pub fn restore(storage_path: &StoragePath, version: &str) -> Result<()> {
+    let StoragePath { bucket, item } = storage_path;
+    let config = config()?;
+    let root = &config.root;
+
+    if !list(storage_path)?.iter().any(|v| v == version) {
+      return Err(std::io::Error::from(std::io::ErrorKind::NotFound).into());
+    }
+
+    // the version already counts towards the size of the bucket
+    write_item(root, bucket, item, 0, || {
+      Metadata::remove(root, bucket, item)?;
+      Item::restore_version(root, bucket, item, version)
+    })?;
+
+    let path = Item::path(root, bucket, item);
+    config
+      .index
+      .insert_item(storage_path, plaintext_size(&path)?)?;
+    index_metadata(storage_path)
+  }
+
+  /// Remove every previous version of the item at `storage_path`.
+  ///
+  /// If the item doesn't exist, a `NotFound` io error is returned.
+  pub fn purge(storage_path: &StoragePath) -> Result<()> {
+    let StoragePath { bucket, item } = storage_path;
+    let config = config()?;
+
+    if !Item::exists(&config.root, bucket, item) {
+      return Err(std::io::Error::from(std::io::ErrorKind::NotFound).into());
+    }
+
+    let _lock = config.write_lock.lock(bucket.as_str())?;
+    Item::remove_versions(&config.root, bucket, item)
+  }
 }
 
 /// Internal functions that can be used to precisely control the storage system &
@@ -137,57 +539,74 @@ where
 pub mod internal {
   use std::path::PathBuf;
 
+  use super::BucketName;
   use super::Item;
+  use super::ItemName;
   use super::Metadata;
   use super::Result;
   use super::StorageError;
+  use super::StoragePath;
 
   use super::config;
 
   /// Get the name of the currently active bucket
-  pub fn active_bucket<'a>() -> Result<String> {
+  pub fn active_bucket() -> Result<BucketName> {
     config()?.with_bucket()
   }
 
+  /// Replace the active bucket with the next one the [crate::BucketPolicy]
+  /// decides on, even if the active bucket isn't full yet.
+  pub fn rotate_bucket() -> Result<BucketName> {
+    config()?.rotate()
+  }
+
   /// Get the path to the storage's root directory
   pub fn root<'a>() -> Result<&'a PathBuf> {
     Ok(&config()?.root)
   }
 
-  /// Parses and returns the `bucket` and the `item` name from the provided
-  /// `storage_path`
-  pub fn bucket_and_item(storage_path: &str) -> Result<(&str, &str)> {
-    let mut split = storage_path.split("/");
-    let (bucket, item) = (
-      split.next().ok_or(StorageError::ReadMissingBucket)?,
-      split.next().ok_or(StorageError::ReadMissingItem)?,
-    );
-
-    Ok((bucket, item))
-  }
-
-  /// Generates the storage path from the `bucket` and the `item`
-  pub fn storage_path(bucket: &str, name: &str) -> String {
-    Item::to_string(bucket, name)
-  }
-
   /// Forcefully write an `item` inside the provided `bucket`
-  pub fn write_exact(root: &PathBuf, bucket: &str, item: &str, content: &str) -> Result<String> {
+  pub fn write_exact(
+    root: &std::path::Path, bucket: &BucketName, item: &ItemName, content: &str,
+  ) -> Result<StoragePath> {
     Item::write(root, bucket, item, content)?;
+    Item::remove_derivatives(root, bucket, item)?;
+
+    let storage_path = StoragePath::new(bucket.clone(), item.clone());
+    config()?
+      .index
+      .insert_item(&storage_path, content.len() as u64)?;
 
-    Ok(storage_path(bucket, item))
+    Ok(storage_path)
   }
 
-  pub fn set_metadata<M>(storage_path: &str, metadata: M) -> Result<()>
+  /// Write the `metadata` of the item at `storage_path` in the configured
+  /// [crate::MetadataFormat], and index it if the item exists
+  pub fn set_metadata<M>(storage_path: &StoragePath, metadata: M) -> Result<()>
   where
     M: serde::Serialize,
   {
-    let (bucket, item) = bucket_and_item(storage_path)?;
+    let StoragePath { bucket, item } = storage_path;
+    let config = config()?;
 
     // There is no point in creating an empty metadata file
     if std::mem::size_of::<M>() > 0 {
-      let content = serde_yaml::to_string(&metadata)?;
-      Metadata::write(&config()?.root, bucket, item, &content)?;
+      Metadata::write(
+        &config.root,
+        bucket,
+        item,
+        config.metadata_format()?,
+        &metadata,
+      )?;
+
+      let path = Item::path(&config.root, bucket, item);
+      if path.exists() {
+        let metadata = serde_json::to_value(&metadata).map_err(StorageError::metadata)?;
+
+        config
+          .index
+          .set_metadata(storage_path, &path, Some(&metadata))?;
+      }
     };
 
     Ok(())
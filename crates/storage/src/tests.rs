@@ -1,14 +1,51 @@
+/// The storage configuration is global to the process, so every test shares
+/// the same root and runs one at a time on a freshly rotated bucket.
+const STORAGE: &str = "storage-test";
+static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Holds the storage for the duration of a test, and removes the buckets the
+/// test wrote when dropped
+struct TestStorage {
+  _guard: std::sync::MutexGuard<'static, ()>,
+}
+
+impl Drop for TestStorage {
+  fn drop(&mut self) {
+    let _ = crate::set_keyring(None);
+
+    for entry in std::fs::read_dir(STORAGE).into_iter().flatten().flatten() {
+      if entry.file_type().is_ok_and(|t| t.is_dir()) {
+        let _ = std::fs::remove_dir_all(entry.path());
+      }
+    }
+
+    let _ = crate::rebuild_index();
+  }
+}
+
+fn setup() -> crate::Result<TestStorage> {
+  let guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+  if crate::config().is_err() {
+    let _ = std::fs::remove_dir_all(STORAGE);
+    crate::initialize(STORAGE, Some(2))?;
+  }
+
+  crate::set_bucket_policy(crate::ItemCount::new(2))?;
+  crate::set_metadata_format(crate::MetadataFormat::default())?;
+  crate::set_keyring(None)?;
+  crate::config()?.rotate()?;
+
+  Ok(TestStorage { _guard: guard })
+}
+
 #[test]
 fn test_create_file() -> crate::Result<()> {
-  use std::path::Path;
-
-  const STORAGE: &'static str = "storage-test-test_create_file";
-  if let Err(_) = std::fs::remove_dir_all(STORAGE) {}
-  if let Err(_) = crate::initialize(STORAGE, Some(2)) {};
+  let _guard = setup()?;
 
-  let one = dbg!(crate::write("one.md", "content one", ())?);
-  let two = crate::write("two.text", "content two", ())?;
-  let three = crate::write("three.rs", "content three", ())?;
+  let one = dbg!(crate::write(&"one.md".parse()?, "content one", ())?);
+  let two = crate::write(&"two.text".parse()?, "content two", ())?;
+  let three = crate::write(&"three.rs".parse()?, "content three", ())?;
 
   let (_, path) = crate::read(&three)?;
   println!("path: {path:?}");
@@ -16,17 +53,11 @@ fn test_create_file() -> crate::Result<()> {
   let content = std::fs::read_to_string(path)?;
 
   // verify both one & two have the same bucket
-  let one = Path::new(&one).parent().unwrap();
-  let two = Path::new(&two).parent().unwrap();
-  assert_eq!(one, two);
-
-  let three = Path::new(&three).parent().unwrap();
-  assert_ne!(two, three);
+  assert_eq!(one.bucket, two.bucket);
+  assert_ne!(two.bucket, three.bucket);
 
   assert_eq!(content, "content three");
 
-  if let Err(_) = std::fs::remove_dir_all(STORAGE) {}
-
   Ok(())
 }
 
@@ -40,12 +71,10 @@ fn test_create_metadata_file() -> crate::Result<()> {
     alias: String,
   }
 
-  const STORAGE: &'static str = "storage-test-test_create_metadata_file";
-  if let Err(_) = std::fs::remove_dir_all(STORAGE) {}
-  if let Err(_) = crate::initialize(STORAGE, Some(2)) {};
+  let _guard = setup()?;
 
   let one = crate::write(
-    "one.md",
+    &"one.md".parse()?,
     "content one",
     Some(TestMetadata {
       alias: "an-alias.md".to_owned(),
@@ -59,7 +88,859 @@ fn test_create_metadata_file() -> crate::Result<()> {
   let metadata = metadata.unwrap();
   assert_eq!(metadata.alias, "an-alias.md");
 
-  if let Err(_) = std::fs::remove_dir_all(STORAGE) {}
+  Ok(())
+}
+
+#[test]
+fn test_item_count_ignores_metadata_files() -> crate::Result<()> {
+  let _guard = setup()?;
+
+  let one = crate::write(&"one.md".parse()?, "content one", "alias one")?;
+  let two = crate::write(&"two.md".parse()?, "content two", "alias two")?;
+  let three = crate::write(&"three.md".parse()?, "content three", "alias three")?;
+
+  assert_eq!(one.bucket, two.bucket);
+  assert_ne!(two.bucket, three.bucket);
+
+  Ok(())
+}
+
+#[test]
+fn test_periodic_policy() -> crate::Result<()> {
+  let _guard = setup()?;
+
+  let date = chrono::NaiveDate::from_ymd_opt(2023, 12, 31).unwrap();
+  assert_eq!(crate::Periodic::Daily.bucket_name(date), "2023-12-31");
+  assert_eq!(crate::Periodic::Monthly.bucket_name(date), "2023-12");
+
+  crate::set_bucket_policy(crate::Periodic::Daily)?;
+
+  let one = crate::write(&"one.md".parse()?, "content one", ())?;
+  let today = chrono::Utc::now().date_naive();
+  let expected = format!("{}/one.md", crate::Periodic::Daily.bucket_name(today));
+
+  assert_eq!(one, expected.parse()?);
+
+  Ok(())
+}
+
+#[test]
+fn test_custom_naming() -> crate::Result<()> {
+  let _guard = setup()?;
+
+  crate::set_bucket_policy(crate::ItemCount::new(1).with_naming(|| "archive".to_owned()))?;
+  let bucket = crate::internal::rotate_bucket()?;
+  assert!(bucket.starts_with("archive"));
+
+  let one = crate::write(&"one.md".parse()?, "content one", ())?;
+  let two = crate::write(&"two.md".parse()?, "content two", ())?;
+
+  // the name is already used, so a suffix is appended to it
+  assert_eq!(
+    one,
+    crate::StoragePath::new(bucket.clone(), "one.md".parse()?)
+  );
+  assert!(two.bucket.starts_with("archive-"));
+  assert_ne!(two.bucket, bucket);
+
+  Ok(())
+}
+
+/// Returns a tempfile holding the `content`, in the storage root so it can be
+/// persisted into a bucket
+fn tempfile(content: &str) -> crate::Result<tempfile::NamedTempFile> {
+  use std::io::Write;
+
+  let mut file = tempfile::NamedTempFile::new_in(STORAGE)?;
+  file.write_all(content.as_bytes())?;
+
+  Ok(file)
+}
+
+#[test]
+fn test_bucket_management() -> crate::Result<()> {
+  use crate::buckets;
+
+  let _guard = setup()?;
+
+  let name: crate::BucketName = format!("named-{}", nanoid::nanoid!(8)).parse()?;
+  let renamed: crate::BucketName = format!("{name}-renamed").parse()?;
+  let settings = crate::BucketSettings {
+    visibility: crate::Visibility::Private,
+    ..Default::default()
+  };
+
+  buckets::create(&name, settings.clone())?;
+  assert!(matches!(
+    buckets::create(&name, settings.clone()),
+    Err(crate::StorageError::BucketAlreadyExists)
+  ));
+
+  crate::persist_tempfile_in(&name, &"one.md".parse()?, tempfile("content one")?, "alias")?;
+
+  let info = buckets::describe(&name)?;
+  assert_eq!(info.settings, settings);
+  assert_eq!(info.items, 1);
+  assert!(!info.active);
+
+  buckets::rename(&name, &renamed)?;
+  assert!(matches!(
+    buckets::describe(&name),
+    Err(crate::StorageError::BucketNotFound)
+  ));
+  assert!(crate::exists(&crate::StoragePath::new(
+    renamed.clone(),
+    "one.md".parse()?
+  ))?);
+
+  assert!(matches!(
+    buckets::delete(&renamed, false),
+    Err(crate::StorageError::BucketNotEmpty)
+  ));
+  buckets::delete(&renamed, true)?;
+  assert!(!buckets::list()?.contains(&renamed));
+
+  Ok(())
+}
+
+#[test]
+fn test_bucket_settings_enforcement() -> crate::Result<()> {
+  use crate::buckets;
+
+  let _guard = setup()?;
+
+  let name: crate::BucketName = format!("settings-{}", nanoid::nanoid!(8)).parse()?;
+  buckets::create(
+    &name,
+    crate::BucketSettings {
+      max_size: Some(25),
+      versioning: true,
+      ..Default::default()
+    },
+  )?;
+
+  let path = crate::persist_tempfile_in(&name, &"one.md".parse()?, tempfile("0123456789")?, ())?;
+
+  // the replaced content is kept as a version, that still counts against the
+  // max size
+  crate::replace_tempfile(&path, tempfile("9876543210")?, ())?;
+  assert!(matches!(
+    crate::persist_tempfile_in(&name, &"two.md".parse()?, tempfile("0123456789")?, ()),
+    Err(crate::StorageError::BucketFull)
+  ));
+
+  let (_, file) = crate::read(&path)?;
+  assert_eq!(std::fs::read_to_string(file)?, "9876543210");
+
+  let versions = crate::internal::root()?
+    .join(name.as_str())
+    .join(".versions")
+    .join("one.md");
+  assert_eq!(std::fs::read_dir(&versions)?.count(), 1);
+
+  // a write that fails puts the archived version back in place, a folder
+  // cannot be copied over the item
+  let folder = crate::StoragePath::new(name.clone(), "folder.md".parse()?);
+  std::fs::create_dir(crate::internal::root()?.join(folder.to_string()))?;
+  assert!(crate::copy(&folder, &path).is_err());
+
+  let (_, file) = crate::read(&path)?;
+  assert_eq!(std::fs::read_to_string(file)?, "9876543210");
+  assert_eq!(std::fs::read_dir(&versions)?.count(), 1);
+
+  buckets::delete(&name, true)?;
 
   Ok(())
 }
+
+#[test]
+fn test_versions() -> crate::Result<()> {
+  use crate::buckets;
+  use crate::versions;
+
+  let _guard = setup()?;
+
+  let name: crate::BucketName = format!("versions-{}", nanoid::nanoid!(8)).parse()?;
+  buckets::create(
+    &name,
+    crate::BucketSettings {
+      versioning: true,
+      max_versions: Some(2),
+      ..Default::default()
+    },
+  )?;
+
+  let content = |path: &crate::StoragePath| -> crate::Result<String> {
+    Ok(std::fs::read_to_string(crate::read(path)?.1)?)
+  };
+
+  let path = crate::persist_tempfile_in(&name, &"one.md".parse()?, tempfile("0")?, "zero")?;
+  for content in ["1", "2", "3"] {
+    crate::replace_tempfile(&path, tempfile(content)?, content)?;
+  }
+
+  // the oldest versions are removed past the max of the bucket
+  let kept = versions::list(&path)?;
+  assert_eq!(kept.len(), 2);
+
+  // the restored version replaces the item, that becomes a version
+  versions::restore(&path, &kept[0])?;
+  assert_eq!(content(&path)?, "1");
+  assert_eq!(
+    crate::deserialize_metadata::<String>(&path)?.as_deref(),
+    Some("1")
+  );
+  assert_eq!(versions::list(&path)?.len(), 2);
+  assert!(!versions::list(&path)?.contains(&kept[0]));
+
+  for version in [kept[0].as_str(), "../one.md", ".bucket.yaml"] {
+    assert!(versions::restore(&path, version).is_err());
+  }
+
+  versions::purge(&path)?;
+  assert!(versions::list(&path)?.is_empty());
+
+  // the versions are removed with their item, and when it expires
+  let folder = crate::internal::root()?
+    .join(name.as_str())
+    .join(".versions");
+  crate::replace_tempfile(&path, tempfile("4")?, ())?;
+  crate::remove(&path)?;
+  assert!(!folder.join("one.md").exists());
+  assert!(versions::list(&path).is_err());
+
+  let path = crate::persist_tempfile_in(&name, &"two.md".parse()?, tempfile("0")?, ())?;
+  crate::replace_tempfile(&path, tempfile("1")?, ())?;
+  assert!(folder.join("two.md").exists());
+
+  let mut settings = buckets::settings(&name)?;
+  settings.ttl = Some(0);
+  buckets::set_settings(&name, settings)?;
+  std::thread::sleep(std::time::Duration::from_millis(10));
+
+  buckets::remove_expired()?;
+  assert!(!folder.join("two.md").exists());
+
+  buckets::delete(&name, true)?;
+
+  Ok(())
+}
+
+#[test]
+fn test_concurrent_writes_respect_max_size() -> crate::Result<()> {
+  use crate::buckets;
+
+  let _guard = setup()?;
+
+  let name: crate::BucketName = format!("concurrent-{}", nanoid::nanoid!(8)).parse()?;
+  buckets::create(
+    &name,
+    crate::BucketSettings {
+      max_size: Some(50),
+      ..Default::default()
+    },
+  )?;
+
+  // the writes start together to race for the remaining space
+  let barrier = std::sync::Arc::new(std::sync::Barrier::new(32));
+  let threads: Vec<_> = (0..32)
+    .map(|i| {
+      let name = name.clone();
+      let barrier = barrier.clone();
+      let tempfile = tempfile("0123456789");
+
+      std::thread::spawn(move || {
+        barrier.wait();
+        crate::persist_tempfile_in(&name, &format!("{i}.md").parse()?, tempfile?, ())
+      })
+    })
+    .collect();
+
+  let stored = threads
+    .into_iter()
+    .map(|thread| thread.join().expect("the write panicked"))
+    .filter(Result::is_ok)
+    .count();
+
+  assert_eq!(stored, 5);
+  assert_eq!(buckets::describe(&name)?.bytes, 50);
+
+  buckets::delete(&name, true)?;
+
+  Ok(())
+}
+
+#[test]
+fn test_metadata_formats() -> crate::Result<()> {
+  use crate::MetadataFormat;
+
+  #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+  struct TestMetadata {
+    alias: String,
+    version: String,
+    size: u64,
+  }
+
+  let _guard = setup()?;
+  let metadata = TestMetadata {
+    alias: "one.md".to_owned(),
+    // a string YAML would read back as a float if it wasn't quoted
+    version: "1.10".to_owned(),
+    size: 42,
+  };
+
+  crate::set_metadata_format(MetadataFormat::Json)?;
+  let path = crate::write(&"one.md".parse()?, "content one", &metadata)?;
+  let (_, file) = crate::read_metadata(&path)?;
+  assert!(file.to_string_lossy().ends_with(".metadata.json"));
+
+  // the sidecar is replaced, and its format detected from its extension
+  for format in [MetadataFormat::Cbor, MetadataFormat::MessagePack] {
+    crate::set_metadata_format(format)?;
+    crate::internal::set_metadata(&path, &metadata)?;
+    crate::set_metadata_format(MetadataFormat::Yaml)?;
+
+    let (_, file) = crate::read_metadata(&path)?;
+    assert!(file.to_string_lossy().ends_with(format.extension()));
+    assert_eq!(crate::deserialize_metadata(&path)?, Some(metadata.clone()));
+  }
+
+  let root = crate::internal::root()?.join(path.bucket.as_str());
+  assert_eq!(crate::migrate_metadata(&root, MetadataFormat::Yaml)?, 1);
+  assert_eq!(crate::migrate_metadata(&root, MetadataFormat::Yaml)?, 0);
+
+  let (_, file) = crate::read_metadata(&path)?;
+  assert!(file.to_string_lossy().ends_with(".metadata.yaml"));
+  assert_eq!(
+    crate::deserialize_metadata::<TestMetadata>(&path)?,
+    Some(metadata)
+  );
+
+  Ok(())
+}
+
+#[test]
+fn test_update_metadata() -> crate::Result<()> {
+  let _guard = setup()?;
+
+  let path = crate::write(&"counted.md".parse()?, "content", ())?;
+  let increment = |count: Option<u64>| Ok::<_, crate::StorageError>(count.unwrap_or(0) + 1);
+
+  let threads: Vec<_> = (0..8)
+    .map(|_| {
+      let path = path.clone();
+      std::thread::spawn(move || crate::update_metadata(&path, increment))
+    })
+    .collect();
+
+  for thread in threads {
+    thread.join().expect("the update panicked")?;
+  }
+
+  assert_eq!(crate::deserialize_metadata::<u64>(&path)?, Some(8));
+
+  let missing = crate::StoragePath::new(path.bucket, "missing.md".parse()?);
+  assert!(crate::update_metadata(&missing, increment).is_err());
+  assert!(!crate::internal::root()?
+    .join(missing.bucket.as_str())
+    .join("missing.md.metadata.yaml")
+    .exists());
+
+  Ok(())
+}
+
+#[test]
+fn test_copy_and_rename() -> crate::Result<()> {
+  let _guard = setup()?;
+
+  let from = crate::write(&"original.md".parse()?, "content", "metadata")?;
+  let to = crate::StoragePath::new(from.bucket.clone(), "copied.md".parse()?);
+  crate::copy(&from, &to)?;
+
+  let content = std::fs::read_to_string(crate::internal::root()?.join(to.to_string()))?;
+  assert_eq!(content, "content");
+  assert_eq!(
+    crate::deserialize_metadata::<String>(&to)?.as_deref(),
+    Some("metadata")
+  );
+  assert!(crate::exists(&from)?);
+
+  let moved = crate::StoragePath::new(from.bucket.clone(), "moved.md".parse()?);
+  crate::rename(&to, &moved)?;
+
+  assert!(!crate::exists(&to)?);
+  assert_eq!(
+    crate::deserialize_metadata::<String>(&moved)?.as_deref(),
+    Some("metadata")
+  );
+  assert!(crate::deserialize_metadata::<String>(&to)?.is_none());
+
+  let missing = crate::StoragePath::new(from.bucket, "missing.md".parse()?);
+  assert!(crate::copy(&missing, &to).is_err());
+  assert!(crate::rename(&missing, &to).is_err());
+
+  Ok(())
+}
+
+#[test]
+fn test_derivatives() -> crate::Result<()> {
+  let _guard = setup()?;
+
+  let name: crate::ItemName = "photo.png".parse()?;
+  let path = crate::write(&name, "image", ())?;
+  let thumbnail: crate::ItemName = "64x64.png".parse()?;
+
+  assert!(crate::read_derivative(&path, &thumbnail)?.is_none());
+
+  crate::write_derivative(&path, &thumbnail, b"thumbnail", 8)?;
+  let (_, derivative) =
+    crate::read_derivative(&path, &thumbnail)?.expect("the derivative is cached");
+  assert_eq!(std::fs::read_to_string(derivative)?, "thumbnail");
+  assert_eq!(crate::buckets::items(&path.bucket)?, vec![name.clone()]);
+
+  // past the limit, the derivatives written the longest ago are evicted
+  let sizes: Vec<crate::ItemName> = vec!["128x128.png".parse()?, "256x256.png".parse()?];
+  for size in &sizes {
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    crate::write_derivative(&path, size, b"thumbnail", 2)?;
+  }
+  assert!(crate::read_derivative(&path, &thumbnail)?.is_none());
+  for size in &sizes {
+    assert!(crate::read_derivative(&path, size)?.is_some());
+  }
+
+  // replacing the item invalidates its derivatives
+  crate::internal::write_exact(crate::internal::root()?, &path.bucket, &name, "new image")?;
+  assert!(crate::read_derivative(&path, &thumbnail)?.is_none());
+
+  crate::write_derivative(&path, &thumbnail, b"thumbnail", 8)?;
+  crate::remove(&path)?;
+  assert!(crate::read_derivative(&path, &thumbnail)?.is_none());
+  assert!(crate::write_derivative(&path, &thumbnail, b"thumbnail", 8).is_err());
+
+  Ok(())
+}
+
+#[test]
+fn test_compression() -> crate::Result<()> {
+  use std::io::Read;
+
+  let _guard = setup()?;
+  let bucket: crate::BucketName = "compressed".parse()?;
+  let compression = crate::Compression::new(crate::Encoding::Zstd);
+  crate::buckets::create(
+    &bucket,
+    crate::BucketSettings {
+      compression: Some(compression.clone()),
+      ..Default::default()
+    },
+  )?;
+
+  let content = "compressible ".repeat(200);
+  let (compressed, encoding) =
+    crate::compress_tempfile(&bucket, tempfile(&content)?, "text/plain")?;
+  assert_eq!(encoding, Some(crate::Encoding::Zstd));
+  assert!(compressed.as_file().metadata()?.len() < content.len() as u64);
+
+  let mut decoded = String::new();
+  crate::Encoding::Zstd
+    .decoder(compressed.reopen()?)?
+    .read_to_string(&mut decoded)?;
+  assert_eq!(decoded, content);
+
+  // media and small items are stored as they are
+  let (_, encoding) = crate::compress_tempfile(&bucket, tempfile(&content)?, "image/png")?;
+  assert_eq!(encoding, None);
+  let (_, encoding) = crate::compress_tempfile(&bucket, tempfile("short")?, "text/plain")?;
+  assert_eq!(encoding, None);
+  assert_eq!(
+    compression.encoding_for(1024, "text/markdown"),
+    Some(crate::Encoding::Zstd)
+  );
+
+  Ok(())
+}
+
+#[test]
+fn test_encryption() -> crate::Result<()> {
+  use std::io::Read;
+
+  let _guard = setup()?;
+  let old_key = crate::MasterKey::new([1; 32]);
+  let new_key: crate::MasterKey = "02".repeat(32).parse()?;
+  crate::set_keyring(Some(crate::Keyring::new(old_key.clone())))?;
+
+  let bucket = crate::internal::active_bucket()?;
+  let metadata = serde_json::json!({ "alias": "secret alias" });
+  let mut paths = Vec::new();
+
+  // around and exactly on the boundaries of the encrypted chunks
+  for size in [0, 10, 64 * 1024, 200_000] {
+    let content = "s".repeat(size);
+    let name: crate::ItemName = format!("secret-{size}.txt").parse()?;
+    let path = crate::persist_tempfile_in(&bucket, &name, tempfile(&content)?, &metadata)?;
+
+    let (mut file, _) = crate::read(&path)?;
+    let mut stored = Vec::new();
+    file.read_to_end(&mut stored)?;
+    assert!(size == 0 || !stored.windows(10).any(|w| w == b"ssssssssss"));
+
+    let (mut decrypted, _) = crate::open(&path)?;
+    assert_eq!(decrypted.size()?, size as u64);
+    let mut read = String::new();
+    decrypted.read_to_string(&mut read)?;
+    assert_eq!(read, content);
+
+    paths.push(path);
+  }
+
+  let (metadata_file, _) = crate::read_metadata(&paths[0])?;
+  let mut stored = String::new();
+  let _ = metadata_file
+    .expect("the metadata is written")
+    .read_to_string(&mut stored);
+  assert!(!stored.contains("secret alias"));
+  assert_eq!(
+    crate::deserialize_metadata::<serde_json::Value>(&paths[0])?,
+    Some(metadata)
+  );
+
+  // the data keys are rewrapped by the new key, the old one is then useless
+  crate::set_keyring(Some(
+    crate::Keyring::new(new_key.clone()).with_previous(old_key.clone()),
+  ))?;
+  assert!(crate::rotate_keys(crate::internal::root()?)? >= 8);
+  assert_eq!(crate::rotate_keys(crate::internal::root()?)?, 0);
+
+  crate::set_keyring(Some(crate::Keyring::new(new_key)))?;
+  let mut read = String::new();
+  crate::open(&paths[1])?.0.read_to_string(&mut read)?;
+  assert_eq!(read, "s".repeat(10));
+
+  crate::set_keyring(Some(crate::Keyring::new(old_key)))?;
+  assert!(matches!(
+    crate::open(&paths[1]),
+    Err(crate::StorageError::UnknownDataKey)
+  ));
+
+  crate::set_keyring(None)?;
+  assert!(matches!(
+    crate::deserialize_metadata::<serde_json::Value>(&paths[1]),
+    Err(crate::StorageError::KeyringNotSet)
+  ));
+  for path in &paths {
+    crate::remove(path)?;
+  }
+
+  Ok(())
+}
+
+#[test]
+fn test_metadata_index() -> crate::Result<()> {
+  use crate::Filter;
+  use crate::FilterOp;
+  use crate::Query;
+
+  let _guard = setup()?;
+  let bucket: crate::BucketName = "indexed".parse()?;
+  crate::buckets::create(&bucket, crate::BucketSettings::default())?;
+
+  for (name, project_id) in [("a.md", 42), ("b.md", 42), ("c.md", 7)] {
+    let metadata = serde_json::json!({ "alias": name, "custom": { "project_id": project_id } });
+    crate::persist_tempfile_in(&bucket, &name.parse()?, tempfile(name)?, metadata)?;
+  }
+
+  let by_project = |project_id: i64| -> crate::Result<Query> {
+    Ok(Query {
+      bucket: Some(bucket.clone()),
+      filters: vec![Filter::new(
+        "custom.project_id".parse()?,
+        FilterOp::Eq,
+        project_id,
+      )],
+      ..Default::default()
+    })
+  };
+  let paths = |query: &Query| -> crate::Result<Vec<String>> {
+    let page = crate::query(query)?;
+    Ok(page.items.iter().map(|hit| hit.path.to_string()).collect())
+  };
+
+  assert_eq!(paths(&by_project(42)?)?, ["indexed/a.md", "indexed/b.md"]);
+
+  // the pages follow each other through the `next` path
+  let first = crate::query(&Query {
+    limit: Some(1),
+    ..by_project(42)?
+  })?;
+  assert_eq!(first.items.len(), 1);
+  let second = crate::query(&Query {
+    limit: Some(1),
+    after: first.next,
+    ..by_project(42)?
+  })?;
+  assert_eq!(second.items[0].path.to_string(), "indexed/b.md");
+  assert!(second.next.is_none());
+
+  // the index follows the metadata updates, removals and renames
+  let c = "indexed/c.md".parse()?;
+  crate::internal::set_metadata(&c, serde_json::json!({ "custom": { "project_id": 42 } }))?;
+  crate::remove(&"indexed/a.md".parse()?)?;
+  assert_eq!(paths(&by_project(42)?)?, ["indexed/b.md", "indexed/c.md"]);
+
+  let renamed: crate::BucketName = "indexed-renamed".parse()?;
+  crate::buckets::rename(&bucket, &renamed)?;
+  assert!(paths(&by_project(42)?)?.is_empty());
+
+  let size = Query {
+    bucket: Some(renamed.clone()),
+    filters: vec![Filter::new("size".parse()?, FilterOp::Gte, 1)],
+    ..Default::default()
+  };
+  assert_eq!(paths(&size)?.len(), 2);
+  assert!(crate::rebuild_index()? >= 2);
+  assert_eq!(paths(&size)?.len(), 2);
+
+  crate::buckets::delete(&renamed, true)?;
+  assert!(paths(&size)?.is_empty());
+
+  assert!("custom..id".parse::<crate::QueryField>().is_err());
+
+  Ok(())
+}
+
+#[test]
+fn test_sealed_metadata_index() -> crate::Result<()> {
+  use crate::Filter;
+  use crate::FilterOp;
+  use crate::Query;
+
+  let _guard = setup()?;
+  crate::set_keyring(Some(crate::Keyring::new(crate::MasterKey::new([3; 32]))))?;
+  let bucket: crate::BucketName = "sealed".parse()?;
+  crate::buckets::create(&bucket, crate::BucketSettings::default())?;
+
+  let content = "s".repeat(100);
+  let metadata = serde_json::json!({
+    "alias": "confidential alias",
+    "custom": { "project": "confidential project" },
+  });
+  crate::persist_tempfile_in(&bucket, &"a.md".parse()?, tempfile(&content)?, &metadata)?;
+
+  // a compressed item records the size of its decompressed content
+  let compressed = serde_json::json!({ "alias": "b.md", "system": { "size": 1000 } });
+  crate::persist_tempfile_in(&bucket, &"b.md".parse()?, tempfile("short")?, &compressed)?;
+
+  let index = std::fs::read(std::path::Path::new(STORAGE).join(".index.sqlite"))?;
+  assert!(!index.windows(12).any(|w| w == b"confidential"));
+
+  let query = |field: &str, op: FilterOp, value: serde_json::Value| -> crate::Result<Vec<u64>> {
+    let page = crate::query(&Query {
+      bucket: Some(bucket.clone()),
+      filters: vec![Filter::new(field.parse()?, op, value)],
+      ..Default::default()
+    })?;
+    Ok(page.items.iter().map(|hit| hit.size).collect())
+  };
+
+  // the sealed metadata is still queried, and the sizes are those of the
+  // content rather than of the stored files
+  assert_eq!(
+    query(
+      "custom.project",
+      FilterOp::Eq,
+      "confidential project".into()
+    )?,
+    [100]
+  );
+  assert_eq!(query("size", FilterOp::Eq, 100.into())?, [100]);
+  assert_eq!(query("size", FilterOp::Gt, 100.into())?, [1000]);
+
+  assert!(crate::rebuild_index()? >= 2);
+  let index = std::fs::read(std::path::Path::new(STORAGE).join(".index.sqlite"))?;
+  assert!(!index.windows(12).any(|w| w == b"confidential"));
+  assert_eq!(query("size", FilterOp::Lte, 1000.into())?, [100, 1000]);
+
+  crate::buckets::delete(&bucket, true)?;
+
+  Ok(())
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn test_nonblocking_item_writer() -> crate::Result<()> {
+  use tokio::io::AsyncReadExt;
+  use tokio::io::AsyncWriteExt;
+
+  let _guard = setup()?;
+  let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+
+  runtime.block_on(async {
+    let mut writer = crate::nonblocking::ItemWriter::new().await?;
+    writer.write_all(b"streamed content").await?;
+    let path = writer.persist(&"streamed.md".parse()?, "alias").await?;
+
+    let (mut file, _) = crate::nonblocking::read(&path).await?;
+    let mut content = String::new();
+    file.read_to_string(&mut content).await?;
+    assert_eq!(content, "streamed content");
+
+    let alias: Option<String> = crate::nonblocking::deserialize_metadata(&path).await?;
+    assert_eq!(alias.as_deref(), Some("alias"));
+
+    crate::nonblocking::remove(&path).await?;
+    assert!(!crate::nonblocking::exists(&path).await?);
+
+    Ok(())
+  })
+}
+
+/// Returns whether the `path` is made of the `root` followed by `depth` plain
+/// components, none of them hidden
+fn is_contained(root: &std::path::Path, path: &std::path::Path, depth: usize) -> bool {
+  use std::path::Component;
+
+  let Ok(relative) = path.strip_prefix(root) else {
+    return false;
+  };
+
+  let components: Vec<_> = relative.components().collect();
+
+  components.len() == depth
+    && components.iter().all(|c| match c {
+      Component::Normal(name) => !name.to_string_lossy().starts_with('.'),
+      _ => false,
+    })
+}
+
+#[test]
+fn test_invalid_names() {
+  let invalid = [
+    "",
+    ".",
+    "..",
+    "../etc",
+    "/etc",
+    "a/b",
+    "a\\b",
+    "a\0b",
+    ".storage",
+    ".bucket.yaml",
+    "é",
+    "a b",
+  ];
+
+  for name in invalid {
+    assert!(crate::BucketName::new(name).is_err(), "{name:?}");
+    assert!(crate::ItemName::new(name).is_err(), "{name:?}");
+  }
+
+  assert!(crate::BucketName::new("a".repeat(201)).is_err());
+  assert!(crate::ItemName::new("one.md.metadata.yaml").is_err());
+
+  // the names are validated apart from the storage, they must still refuse
+  // the metadata files of every format
+  for format in crate::MetadataFormat::ALL {
+    assert!(crate::ItemName::new(format!("one.md{}", format.extension())).is_err());
+  }
+  assert!(serde_yaml::from_str::<crate::BucketName>("../etc").is_err());
+}
+
+#[test]
+fn test_storage_path_parsing() -> crate::Result<()> {
+  use crate::StorageError;
+  use crate::StoragePath;
+
+  let path: StoragePath = "bucket/one.md".parse()?;
+  assert_eq!(path.bucket.as_str(), "bucket");
+  assert_eq!(path.item.as_str(), "one.md");
+  assert_eq!(path.to_string(), "bucket/one.md");
+
+  let cases = [
+    ("", StorageError::MissingBucketName),
+    ("/one.md", StorageError::MissingBucketName),
+    ("bucket", StorageError::MissingItemName),
+    ("bucket/", StorageError::MissingItemName),
+    ("bucket/one.md/extra", StorageError::TrailingPathSegments),
+    ("../passwd", StorageError::InvalidBucketName),
+    ("bucket/..", StorageError::InvalidItemName),
+  ];
+
+  for (input, expected) in cases {
+    let error = StorageError::from(input.parse::<StoragePath>().unwrap_err());
+    assert_eq!(
+      std::mem::discriminant(&error),
+      std::mem::discriminant(&expected),
+      "{input:?}"
+    );
+  }
+
+  assert!(serde_yaml::from_str::<StoragePath>("bucket/one.md/extra").is_err());
+
+  Ok(())
+}
+
+mod properties {
+  use proptest::prelude::*;
+
+  use super::is_contained;
+
+  /// Segments mixing the allowed charset with the characters that have a
+  /// meaning for the filesystems
+  fn segment() -> impl Strategy<Value = String> {
+    prop_oneof![
+      "[A-Za-z0-9._-]{0,12}",
+      "[./\\\\\0~:A-Za-z]{0,12}",
+      Just("..".to_owned()),
+      Just(".".to_owned()),
+      any::<String>(),
+    ]
+  }
+
+  proptest! {
+    #[test]
+    fn valid_names_are_a_single_component(name in segment()) {
+      let root = std::path::Path::new("/srv/root");
+
+      if let Ok(bucket) = crate::BucketName::new(name.clone()) {
+        prop_assert!(is_contained(root, &crate::Bucket::path(root, &bucket), 1));
+        prop_assert_eq!(bucket.as_str(), name.as_str());
+      }
+
+      if let Ok(item) = crate::ItemName::new(name.clone()) {
+        prop_assert!(is_contained(root, &root.join(item.as_str()), 1));
+      }
+    }
+
+    #[test]
+    fn storage_paths_stay_in_the_root(segments in prop::collection::vec(segment(), 0..4)) {
+      let root = std::path::Path::new("/srv/root");
+      let storage_path = segments.join("/");
+
+      if let Ok(crate::StoragePath { bucket, item }) = storage_path.parse() {
+        prop_assert!(is_contained(root, &crate::Item::path(root, &bucket, &item), 2));
+
+        for format in crate::MetadataFormat::ALL {
+          let path = crate::Metadata::path(root, &bucket, &item, format);
+          prop_assert!(is_contained(root, &path, 2));
+        }
+      }
+    }
+
+    #[test]
+    fn names_round_trip(name in "[A-Za-z0-9_-][A-Za-z0-9._-]{0,40}") {
+      let bucket: crate::BucketName = name.parse().unwrap();
+      let yaml = serde_yaml::to_string(&bucket).unwrap();
+
+      prop_assert_eq!(serde_yaml::from_str::<crate::BucketName>(&yaml).unwrap(), bucket);
+    }
+
+    #[test]
+    fn storage_paths_round_trip(segments in prop::collection::vec(segment(), 0..4)) {
+      if let Ok(path) = segments.join("/").parse::<crate::StoragePath>() {
+        prop_assert_eq!(path.to_string(), segments.join("/"));
+        prop_assert_eq!(path.to_string().parse::<crate::StoragePath>().unwrap(), path);
+      }
+    }
+  }
+}
@@ -0,0 +1,52 @@
+use crate::BucketName;
+use crate::StoragePath;
+
+/// The body of a `POST /v1/archive` request, that selects either the `paths`
+/// or the items of the `bucket` whose name starts with the `prefix`:
+///
+/// ```json
+/// { "bucket": "invoices", "prefix": "2023-", "format": "tar.gz" }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArchiveRequest {
+  pub paths: Vec<StoragePath>,
+  pub bucket: Option<BucketName>,
+  pub prefix: Option<String>,
+  pub format: ArchiveFormat,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ArchiveFormat {
+  #[default]
+  #[serde(rename = "zip")]
+  Zip,
+
+  #[serde(rename = "tar.gz")]
+  TarGz,
+}
+
+impl ArchiveFormat {
+  pub fn content_type(self) -> &'static str {
+    match self {
+      ArchiveFormat::Zip => "application/zip",
+      ArchiveFormat::TarGz => "application/gzip",
+    }
+  }
+
+  pub fn extension(self) -> &'static str {
+    match self {
+      ArchiveFormat::Zip => "zip",
+      ArchiveFormat::TarGz => "tar.gz",
+    }
+  }
+}
+
+/// An item created from a file of an archive uploaded with `?extract=true`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ExtractedItem {
+  /// The path of the file in the archive, that is the alias of the item
+  pub entry: String,
+
+  pub path: StoragePath,
+}
@@ -0,0 +1,52 @@
+use crate::StoragePath;
+
+/// An operation of a `POST /v1/batch` request, written with its `op`:
+///
+/// ```json
+/// { "op": "copy", "path": "invoices/2023.pdf", "destination": "archive/2023.pdf" }
+/// ```
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case", deny_unknown_fields)]
+pub enum BatchOperation {
+  Delete {
+    path: StoragePath,
+  },
+
+  /// Replace the custom metadata of the item, if its metadata is still at the
+  /// given `revision` when there is one
+  MetadataSet {
+    path: StoragePath,
+    custom: Option<serde_json::Value>,
+    #[serde(default)]
+    revision: Option<u64>,
+  },
+
+  MetadataGet {
+    path: StoragePath,
+  },
+
+  Copy {
+    path: StoragePath,
+    destination: StoragePath,
+  },
+
+  Stat {
+    path: StoragePath,
+  },
+}
+
+/// The outcome of a [BatchOperation], at the same index in the response as the
+/// operation is in the request
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct BatchResult {
+  /// The status code the operation would have had as a request of its own
+  pub status: u16,
+
+  /// The custom metadata for `metadata_get`, the [super::Stat] for `stat` and
+  /// the new revision for `metadata_set`
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub body: Option<serde_json::Value>,
+
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+}
@@ -0,0 +1,43 @@
+pub type Result<T> = std::result::Result<T, ParseError>;
+
+/// A name, a storage path or a query field that couldn't be parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+  /// The storage path has no bucket name before its `/`
+  MissingBucketName,
+
+  /// The storage path has no item name after its `/`
+  MissingItemName,
+
+  /// The storage path has more than a bucket and an item segment
+  TrailingPathSegments,
+
+  /// The name doesn't fit the allowed charset of [crate::BucketName]
+  InvalidBucketName,
+
+  /// The name doesn't fit the allowed charset of [crate::ItemName]
+  InvalidItemName,
+
+  /// The [crate::QueryField] has an empty key or a quote in its metadata path
+  InvalidQueryField,
+}
+
+impl std::fmt::Display for ParseError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ParseError::MissingBucketName => write!(f, "invalid storage path, missing bucket name"),
+      ParseError::MissingItemName => write!(f, "invalid storage path, missing item name"),
+      ParseError::TrailingPathSegments => {
+        write!(
+          f,
+          "invalid storage path, unexpected segments after the item name"
+        )
+      }
+      ParseError::InvalidBucketName => write!(f, "invalid bucket name"),
+      ParseError::InvalidItemName => write!(f, "invalid item name"),
+      ParseError::InvalidQueryField => write!(f, "invalid query: invalid metadata path"),
+    }
+  }
+}
+
+impl std::error::Error for ParseError {}
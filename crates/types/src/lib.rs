@@ -0,0 +1,33 @@
+//! The types shared by the storage, the server and the clients of its v1 API
+mod names;
+pub use names::*;
+
+mod path;
+pub use path::*;
+
+mod query;
+pub use query::*;
+
+mod error;
+pub use error::*;
+
+mod operation;
+pub use operation::Operation;
+
+mod stat;
+pub use stat::Stat;
+
+mod batch;
+pub use batch::BatchOperation;
+pub use batch::BatchResult;
+
+mod problem;
+pub use problem::Problem;
+
+mod archive;
+pub use archive::ArchiveFormat;
+pub use archive::ArchiveRequest;
+pub use archive::ExtractedItem;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,146 @@
+use crate::*;
+
+/// The extensions of the metadata files written next to the items, in every
+/// format the storage supports. No item can be named like one.
+pub const METADATA_EXTENSIONS: [&str; 4] = [
+  ".metadata.yaml",
+  ".metadata.json",
+  ".metadata.msgpack",
+  ".metadata.cbor",
+];
+
+/// The maximum length of a name, short enough for the metadata filename of an
+/// item to stay within the filename limit of the common filesystems.
+const MAX_LENGTH: usize = 200;
+
+/// Returns whether the `name` can be used as a single path segment under the
+/// storage root: it is made of ASCII alphanumerics, `-`, `_` and `.` only, and
+/// doesn't start with a `.` so it can't point to a parent folder nor to the
+/// hidden files of the storage.
+fn is_valid_name(name: &str) -> bool {
+  !name.is_empty()
+    && name.len() <= MAX_LENGTH
+    && !name.starts_with('.')
+    && name
+      .bytes()
+      .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+}
+
+/// Returns whether the `filename` is the one of a metadata file, in any format
+fn is_metadata_filename(filename: &str) -> bool {
+  METADATA_EXTENSIONS
+    .iter()
+    .any(|extension| filename.ends_with(extension))
+}
+
+/// The name of a bucket, always pointing to a folder directly inside of the
+/// storage root.
+///
+/// ```rs
+/// let bucket: shcs_types::BucketName = "invoices".parse()?;
+/// ```
+#[derive(
+  Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(try_from = "String", into = "String")]
+pub struct BucketName(String);
+
+/// The name of an item, always pointing to a file directly inside of its
+/// bucket. Names that would clash with the metadata file of another item are
+/// refused.
+///
+/// ```rs
+/// let item: shcs_types::ItemName = "report.pdf".parse()?;
+/// ```
+#[derive(
+  Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(try_from = "String", into = "String")]
+pub struct ItemName(String);
+
+impl BucketName {
+  pub fn new(name: impl Into<String>) -> Result<Self> {
+    let name = name.into();
+
+    match is_valid_name(&name) {
+      true => Ok(Self(name)),
+      false => Err(ParseError::InvalidBucketName),
+    }
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+}
+
+impl ItemName {
+  pub fn new(name: impl Into<String>) -> Result<Self> {
+    let name = name.into();
+
+    match is_valid_name(&name) && !is_metadata_filename(&name) {
+      true => Ok(Self(name)),
+      false => Err(ParseError::InvalidItemName),
+    }
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+}
+
+macro_rules! impl_name_traits {
+  ($name:ident) => {
+    impl TryFrom<String> for $name {
+      type Error = ParseError;
+
+      fn try_from(value: String) -> Result<Self> {
+        Self::new(value)
+      }
+    }
+
+    impl TryFrom<&str> for $name {
+      type Error = ParseError;
+
+      fn try_from(value: &str) -> Result<Self> {
+        Self::new(value)
+      }
+    }
+
+    impl std::str::FromStr for $name {
+      type Err = ParseError;
+
+      fn from_str(s: &str) -> Result<Self> {
+        Self::new(s)
+      }
+    }
+
+    impl From<$name> for String {
+      fn from(value: $name) -> Self {
+        value.0
+      }
+    }
+
+    impl AsRef<str> for $name {
+      fn as_ref(&self) -> &str {
+        &self.0
+      }
+    }
+
+    impl std::ops::Deref for $name {
+      type Target = str;
+
+      fn deref(&self) -> &str {
+        &self.0
+      }
+    }
+
+    impl std::fmt::Display for $name {
+      fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+      }
+    }
+  };
+}
+
+impl_name_traits!(BucketName);
+impl_name_traits!(ItemName);
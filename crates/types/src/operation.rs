@@ -0,0 +1,48 @@
+use std::fmt::Display;
+
+/// Describes the attempt to perform an operation
+#[derive(Debug, serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+#[repr(usize)]
+pub enum Operation {
+  Upload = 0,
+  Replace = 1,
+  ReplaceActive = 2,
+  MetadataSet = 3,
+  MetadataGet = 4,
+  Delete = 5,
+  Read = 6,
+  BucketCreate = 7,
+  BucketDelete = 8,
+  BucketRename = 9,
+  BucketDescribe = 10,
+  BucketUpdate = 11,
+  Query = 12,
+  Batch = 13,
+  Copy = 14,
+  Move = 15,
+  Archive = 16,
+}
+
+impl Display for Operation {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Operation::Upload => write!(f, "Upload"),
+      Operation::Replace => write!(f, "Replace"),
+      Operation::ReplaceActive => write!(f, "ReplaceActive"),
+      Operation::MetadataSet => write!(f, "MetadataSet"),
+      Operation::MetadataGet => write!(f, "MetadataGet"),
+      Operation::Delete => write!(f, "Delete"),
+      Operation::Read => write!(f, "Read"),
+      Operation::BucketCreate => write!(f, "BucketCreate"),
+      Operation::BucketDelete => write!(f, "BucketDelete"),
+      Operation::BucketRename => write!(f, "BucketRename"),
+      Operation::BucketDescribe => write!(f, "BucketDescribe"),
+      Operation::BucketUpdate => write!(f, "BucketUpdate"),
+      Operation::Query => write!(f, "Query"),
+      Operation::Batch => write!(f, "Batch"),
+      Operation::Copy => write!(f, "Copy"),
+      Operation::Move => write!(f, "Move"),
+      Operation::Archive => write!(f, "Archive"),
+    }
+  }
+}
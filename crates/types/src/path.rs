@@ -0,0 +1,72 @@
+use crate::*;
+
+/// The location of an item in the storage: the bucket holding it and its name
+/// inside of the bucket. It is written `{bucket}/{item}`:
+///
+/// ```rs
+/// let path: shcs_types::StoragePath = "qsdo34-23d/filename.md".parse()?;
+/// ```
+#[derive(
+  Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(try_from = "String", into = "String")]
+pub struct StoragePath {
+  pub bucket: BucketName,
+  pub item: ItemName,
+}
+
+impl StoragePath {
+  pub fn new(bucket: BucketName, item: ItemName) -> Self {
+    Self { bucket, item }
+  }
+}
+
+impl From<(BucketName, ItemName)> for StoragePath {
+  fn from((bucket, item): (BucketName, ItemName)) -> Self {
+    Self::new(bucket, item)
+  }
+}
+
+impl std::str::FromStr for StoragePath {
+  type Err = ParseError;
+
+  /// Parses a `{bucket}/{item}` path, anything else than exactly two valid
+  /// names separated by a `/` is refused
+  fn from_str(s: &str) -> Result<Self> {
+    let (bucket, item) = s.split_once('/').unwrap_or((s, ""));
+
+    if bucket.is_empty() {
+      return Err(ParseError::MissingBucketName);
+    }
+
+    if item.is_empty() {
+      return Err(ParseError::MissingItemName);
+    }
+
+    if item.contains('/') {
+      return Err(ParseError::TrailingPathSegments);
+    }
+
+    Ok(Self::new(BucketName::new(bucket)?, ItemName::new(item)?))
+  }
+}
+
+impl TryFrom<String> for StoragePath {
+  type Error = ParseError;
+
+  fn try_from(value: String) -> Result<Self> {
+    value.parse()
+  }
+}
+
+impl From<StoragePath> for String {
+  fn from(value: StoragePath) -> Self {
+    value.to_string()
+  }
+}
+
+impl std::fmt::Display for StoragePath {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}/{}", self.bucket, self.item)
+  }
+}
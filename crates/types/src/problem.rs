@@ -0,0 +1,26 @@
+/// The JSON body of the error responses of the v1 API, sent with the
+/// `application/problem+json` content type of RFC 9457:
+///
+/// ```json
+/// { "status": 404, "title": "Not Found", "detail": "item not found" }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Problem {
+  pub status: u16,
+
+  /// The reason phrase of the status
+  pub title: String,
+
+  /// What went wrong, left out for the internal errors of the server
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub detail: Option<String>,
+}
+
+impl std::fmt::Display for Problem {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match &self.detail {
+      Some(detail) => write!(f, "{} {}: {detail}", self.status, self.title),
+      None => write!(f, "{} {}", self.status, self.title),
+    }
+  }
+}
@@ -0,0 +1,160 @@
+use crate::*;
+
+/// Selects the items whose fields match every one of the `filters`, sorted by
+/// their storage path.
+///
+/// ```rs
+/// let query = shcs_types::Query {
+///   filters: vec![shcs_types::Filter::new("custom.project_id".parse()?, shcs_types::FilterOp::Eq, 42)],
+///   ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Query {
+  /// Only select the items of this bucket
+  pub bucket: Option<BucketName>,
+
+  pub filters: Vec<Filter>,
+
+  /// The maximum number of items in the page, the storage has a default and a
+  /// maximum for it
+  pub limit: Option<usize>,
+
+  /// Only select the items after this storage path, the `next` path of the
+  /// previous [QueryPage]
+  pub after: Option<StoragePath>,
+}
+
+/// Compares a field of the items to a value:
+///
+/// ```json
+/// { "field": "size", "op": "gte", "value": 1024 }
+/// ```
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Filter {
+  pub field: QueryField,
+  pub op: FilterOp,
+
+  /// A string, a number, a boolean or null. The `uploaded_at` field is
+  /// compared to unix timestamps, but RFC 3339 dates are accepted too.
+  pub value: serde_json::Value,
+}
+
+impl Filter {
+  pub fn new(field: QueryField, op: FilterOp, value: impl Into<serde_json::Value>) -> Self {
+    Self {
+      field,
+      op,
+      value: value.into(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterOp {
+  Eq,
+  Ne,
+  Lt,
+  Lte,
+  Gt,
+  Gte,
+}
+
+/// The field of the items a [Filter] compares, written `size`, `uploaded_at`,
+/// `bucket`, `item`, or a dotted path into the metadata of the items like
+/// `custom.project_id`
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum QueryField {
+  Bucket,
+  Item,
+
+  /// The size of the item in bytes
+  Size,
+
+  /// The unix timestamp of the last time the content of the item was written
+  UploadedAt,
+
+  /// The keys leading to a value of the metadata
+  Metadata(Vec<String>),
+}
+
+impl std::str::FromStr for QueryField {
+  type Err = ParseError;
+
+  fn from_str(s: &str) -> Result<Self> {
+    let field = match s {
+      "bucket" => QueryField::Bucket,
+      "item" => QueryField::Item,
+      "size" => QueryField::Size,
+      "uploaded_at" => QueryField::UploadedAt,
+      path => {
+        let keys: Vec<String> = path.split('.').map(str::to_owned).collect();
+
+        if keys
+          .iter()
+          .any(|key| key.is_empty() || key.contains(['"', '\\']))
+        {
+          return Err(ParseError::InvalidQueryField);
+        }
+
+        QueryField::Metadata(keys)
+      }
+    };
+
+    Ok(field)
+  }
+}
+
+impl TryFrom<String> for QueryField {
+  type Error = ParseError;
+
+  fn try_from(value: String) -> Result<Self> {
+    value.parse()
+  }
+}
+
+impl From<QueryField> for String {
+  fn from(value: QueryField) -> Self {
+    value.to_string()
+  }
+}
+
+impl std::fmt::Display for QueryField {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      QueryField::Bucket => write!(f, "bucket"),
+      QueryField::Item => write!(f, "item"),
+      QueryField::Size => write!(f, "size"),
+      QueryField::UploadedAt => write!(f, "uploaded_at"),
+      QueryField::Metadata(keys) => write!(f, "{}", keys.join(".")),
+    }
+  }
+}
+
+/// An item selected by a [Query]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct QueryHit {
+  pub path: StoragePath,
+
+  /// The size of the item in bytes
+  pub size: u64,
+
+  /// The unix timestamp of the last time the content of the item was written
+  pub uploaded_at: i64,
+
+  pub metadata: Option<serde_json::Value>,
+}
+
+/// A page of the items selected by a [Query]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct QueryPage {
+  pub items: Vec<QueryHit>,
+
+  /// Set when more items match the query, pass it as the `after` of the query
+  /// to get the next page
+  pub next: Option<StoragePath>,
+}
@@ -0,0 +1,29 @@
+/// Everything the server knows about an item, returned by
+/// `GET /v1/{bucket}/{item}/stat`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Stat {
+  pub path: crate::StoragePath,
+  pub alias: String,
+  pub content_type: String,
+
+  /// The size of the item in bytes
+  pub size: u64,
+
+  /// The hex encoded SHA-256 of the content, unknown for the items uploaded
+  /// before it was recorded
+  pub checksum: Option<String>,
+
+  /// The unix timestamp of the first upload of the item
+  pub created_at: i64,
+
+  /// The unix timestamp of the last time the content of the item was written
+  pub modified_at: i64,
+
+  pub uploader: Option<String>,
+  pub filename: Option<String>,
+  pub custom: Option<serde_json::Value>,
+
+  /// The revision of the metadata, see the `If-Match` header of the metadata
+  /// endpoints
+  pub revision: u64,
+}
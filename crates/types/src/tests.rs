@@ -0,0 +1,71 @@
+use crate::*;
+
+#[test]
+fn test_names() {
+  assert!(BucketName::new("invoices-2024_01").is_ok());
+  assert!(ItemName::new("report.v2.pdf").is_ok());
+
+  for name in ["", ".hidden", "..", "a/b", "é.md", &"a".repeat(201)] {
+    assert_eq!(BucketName::new(name), Err(ParseError::InvalidBucketName));
+    assert_eq!(ItemName::new(name), Err(ParseError::InvalidItemName));
+  }
+
+  // the bucket can't hold metadata files, only the items can clash with them
+  assert!(BucketName::new("report.metadata.json").is_ok());
+  for extension in METADATA_EXTENSIONS {
+    let name = format!("report{extension}");
+    assert_eq!(ItemName::new(name), Err(ParseError::InvalidItemName));
+  }
+}
+
+#[test]
+fn test_storage_path() {
+  let path: StoragePath = "invoices/report.pdf".parse().unwrap();
+  assert_eq!(path.bucket.as_str(), "invoices");
+  assert_eq!(path.item.as_str(), "report.pdf");
+  assert_eq!(path.to_string(), "invoices/report.pdf");
+
+  let cases = [
+    ("", ParseError::MissingBucketName),
+    ("/report.pdf", ParseError::MissingBucketName),
+    ("invoices", ParseError::MissingItemName),
+    ("invoices/", ParseError::MissingItemName),
+    ("invoices/2024/report.pdf", ParseError::TrailingPathSegments),
+    ("../report.pdf", ParseError::InvalidBucketName),
+    ("invoices/..", ParseError::InvalidItemName),
+  ];
+
+  for (path, error) in cases {
+    assert_eq!(path.parse::<StoragePath>(), Err(error), "{path}");
+  }
+}
+
+#[test]
+fn test_serde() {
+  let path: StoragePath = serde_json::from_str(r#""invoices/report.pdf""#).unwrap();
+  assert_eq!(
+    serde_json::to_string(&path).unwrap(),
+    r#""invoices/report.pdf""#
+  );
+  assert!(serde_json::from_str::<StoragePath>(r#""invoices/.hidden""#).is_err());
+  assert!(serde_json::from_str::<BucketName>(r#""../etc""#).is_err());
+}
+
+#[test]
+fn test_problem() {
+  let problem = Problem {
+    status: 404,
+    title: "Not Found".to_owned(),
+    detail: Some("item not found".to_owned()),
+  };
+  let json = serde_json::to_string(&problem).unwrap();
+  assert_eq!(serde_json::from_str::<Problem>(&json).unwrap(), problem);
+  assert_eq!(problem.to_string(), "404 Not Found: item not found");
+
+  // the detail is left out of the internal errors
+  let problem: Problem =
+    serde_json::from_str(r#"{ "status": 500, "title": "Internal Server Error" }"#).unwrap();
+  assert_eq!(problem.detail, None);
+  assert_eq!(problem.to_string(), "500 Internal Server Error");
+  assert!(!serde_json::to_string(&problem).unwrap().contains("detail"));
+}
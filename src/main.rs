@@ -1,16 +1,95 @@
 use shcs::server::launch_server;
+use shcs::server::ServerConfig;
+use shcs::storage::MetadataFormat;
+
+/// The configuration file used when none is supplied as the first argument or
+/// through the `SHCS_CONFIG` environment variable.
+const DEFAULT_CONFIG_PATH: &str = "shcs.toml";
 
 #[actix_web::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-  dotenvy::dotenv().expect(".env error");
-  let port = dotenvy::var("port")
-    .expect("failed to get env port")
-    .parse()
-    .expect("invalid port number");
+  // the .env file is optional, it only offers a way to set the SHCS_*
+  // overrides of the configuration file
+  let _ = dotenvy::dotenv();
+
+  let mut args = std::env::args().skip(1).peekable();
+
+  // `shcs migrate-metadata <format> [config]` converts the metadata files of
+  // the root, and `shcs rotate-keys [config]` wraps the data keys of its
+  // encrypted files with the current master key, instead of starting the server
+  let mut migration = None;
+  let mut rotation = false;
+
+  match args.peek().map(String::as_str) {
+    Some("migrate-metadata") => {
+      args.next();
+
+      migration = Some(args.next().map(|format| format.parse::<MetadataFormat>()));
+    }
+    Some("rotate-keys") => {
+      args.next();
+
+      rotation = true;
+    }
+    _ => {}
+  }
+
+  let path = args
+    .next()
+    .or_else(|| dotenvy::var("SHCS_CONFIG").ok())
+    .unwrap_or_else(|| DEFAULT_CONFIG_PATH.to_owned());
+
+  let config = match ServerConfig::load(&path) {
+    Ok(config) => config,
+    Err(e) => {
+      eprintln!("ERROR: {e}");
+      std::process::exit(1);
+    }
+  };
+
+  // the encrypted files can only be read, and written, with the keys
+  shcs::storage::set_keyring(config.keyring()?)?;
+
+  if rotation {
+    if config.encryption.is_none() {
+      eprintln!("ERROR: the configuration has no [encryption] section");
+      std::process::exit(1);
+    }
+
+    let rotated = shcs::storage::rotate_keys(&config.root)?;
+    println!("wrapped {rotated} data keys with the current master key");
+
+    return Ok(());
+  }
+
+  if let Some(format) = migration {
+    let format = match format {
+      Some(Ok(format)) => format,
+      Some(Err(e)) => {
+        eprintln!("ERROR: {e}");
+        std::process::exit(1);
+      }
+      None => {
+        eprintln!("usage: shcs migrate-metadata <yaml|json|messagepack|cbor> [config]");
+        std::process::exit(1);
+      }
+    };
+
+    let converted = shcs::storage::migrate_metadata(&config.root, format)?;
+    println!("converted {converted} metadata files to {format}");
+
+    return Ok(());
+  }
+
+  for address in &config.listen {
+    println!("starting server at http://{address}");
+  }
 
-  println!("starting server at http://localhost:{port}");
+  for address in config.tls.iter().flat_map(|tls| &tls.listen) {
+    println!("starting server at https://{address}");
+  }
 
-  launch_server(port, "buckets").await?;
+  launch_server(config).await?;
 
   Ok(())
 }